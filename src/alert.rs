@@ -0,0 +1,407 @@
+use crate::history::{AlertEvent, AlertState};
+use crate::process::MemoryMetric;
+use crate::tree::ProcessTreeNode;
+use chrono::Local;
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use sysinfo::{ProcessStatus, System};
+
+/// アラートで監視する値（一致したプロセス全体から計算）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertMetric {
+    TotalMemoryMb,   // メモリの合計（MB、--memory-metric の指標）
+    ProcessMemoryMb, // 最もメモリの多いプロセスのメモリ（MB）
+    Count,           // プロセス数
+    Zombies,         // ゾンビプロセスの数
+    Cpu,             // CPU 使用率の合計（%）
+    ProcessCpu,      // 最も CPU 使用率の高いプロセスの CPU 使用率（%）
+}
+
+impl AlertMetric {
+    const ALL: [AlertMetric; 6] = [
+        AlertMetric::TotalMemoryMb,
+        AlertMetric::ProcessMemoryMb,
+        AlertMetric::Count,
+        AlertMetric::Zombies,
+        AlertMetric::Cpu,
+        AlertMetric::ProcessCpu,
+    ];
+
+    /// ルールの記述・データベースに保存する名前
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertMetric::TotalMemoryMb => "total_memory_mb",
+            AlertMetric::ProcessMemoryMb => "process_memory_mb",
+            AlertMetric::Count => "count",
+            AlertMetric::Zombies => "zombies",
+            AlertMetric::Cpu => "cpu",
+            AlertMetric::ProcessCpu => "process_cpu",
+        }
+    }
+
+    /// 抽出したプロセスから値を計算
+    fn value(&self, nodes: &[ProcessTreeNode], memory_metric: &MemoryMetric) -> f64 {
+        let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+        match self {
+            AlertMetric::TotalMemoryMb => {
+                mb(nodes.iter().map(|n| n.memory_by(memory_metric)).sum())
+            }
+            AlertMetric::ProcessMemoryMb => mb(nodes
+                .iter()
+                .map(|n| n.memory_by(memory_metric))
+                .max()
+                .unwrap_or(0)),
+            AlertMetric::Count => nodes.len() as f64,
+            AlertMetric::Zombies => nodes
+                .iter()
+                .filter(|n| n.status == ProcessStatus::Zombie)
+                .count() as f64,
+            AlertMetric::Cpu => nodes.iter().map(|n| n.cpu_usage as f64).sum(),
+            AlertMetric::ProcessCpu => nodes.iter().map(|n| n.cpu_usage as f64).fold(0.0, f64::max),
+        }
+    }
+}
+
+/// 比較演算子
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    /// 2文字の演算子を先に判定する
+    const ALL: [(&'static str, Comparison); 4] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+        }
+    }
+
+    fn matches(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+        }
+    }
+}
+
+/// アラートのルール（例: `total_memory_mb > 2048`, `cpu > 80 for 3`）
+///
+/// 条件を満たした状態が ticks 回続くと発火し、満たさない状態が ticks 回続くと解除する
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    pub ticks: u32,
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.metric.as_str(),
+            self.comparison.as_str(),
+            self.threshold
+        )?;
+        if self.ticks > 1 {
+            write!(f, " for {}", self.ticks)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    /// `<metric> <op> <value> [for <ticks>]` を解析（演算子の前後の空白は省略可）
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, operator, comparison) = Comparison::ALL
+            .iter()
+            .filter_map(|&(operator, comparison)| {
+                s.find(operator).map(|i| (i, operator, comparison))
+            })
+            .min_by_key(|&(i, operator, _)| (i, std::cmp::Reverse(operator.len())))
+            .ok_or_else(|| format!("missing comparison (>, >=, <, <=) in '{}'", s))?;
+
+        let metric_name = s[..position].trim();
+        let metric = AlertMetric::ALL
+            .into_iter()
+            .find(|m| m.as_str() == metric_name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = AlertMetric::ALL.iter().map(|m| m.as_str()).collect();
+                format!(
+                    "unknown metric '{}' (expected one of: {})",
+                    metric_name,
+                    names.join(", ")
+                )
+            })?;
+
+        let mut rest = s[position + operator.len()..].split_whitespace();
+        let threshold = rest
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| format!("missing or invalid threshold in '{}'", s))?;
+        let ticks = match (rest.next(), rest.next(), rest.next()) {
+            (None, _, _) => 1,
+            (Some("for"), Some(ticks), None) => ticks
+                .parse::<u32>()
+                .ok()
+                .filter(|&t| t > 0)
+                .ok_or_else(|| format!("invalid tick count '{}' in '{}'", ticks, s))?,
+            _ => {
+                return Err(format!(
+                    "unexpected text after threshold in '{}' (use: for <ticks>)",
+                    s
+                ));
+            }
+        };
+
+        Ok(Self {
+            metric,
+            comparison,
+            threshold,
+            ticks,
+        })
+    }
+}
+
+/// clap 用のパーサ
+pub fn parse_rule(s: &str) -> Result<AlertRule, String> {
+    s.parse()
+}
+
+/// ルールごとの状態
+struct RuleState {
+    rule: AlertRule,
+    firing: bool,
+    streak: u32, // 現在の状態と異なる判定が続いた回数
+    value: f64,  // 直近の値
+}
+
+/// 更新ごとにルールを評価し、発火・解除のイベントを生成する
+pub struct AlertEvaluator {
+    rules: Vec<RuleState>,
+    name_filter: String,
+}
+
+impl AlertEvaluator {
+    pub fn new(rules: &[AlertRule], name_filter: &str) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .map(|rule| RuleState {
+                    rule: rule.clone(),
+                    firing: false,
+                    streak: 0,
+                    value: 0.0,
+                })
+                .collect(),
+            name_filter: name_filter.to_string(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 抽出したプロセスでルールを評価し、状態が変わったルールのイベントを返す
+    pub fn evaluate(
+        &mut self,
+        nodes: &[ProcessTreeNode],
+        memory_metric: &MemoryMetric,
+    ) -> Vec<AlertEvent> {
+        let timestamp = Local::now();
+        let mut events = vec![];
+
+        for state in &mut self.rules {
+            state.value = state.rule.metric.value(nodes, memory_metric);
+            let breaching = state
+                .rule
+                .comparison
+                .matches(state.value, state.rule.threshold);
+
+            if breaching == state.firing {
+                state.streak = 0;
+                continue;
+            }
+            state.streak += 1;
+            if state.streak < state.rule.ticks {
+                continue;
+            }
+
+            state.firing = breaching;
+            state.streak = 0;
+            events.push(AlertEvent {
+                timestamp,
+                state: if breaching {
+                    AlertState::Firing
+                } else {
+                    AlertState::Resolved
+                },
+                rule: state.rule.to_string(),
+                metric: state.rule.metric.as_str().to_string(),
+                value: state.value,
+                threshold: state.rule.threshold,
+                name_filter: self.name_filter.clone(),
+            });
+        }
+
+        events
+    }
+
+    /// 発火中のルールと直近の値
+    pub fn firing(&self) -> impl Iterator<Item = (&AlertRule, f64)> {
+        self.rules
+            .iter()
+            .filter(|state| state.firing)
+            .map(|state| (&state.rule, state.value))
+    }
+
+    /// ルールの数
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+/// 発火・解除時のコマンドを実行（sh -c、完了は待たない）
+///
+/// アラートの内容は PROCESS_MONITOR_ALERT_* 環境変数で渡す。
+/// TUI では画面を壊さないよう出力を捨てる（inherit_output = false）
+pub fn run_hook(command: &str, event: &AlertEvent, inherit_output: bool) -> std::io::Result<()> {
+    let output = || {
+        if inherit_output {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        }
+    };
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(output())
+        .stderr(output())
+        .env("PROCESS_MONITOR_ALERT_STATE", event.state.as_str())
+        .env("PROCESS_MONITOR_ALERT_RULE", &event.rule)
+        .env("PROCESS_MONITOR_ALERT_METRIC", &event.metric)
+        .env("PROCESS_MONITOR_ALERT_VALUE", format!("{:.2}", event.value))
+        .env(
+            "PROCESS_MONITOR_ALERT_THRESHOLD",
+            event.threshold.to_string(),
+        )
+        .env("PROCESS_MONITOR_ALERT_NAME", &event.name_filter)
+        .env(
+            "PROCESS_MONITOR_ALERT_TIMESTAMP",
+            event.timestamp.to_rfc3339(),
+        )
+        .env(
+            "PROCESS_MONITOR_ALERT_HOST",
+            System::host_name().unwrap_or_default(),
+        )
+        .spawn()?;
+
+    // 終了したコマンドがゾンビとして残らないよう別スレッドで回収
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// 発火・解除の1行の説明
+pub fn describe(event: &AlertEvent) -> String {
+    format!(
+        "Alert {} for '{}': {} (value: {:.2})",
+        event.state.as_str(),
+        event.name_filter,
+        event.rule,
+        event.value
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(pid: u32, memory_mb: u64, status: ProcessStatus) -> ProcessTreeNode {
+        ProcessTreeNode {
+            pid,
+            parent_pid: None,
+            process_name: "php-fpm".to_string(),
+            cpu_usage: 10.0,
+            memory_bytes: memory_mb * 1024 * 1024,
+            smaps: None,
+            thread_count: 1,
+            status,
+            depth: 0,
+            is_last_child: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule: AlertRule = "cpu > 80 for 3".parse().unwrap();
+        assert_eq!(rule.metric, AlertMetric::Cpu);
+        assert_eq!(rule.comparison, Comparison::Greater);
+        assert_eq!(rule.threshold, 80.0);
+        assert_eq!(rule.ticks, 3);
+        assert_eq!(rule.to_string(), "cpu > 80 for 3");
+
+        let rule: AlertRule = "count<=2".parse().unwrap();
+        assert_eq!(rule.comparison, Comparison::LessOrEqual);
+        assert_eq!(rule.to_string(), "count <= 2");
+
+        assert!("memory > 1".parse::<AlertRule>().is_err());
+        assert!("count 2".parse::<AlertRule>().is_err());
+        assert!("count < 2 for 0".parse::<AlertRule>().is_err());
+        assert!("count < 2 during 3".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn test_evaluator_hysteresis() {
+        let rules = [
+            "total_memory_mb > 100 for 2".parse().unwrap(),
+            "zombies > 0".parse().unwrap(),
+        ];
+        let mut evaluator = AlertEvaluator::new(&rules, "php-fpm");
+        let high = [
+            node(1, 80, ProcessStatus::Sleep),
+            node(2, 40, ProcessStatus::Sleep),
+        ];
+        let low = [node(1, 80, ProcessStatus::Sleep)];
+
+        // 1回目の超過では発火しない
+        assert!(evaluator.evaluate(&high, &MemoryMetric::Rss).is_empty());
+        let events = evaluator.evaluate(&high, &MemoryMetric::Rss);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Firing);
+        assert_eq!(events[0].value, 120.0);
+        assert_eq!(evaluator.firing().count(), 1);
+
+        // 1回だけ下回っても解除しない。2回続くと解除
+        assert!(evaluator.evaluate(&low, &MemoryMetric::Rss).is_empty());
+        assert!(evaluator.evaluate(&high, &MemoryMetric::Rss).is_empty());
+        assert!(evaluator.evaluate(&low, &MemoryMetric::Rss).is_empty());
+        let events = evaluator.evaluate(&low, &MemoryMetric::Rss);
+        assert_eq!(events[0].state, AlertState::Resolved);
+
+        // for を省略したルールは1回で発火
+        let events = evaluator.evaluate(&[node(3, 1, ProcessStatus::Zombie)], &MemoryMetric::Rss);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "zombies > 0");
+    }
+}
@@ -1,6 +1,15 @@
 use crate::formatter;
-use crate::history::{ProcessHistory, ProcessSnapshot};
-use chrono::DateTime;
+use crate::headroom::{self, HostHeadroom};
+use crate::history::{BucketStats, ProcessHistory, ProcessSnapshot, RecordFilter, Session};
+use crate::leak::{self, LeakCriteria};
+use crate::lifecycle;
+use crate::recommend::{self, HostMemory, Recommendation, RecommendOptions};
+use crate::session;
+use crate::matcher::NameMatcher;
+use chrono::{
+    DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone,
+};
 use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 
@@ -28,6 +37,11 @@ pub struct MemoryStats {
     pub min_bytes: u64,
     pub avg_bytes: f64,
     pub max_bytes: u64,
+    pub p50_bytes: u64,
+    pub p90_bytes: u64,
+    pub p95_bytes: u64,
+    pub p99_bytes: u64,
+    pub stddev_bytes: f64,
 }
 
 /// CPU統計情報
@@ -36,6 +50,11 @@ pub struct CpuStats {
     pub min_percent: f32,
     pub avg_percent: f64,
     pub max_percent: f32,
+    pub p50_percent: f32,
+    pub p90_percent: f32,
+    pub p95_percent: f32,
+    pub p99_percent: f32,
+    pub stddev_percent: f64,
 }
 
 /// プロセス数統計情報
@@ -56,37 +75,214 @@ pub struct PeakDetail {
     pub process_name: String,
 }
 
+/// --group-by host で記録元のない（自ホストで記録した）記録に付けるキー
+const LOCAL_HOST: &str = "(local)";
+
+/// グループ化の単位
+#[derive(Clone, Debug, clap::ValueEnum)]
+pub enum GroupBy {
+    Name,
+    Pid,
+    Host, // history merge で付与した記録元（自ホストの記録は "(local)"）
+}
+
+/// グループのソート順
+#[derive(Clone, Debug, clap::ValueEnum)]
+pub enum GroupSort {
+    Memory,   // p95 メモリ（降順）
+    Cpu,      // 平均CPU（降順）
+    Records,  // レコード数（降順）
+    Lifespan, // 記録期間（降順）
+    Key,      // プロセス名 / PID（昇順）
+}
+
+/// グループごとの分析結果
+#[derive(Serialize)]
+pub struct GroupAnalysis {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    pub process_names: Vec<String>,
+    pub lifespan_secs: i64,
+    #[serde(flatten)]
+    pub analysis: AnalysisResult,
+}
+
+/// JSON 出力用のグループ別レポート
+#[derive(Serialize)]
+struct GroupReport<'a> {
+    group_by: &'a str,
+    total_groups: usize,
+    groups: &'a [GroupAnalysis],
+}
+
+/// JSON 出力用の時間窓レポート
+#[derive(Serialize)]
+struct BucketReport<'a> {
+    bucket: &'a str,
+    bucket_secs: i64,
+    source: &'a str,
+    buckets: &'a [BucketStats],
+}
+
+/// JSON 出力用のレポート（分析結果 + 推奨値）
+#[derive(Serialize)]
+struct AnalysisReport<'a> {
+    #[serde(flatten)]
+    analysis: &'a AnalysisResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommendation: Option<&'a Recommendation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_headroom: Option<&'a HostHeadroom>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<&'a Session>,
+}
+
+/// analyze サブコマンドの引数
+pub struct AnalyzeOptions<'a> {
+    pub db_path: &'a str,
+    pub name: Option<&'a NameMatcher>,
+    pub from: Option<&'a str>,
+    pub to: Option<&'a str>,
+    pub last_secs: Option<i64>,
+    pub session: Option<i64>,
+    pub host: Option<&'a str>,
+    pub format: &'a OutputFormat,
+    pub recommend: Option<&'a RecommendOptions>,
+    pub group_by: Option<&'a GroupBy>,
+    pub group_sort: &'a GroupSort,
+    pub top: Option<usize>,
+    pub bucket: Option<&'a str>,
+    pub leaks: Option<LeakCriteria>,
+    pub events: bool,
+}
+
 /// analyze サブコマンドのエントリーポイント
-pub fn run_analyze(
-    db_path: &str,
-    name: Option<&str>,
-    from: Option<&str>,
-    to: Option<&str>,
-    format: &OutputFormat,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // 1. データベースファイルの存在確認
-    if !std::path::Path::new(db_path).exists() {
-        return Err(format!("Database file not found: {}", db_path).into());
+pub fn run_analyze(options: AnalyzeOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let AnalyzeOptions {
+        db_path,
+        name,
+        from,
+        to,
+        last_secs,
+        session: session_id,
+        host,
+        format,
+        recommend: recommend_options,
+        group_by,
+        group_sort,
+        top,
+        bucket,
+        leaks,
+        events,
+    } = options;
+
+    // 1. 時刻指定の解釈（--last は現在から遡った開始時刻）
+    let from = match last_secs {
+        Some(secs) => Some(format_timestamp(&(Local::now() - Duration::seconds(secs)))),
+        None => from.map(resolve_timestamp).transpose()?,
+    };
+    let to = to.map(resolve_timestamp).transpose()?;
+    let (from, to) = (from.as_deref(), to.as_deref());
+
+    // 2. データベースを開く（存在確認付き）
+    let history = open_history(db_path)?;
+
+    // セッション指定時はそのセッションの記録のみ
+    let session = match session_id {
+        Some(id) => Some(
+            history
+                .get_session(id)
+                .map_err(|e| format!("Database query failed: {}", e))?
+                .ok_or_else(|| {
+                    format!("Session #{} not found. List sessions with: history sessions", id)
+                })?,
+        ),
+        None => None,
+    };
+    if let OutputFormat::Table = format {
+        if let Some(ref session) = session {
+            println!("Session: {}", session::describe(session));
+        }
+        if let Some(host) = host {
+            println!("Host: {}", host);
+        }
+        if session.is_some() || host.is_some() {
+            println!();
+        }
     }
-
-    // 2. タイムスタンプの検証
-    if let Some(from_time) = from {
-        validate_timestamp(from_time)?;
+    let filter = RecordFilter {
+        from,
+        to,
+        name,
+        session: session_id,
+        host,
+    };
+
+    // 時間窓ごとの集計（--bucket 指定時、集計は SQL 側で実行）
+    if let Some(bucket) = bucket {
+        let bucket_secs = parse_duration_secs(bucket)?;
+        let utc_offset_secs = Local::now().offset().fix().local_minus_utc() as i64;
+        let (buckets, rollup) = history
+            .query_buckets(&filter, bucket_secs, utc_offset_secs)
+            .map_err(|e| format!("Database query failed: {}", e))?;
+
+        if buckets.is_empty() {
+            return Err("No records found matching the criteria".into());
+        }
+        // 集計に使ったテーブル（集計済みテーブルの場合、その後の生データも含む）
+        let source = rollup.map_or("process_snapshots", |r| r.table());
+
+        match format {
+            OutputFormat::Table => print_bucket_table(&buckets, bucket, source, name),
+            OutputFormat::Json => {
+                let report = BucketReport {
+                    bucket,
+                    bucket_secs,
+                    source,
+                    buckets: &buckets,
+                };
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            OutputFormat::Csv => print_bucket_csv(&buckets),
+        }
+        return Ok(());
     }
-    if let Some(to_time) = to {
-        validate_timestamp(to_time)?;
+
+    if let OutputFormat::Csv = format {
+        return Err("CSV output is only supported with --bucket".into());
     }
 
-    // 3. データベースを開く
-    let history = ProcessHistory::new(db_path)
-        .map_err(|e| format!("Failed to open database: {}", e))?;
+    // 起動・終了イベントのレポート（--events 指定時）
+    if events {
+        let records = history
+            .query_events(&filter)
+            .map_err(|e| format!("Database query failed: {}", e))?;
+
+        let parse = |ts: &str| DateTime::parse_from_rfc3339(ts).map(|t| t.with_timezone(&Local));
+        let range_from = from.map(parse).transpose()?;
+        // 開始のみ指定された場合は現在までを期間とする
+        let range_to = match to {
+            Some(to_time) => Some(parse(to_time)?),
+            None => range_from.map(|_| Local::now()),
+        };
 
-    // 4. データをクエリ
+        let report = lifecycle::build_report(&records, range_from, range_to);
+
+        match format {
+            OutputFormat::Table => lifecycle::print_table(&report, name, 20),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            OutputFormat::Csv => unreachable!("CSV is rejected before querying"),
+        }
+        return Ok(());
+    }
+
+    // 3. データをクエリ
     let snapshots = history
-        .query_snapshots(from, to, name)
+        .query_snapshots(&filter)
         .map_err(|e| format!("Database query failed: {}", e))?;
 
-    // 5. データが空でないことを確認
+    // 4. データが空でないことを確認
     if snapshots.is_empty() {
         return Err(
             "No records found matching the criteria. Try:\n  \
@@ -97,27 +293,203 @@ pub fn run_analyze(
         );
     }
 
-    // 6. 統計を計算
+    // メモリリーク検出（--leaks 指定時）
+    if let Some(criteria) = leaks {
+        // 起動・終了イベントが記録されていれば PID の再利用の判定に使う
+        let events = history
+            .has_events()
+            .and_then(|recorded| recorded.then(|| history.query_events(&filter)).transpose())
+            .map_err(|e| format!("Database query failed: {}", e))?;
+        let report = leak::detect_leaks(&snapshots, events.as_deref(), criteria);
+        match format {
+            OutputFormat::Table => leak::print_table(&report, name),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            OutputFormat::Csv => unreachable!("CSV is rejected before querying"),
+        }
+        return Ok(());
+    }
+
+    // グループ別分析（--group-by 指定時）
+    if let Some(group_by) = group_by {
+        let mut groups = analyze_groups(&snapshots, group_by)?;
+        let total_groups = groups.len();
+        sort_groups(&mut groups, group_sort);
+        if let Some(n) = top {
+            groups.truncate(n);
+        }
+
+        match format {
+            OutputFormat::Table => print_group_table(&groups, group_by, total_groups, name),
+            OutputFormat::Json => {
+                let report = GroupReport {
+                    group_by: match group_by {
+                        GroupBy::Name => "name",
+                        GroupBy::Pid => "pid",
+                        GroupBy::Host => "host",
+                    },
+                    total_groups,
+                    groups: &groups,
+                };
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            OutputFormat::Csv => unreachable!("CSV is rejected before querying"),
+        }
+        return Ok(());
+    }
+
+    // 5. 統計を計算
     let analysis = AnalysisResult::from_snapshots(&snapshots)?;
 
-    // 7. 出力
+    // 6. 推奨値の算出（--recommend 指定時のみ）
+    let recommendation = match recommend_options {
+        Some(opts) => Some(recommend::recommend(&snapshots, HostMemory::current(), opts)?),
+        None => None,
+    };
+
+    // 7. ホスト全体の余裕（system_snapshots が記録されている場合のみ）
+    let system_snapshots = history
+        .query_system_snapshots(&filter)
+        .map_err(|e| format!("Database query failed: {}", e))?;
+    let host_headroom =
+        HostHeadroom::from_snapshots(&system_snapshots, analysis.memory_stats.p95_bytes);
+
+    // 8. 出力
     match format {
-        OutputFormat::Table => print_table(&analysis, name),
-        OutputFormat::Json => print_json(&analysis)?,
+        OutputFormat::Table => {
+            print_table(&analysis, name);
+            if let Some(ref headroom) = host_headroom {
+                headroom::print_table(headroom);
+            }
+            if let Some(ref rec) = recommendation {
+                recommend::print_table(rec);
+            }
+        }
+        OutputFormat::Json => print_json(&AnalysisReport {
+            analysis: &analysis,
+            recommendation: recommendation.as_ref(),
+            host_headroom: host_headroom.as_ref(),
+            session: session.as_ref(),
+        })?,
+        OutputFormat::Csv => unreachable!("CSV is rejected before querying"),
     }
 
     Ok(())
 }
 
-/// タイムスタンプの形式を検証（ISO 8601 / RFC3339）
-fn validate_timestamp(ts: &str) -> Result<(), Box<dyn std::error::Error>> {
-    DateTime::parse_from_rfc3339(ts).map_err(|_| {
+/// 既存の履歴データベースを開く（存在しなければエラー）
+pub fn open_history(db_path: &str) -> Result<ProcessHistory, Box<dyn std::error::Error>> {
+    if !std::path::Path::new(db_path).exists() {
+        return Err(format!("Database file not found: {}", db_path).into());
+    }
+
+    let history = ProcessHistory::new(db_path)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    Ok(history)
+}
+
+/// 時刻指定を解釈し、記録と同じ形式（ローカルオフセット付き RFC3339）に正規化
+///
+/// 受け付ける形式:
+/// - RFC3339: `2026-01-05T14:00:00+09:00`
+/// - オフセットなしのローカル時刻: `2026-01-05T14:00`, `2026-01-05 14:00:00`, `2026-01-05`
+/// - 相対指定: `now`, `-30m`, `-2h`, `today`, `yesterday`, `today 09:00`, `yesterday 09:00`
+pub fn resolve_timestamp(expr: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let time = parse_time_expr(expr, Local::now()).map_err(|_| {
         format!(
-            "Invalid timestamp format: '{}'. Expected ISO 8601 (e.g., 2026-01-05T14:00:00+09:00)",
-            ts
+            "Invalid timestamp: '{}'. Expected ISO 8601 (e.g., 2026-01-05T14:00:00+09:00), \
+             a local time (2026-01-05 14:00), or a relative time (-30m, today, yesterday 09:00)",
+            expr
         )
     })?;
-    Ok(())
+    Ok(format_timestamp(&time))
+}
+
+/// 記録と同じ形式で時刻を文字列化（文字列比較で範囲指定できる形）
+///
+/// 記録は秒未満を含むため、範囲の端も秒未満を切り捨てない（`--to now` の直前の記録を落とさない）
+pub fn format_timestamp(time: &DateTime<Local>) -> String {
+    time.to_rfc3339()
+}
+
+/// 時刻指定を基準時刻 now に対して解釈
+fn parse_time_expr(expr: &str, now: DateTime<Local>) -> Result<DateTime<Local>, ()> {
+    let expr = expr.trim();
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(expr) {
+        return Ok(time.with_timezone(&Local));
+    }
+    if expr == "now" {
+        return Ok(now);
+    }
+    if let Some(ago) = expr.strip_prefix('-') {
+        let secs = parse_duration_secs(ago).map_err(|_| ())?;
+        return Ok(now - Duration::seconds(secs));
+    }
+
+    // today / yesterday（時刻の指定があればその時刻、なければ 0:00）
+    let (day, rest) = expr.split_once(' ').unwrap_or((expr, ""));
+    let date = match day {
+        "today" => Some(now.date_naive()),
+        "yesterday" => now.date_naive().pred_opt(),
+        _ => None,
+    };
+    if let Some(date) = date {
+        let time = if rest.is_empty() {
+            NaiveTime::MIN
+        } else {
+            parse_naive_time(rest.trim())?
+        };
+        return local_from_naive(date.and_time(time));
+    }
+
+    // オフセットなしのローカル時刻
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(expr, format) {
+            return local_from_naive(naive);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(expr, "%Y-%m-%d") {
+        return local_from_naive(date.and_time(NaiveTime::MIN));
+    }
+
+    Err(())
+}
+
+fn parse_naive_time(s: &str) -> Result<NaiveTime, ()> {
+    NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+        .map_err(|_| ())
+}
+
+/// ローカル時刻を解決（夏時間の重複は早い方、存在しない時刻はエラー）
+fn local_from_naive(naive: NaiveDateTime) -> Result<DateTime<Local>, ()> {
+    Local.from_local_datetime(&naive).earliest().ok_or(())
+}
+
+/// 期間表記（例: 30s, 5m, 1h, 1d）を秒数に変換
+pub fn parse_duration_secs(s: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration: '{}'. Expected a number followed by s, m, h or d (e.g., 5m, 1h)",
+            s
+        )
+    };
+
+    let unit_pos = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (value, unit) = s.split_at(unit_pos);
+    let value: i64 = value.parse().map_err(|_| invalid())?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(invalid()),
+    };
+
+    if value <= 0 {
+        return Err(invalid());
+    }
+    Ok(value * multiplier)
 }
 
 /// 出力フォーマットの種類
@@ -125,6 +497,7 @@ fn validate_timestamp(ts: &str) -> Result<(), Box<dyn std::error::Error>> {
 pub enum OutputFormat {
     Table,
     Json,
+    Csv,
 }
 
 impl AnalysisResult {
@@ -143,25 +516,33 @@ impl AnalysisResult {
         };
 
         // メモリ統計
-        let memory_values: Vec<u64> = snapshots.iter().map(|s| s.memory_bytes).collect();
+        let mut memory_values: Vec<u64> = snapshots.iter().map(|s| s.memory_bytes).collect();
+        memory_values.sort_unstable();
+        let memory_avg = memory_values.iter().sum::<u64>() as f64 / memory_values.len() as f64;
         let memory_stats = MemoryStats {
-            min_bytes: *memory_values.iter().min().unwrap(),
-            avg_bytes: memory_values.iter().sum::<u64>() as f64 / memory_values.len() as f64,
-            max_bytes: *memory_values.iter().max().unwrap(),
+            min_bytes: memory_values[0],
+            avg_bytes: memory_avg,
+            max_bytes: memory_values[memory_values.len() - 1],
+            p50_bytes: percentile(&memory_values, 50.0),
+            p90_bytes: percentile(&memory_values, 90.0),
+            p95_bytes: percentile(&memory_values, 95.0),
+            p99_bytes: percentile(&memory_values, 99.0),
+            stddev_bytes: stddev(memory_values.iter().map(|&v| v as f64), memory_avg),
         };
 
         // CPU統計
-        let cpu_values: Vec<f32> = snapshots.iter().map(|s| s.cpu_usage).collect();
+        let mut cpu_values: Vec<f32> = snapshots.iter().map(|s| s.cpu_usage).collect();
+        cpu_values.sort_by(|a, b| a.total_cmp(b));
+        let cpu_avg = cpu_values.iter().sum::<f32>() as f64 / cpu_values.len() as f64;
         let cpu_stats = CpuStats {
-            min_percent: *cpu_values
-                .iter()
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap(),
-            avg_percent: cpu_values.iter().sum::<f32>() as f64 / cpu_values.len() as f64,
-            max_percent: *cpu_values
-                .iter()
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap(),
+            min_percent: cpu_values[0],
+            avg_percent: cpu_avg,
+            max_percent: cpu_values[cpu_values.len() - 1],
+            p50_percent: percentile(&cpu_values, 50.0),
+            p90_percent: percentile(&cpu_values, 90.0),
+            p95_percent: percentile(&cpu_values, 95.0),
+            p99_percent: percentile(&cpu_values, 99.0),
+            stddev_percent: stddev(cpu_values.iter().map(|&v| v as f64), cpu_avg),
         };
 
         // プロセス数統計（タイムスタンプごとのユニークなPID数）
@@ -170,7 +551,7 @@ impl AnalysisResult {
         for snapshot in snapshots {
             counts_by_time
                 .entry(snapshot.timestamp.to_rfc3339())
-                .or_insert_with(HashSet::new)
+                .or_default()
                 .insert(snapshot.pid);
         }
 
@@ -216,8 +597,91 @@ impl AnalysisResult {
     }
 }
 
+/// スナップショットをプロセス名 / PID ごとに分けて分析
+pub fn analyze_groups(
+    snapshots: &[ProcessSnapshot],
+    group_by: &GroupBy,
+) -> Result<Vec<GroupAnalysis>, Box<dyn std::error::Error>> {
+    // 入力の順序（タイムスタンプ昇順）を保ったまま振り分ける
+    let mut grouped: HashMap<String, Vec<ProcessSnapshot>> = HashMap::new();
+    for snapshot in snapshots {
+        let key = match group_by {
+            GroupBy::Name => snapshot.process_name.clone(),
+            GroupBy::Pid => snapshot.pid.to_string(),
+            GroupBy::Host => snapshot.host.clone().unwrap_or_else(|| LOCAL_HOST.to_string()),
+        };
+        grouped.entry(key).or_default().push(snapshot.clone());
+    }
+
+    grouped
+        .into_iter()
+        .map(|(key, group)| {
+            let first = group.first().unwrap().timestamp;
+            let last = group.last().unwrap().timestamp;
+
+            let mut process_names: Vec<String> = group.iter().map(|s| s.process_name.clone()).collect();
+            process_names.sort();
+            process_names.dedup();
+
+            Ok(GroupAnalysis {
+                pid: match group_by {
+                    GroupBy::Name | GroupBy::Host => None,
+                    GroupBy::Pid => Some(group[0].pid),
+                },
+                key,
+                process_names,
+                lifespan_secs: (last - first).num_seconds(),
+                analysis: AnalysisResult::from_snapshots(&group)?,
+            })
+        })
+        .collect()
+}
+
+/// グループをソート
+fn sort_groups(groups: &mut [GroupAnalysis], sort: &GroupSort) {
+    match sort {
+        GroupSort::Memory => {
+            groups.sort_by_key(|g| std::cmp::Reverse(g.analysis.memory_stats.p95_bytes));
+        }
+        GroupSort::Cpu => {
+            groups.sort_by(|a, b| {
+                b.analysis.cpu_stats.avg_percent.total_cmp(&a.analysis.cpu_stats.avg_percent)
+            });
+        }
+        GroupSort::Records => {
+            groups.sort_by_key(|g| std::cmp::Reverse(g.analysis.total_records));
+        }
+        GroupSort::Lifespan => {
+            groups.sort_by_key(|g| std::cmp::Reverse(g.lifespan_secs));
+        }
+        GroupSort::Key => {
+            // PID は数値として比較
+            groups.sort_by(|a, b| match (a.pid, b.pid) {
+                (Some(pa), Some(pb)) => pa.cmp(&pb),
+                _ => a.key.cmp(&b.key),
+            });
+        }
+    }
+}
+
+/// ソート済みの値からパーセンタイル値を取得（nearest-rank 法）
+pub fn percentile<T: Copy>(sorted: &[T], p: f64) -> T {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// 母標準偏差を計算
+pub fn stddev(values: impl ExactSizeIterator<Item = f64>, mean: f64) -> f64 {
+    let count = values.len();
+    if count == 0 {
+        return 0.0;
+    }
+    let variance = values.map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+    variance.sqrt()
+}
+
 /// テーブル形式で結果を出力
-fn print_table(analysis: &AnalysisResult, process_name_filter: Option<&str>) {
+fn print_table(analysis: &AnalysisResult, process_name_filter: Option<&NameMatcher>) {
     println!("{}", "=".repeat(70));
     println!("Analysis Report");
     println!("{}", "=".repeat(70));
@@ -229,7 +693,7 @@ fn print_table(analysis: &AnalysisResult, process_name_filter: Option<&str>) {
 
     // フィルタ情報
     if let Some(name) = process_name_filter {
-        println!("  Filter: process name contains '{}'", name);
+        println!("  Filter: process name {}", name.describe());
     }
 
     // メモリ統計
@@ -246,12 +710,31 @@ fn print_table(analysis: &AnalysisResult, process_name_filter: Option<&str>) {
         "  Max:  {}",
         formatter::format_bytes(analysis.memory_stats.max_bytes)
     );
+    println!(
+        "  p50:  {}  p90: {}  p95: {}  p99: {}",
+        formatter::format_bytes(analysis.memory_stats.p50_bytes),
+        formatter::format_bytes(analysis.memory_stats.p90_bytes),
+        formatter::format_bytes(analysis.memory_stats.p95_bytes),
+        formatter::format_bytes(analysis.memory_stats.p99_bytes)
+    );
+    println!(
+        "  StdDev: {}",
+        formatter::format_bytes(analysis.memory_stats.stddev_bytes as u64)
+    );
 
     // CPU統計
     println!("\nCPU Statistics:");
     println!("  Min:  {:.2}%", analysis.cpu_stats.min_percent);
     println!("  Avg:  {:.2}%", analysis.cpu_stats.avg_percent);
     println!("  Max:  {:.2}%", analysis.cpu_stats.max_percent);
+    println!(
+        "  p50:  {:.2}%  p90: {:.2}%  p95: {:.2}%  p99: {:.2}%",
+        analysis.cpu_stats.p50_percent,
+        analysis.cpu_stats.p90_percent,
+        analysis.cpu_stats.p95_percent,
+        analysis.cpu_stats.p99_percent
+    );
+    println!("  StdDev: {:.2}%", analysis.cpu_stats.stddev_percent);
 
     // プロセス数
     println!("\nProcess Count:");
@@ -275,9 +758,246 @@ fn print_table(analysis: &AnalysisResult, process_name_filter: Option<&str>) {
     println!("{}", "=".repeat(70));
 }
 
+/// グループ別の結果をテーブル形式で出力
+fn print_group_table(
+    groups: &[GroupAnalysis],
+    group_by: &GroupBy,
+    total_groups: usize,
+    process_name_filter: Option<&NameMatcher>,
+) {
+    println!("{}", "=".repeat(70));
+    match group_by {
+        GroupBy::Name => println!("Analysis Report by Process Name"),
+        GroupBy::Pid => println!("Analysis Report by PID"),
+        GroupBy::Host => println!("Analysis Report by Host"),
+    }
+    println!("{}", "=".repeat(70));
+
+    if let Some(name) = process_name_filter {
+        println!("Filter: process name {}", name.describe());
+    }
+    println!("Groups: {} shown / {} total", groups.len(), total_groups);
+
+    for group in groups {
+        let analysis = &group.analysis;
+        match group_by {
+            GroupBy::Name | GroupBy::Host => println!("\n--- {} ---", group.key),
+            GroupBy::Pid => println!("\n--- PID {} ({}) ---", group.key, group.process_names.join(", ")),
+        }
+        println!(
+            "  Lifespan: {} ({} - {})",
+            formatter::format_duration(group.lifespan_secs),
+            analysis.time_range.from,
+            analysis.time_range.to
+        );
+        println!("  Records:  {}", analysis.total_records);
+        println!(
+            "  Memory:   Min {} / Avg {} / p95 {} / Max {}",
+            formatter::format_bytes(analysis.memory_stats.min_bytes),
+            formatter::format_bytes(analysis.memory_stats.avg_bytes as u64),
+            formatter::format_bytes(analysis.memory_stats.p95_bytes),
+            formatter::format_bytes(analysis.memory_stats.max_bytes)
+        );
+        println!(
+            "  CPU:      Min {:.2}% / Avg {:.2}% / p95 {:.2}% / Max {:.2}%",
+            analysis.cpu_stats.min_percent,
+            analysis.cpu_stats.avg_percent,
+            analysis.cpu_stats.p95_percent,
+            analysis.cpu_stats.max_percent
+        );
+        if let GroupBy::Name | GroupBy::Host = group_by {
+            println!(
+                "  Processes: {}-{} (avg {:.1})",
+                analysis.process_count.min, analysis.process_count.max, analysis.process_count.avg
+            );
+        }
+    }
+
+    println!("{}", "=".repeat(70));
+}
+
+/// 時間窓ごとの集計をテーブル形式で出力
+fn print_bucket_table(
+    buckets: &[BucketStats],
+    bucket: &str,
+    source: &str,
+    process_name_filter: Option<&NameMatcher>,
+) {
+    println!("{}", "=".repeat(118));
+    println!("Time-Bucketed Report (bucket: {}, source: {})", bucket, source);
+    if let Some(name) = process_name_filter {
+        println!("Filter: process name {}", name.describe());
+    }
+    println!("{}", "=".repeat(118));
+
+    println!(
+        "{:<26} {:>7} {:>13} {:>13} {:>9} {:>9} {:>11} {:>13}",
+        "Bucket Start", "Samples", "Avg Memory", "Peak Memory", "Avg CPU", "Peak CPU", "Processes", "Peak Process"
+    );
+    println!("{}", "-".repeat(118));
+
+    for b in buckets {
+        println!(
+            "{:<26} {:>7} {:>13} {:>13} {:>8.2}% {:>8.2}% {:>11} {:>13}",
+            b.start,
+            b.samples,
+            formatter::format_bytes(b.avg_total_memory_bytes as u64),
+            formatter::format_bytes(b.peak_total_memory_bytes),
+            b.avg_total_cpu_percent,
+            b.peak_total_cpu_percent,
+            format!("{:.1}/{}", b.avg_process_count, b.max_process_count),
+            formatter::format_bytes(b.peak_process_memory_bytes)
+        );
+    }
+
+    println!("{}", "=".repeat(118));
+}
+
+/// 時間窓ごとの集計を CSV 形式で出力
+fn print_bucket_csv(buckets: &[BucketStats]) {
+    println!(
+        "bucket_start,samples,avg_total_memory_bytes,peak_total_memory_bytes,\
+         avg_total_cpu_percent,peak_total_cpu_percent,avg_process_count,max_process_count,\
+         peak_process_memory_bytes"
+    );
+    for b in buckets {
+        println!(
+            "{},{},{:.0},{},{:.2},{:.2},{:.2},{},{}",
+            b.start,
+            b.samples,
+            b.avg_total_memory_bytes,
+            b.peak_total_memory_bytes,
+            b.avg_total_cpu_percent,
+            b.peak_total_cpu_percent,
+            b.avg_process_count,
+            b.max_process_count,
+            b.peak_process_memory_bytes
+        );
+    }
+}
+
 /// JSON形式で結果を出力
-fn print_json(analysis: &AnalysisResult) -> Result<(), Box<dyn std::error::Error>> {
-    let json = serde_json::to_string_pretty(analysis)?;
+fn print_json(report: &AnalysisReport) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(report)?;
     println!("{}", json);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let values: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&values, 50.0), 50);
+        assert_eq!(percentile(&values, 95.0), 95);
+        assert_eq!(percentile(&values, 100.0), 100);
+        assert_eq!(percentile(&values, 0.0), 1);
+    }
+
+    #[test]
+    fn test_percentile_single_value() {
+        assert_eq!(percentile(&[42u64], 95.0), 42);
+    }
+
+    fn snapshot(seconds: i64, name: &str, pid: u32, memory_bytes: u64) -> ProcessSnapshot {
+        ProcessSnapshot {
+            timestamp: DateTime::parse_from_rfc3339("2026-01-05T14:00:00+09:00")
+                .unwrap()
+                .with_timezone(&chrono::Local)
+                + chrono::Duration::seconds(seconds),
+            process_name: name.to_string(),
+            pid,
+            parent_pid: None,
+            cpu_usage: 0.0,
+            memory_bytes,
+            smaps: None,
+            thread_count: 1,
+            status: sysinfo::ProcessStatus::Sleep,
+            host: None,
+        }
+    }
+
+    #[test]
+    fn test_analyze_groups_by_name() {
+        let snapshots = vec![
+            snapshot(0, "httpd", 10, 100),
+            snapshot(0, "php-fpm", 20, 300),
+            snapshot(60, "httpd", 10, 200),
+            snapshot(120, "httpd", 11, 400),
+        ];
+        let mut groups = analyze_groups(&snapshots, &GroupBy::Name).unwrap();
+        sort_groups(&mut groups, &GroupSort::Key);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, "httpd");
+        assert_eq!(groups[0].analysis.total_records, 3);
+        assert_eq!(groups[0].lifespan_secs, 120);
+        assert_eq!(groups[0].analysis.memory_stats.max_bytes, 400);
+        assert_eq!(groups[1].key, "php-fpm");
+        assert_eq!(groups[1].lifespan_secs, 0);
+    }
+
+    #[test]
+    fn test_parse_duration_secs() {
+        assert_eq!(parse_duration_secs("1m").unwrap(), 60);
+        assert_eq!(parse_duration_secs("5m").unwrap(), 300);
+        assert_eq!(parse_duration_secs("1h").unwrap(), 3600);
+        assert_eq!(parse_duration_secs("1d").unwrap(), 86400);
+        assert!(parse_duration_secs("5").is_err());
+        assert!(parse_duration_secs("m").is_err());
+        assert!(parse_duration_secs("0m").is_err());
+        assert!(parse_duration_secs("5w").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_expr() {
+        let now = local_from_naive(
+            NaiveDate::from_ymd_opt(2026, 1, 5)
+                .unwrap()
+                .and_hms_opt(14, 30, 0)
+                .unwrap(),
+        )
+        .unwrap();
+        let at = |expr: &str| parse_time_expr(expr, now).map(|t| t.naive_local().to_string());
+
+        assert_eq!(at("now"), Ok("2026-01-05 14:30:00".to_string()));
+        assert_eq!(at("-30m"), Ok("2026-01-05 14:00:00".to_string()));
+        assert_eq!(at("today"), Ok("2026-01-05 00:00:00".to_string()));
+        assert_eq!(at("yesterday 09:00"), Ok("2026-01-04 09:00:00".to_string()));
+        assert_eq!(at("2026-01-03 08:15"), Ok("2026-01-03 08:15:00".to_string()));
+        assert_eq!(at("2026-01-03T08:15:30"), Ok("2026-01-03 08:15:30".to_string()));
+        assert_eq!(at("2026-01-03"), Ok("2026-01-03 00:00:00".to_string()));
+        assert!(at("2026-01-05T14:00:00+09:00").is_ok());
+        assert_eq!(at("tomorrow"), Err(()));
+        assert_eq!(at("-30x"), Err(()));
+        assert_eq!(at("today 25:00"), Err(()));
+    }
+
+    #[test]
+    fn test_to_bound_keeps_records_within_the_same_second() {
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let record = snapshot(0, "httpd", 10, 100);
+        // 記録の 0.5 秒後（同じ秒のうち）を --to に指定
+        let to = format_timestamp(&(record.timestamp + chrono::Duration::milliseconds(500)));
+        let mut inside = record.clone();
+        inside.timestamp += chrono::Duration::milliseconds(250);
+        let mut outside = record.clone();
+        outside.timestamp += chrono::Duration::milliseconds(750);
+        history.insert_snapshots(&[record, inside, outside]).unwrap();
+
+        let filter = RecordFilter {
+            to: Some(&to),
+            ..Default::default()
+        };
+        assert_eq!(history.query_snapshots(&filter).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_stddev() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(stddev(values.iter().copied(), 5.0), 2.0);
+        assert_eq!(stddev(std::iter::empty::<f64>(), 0.0), 0.0);
+    }
+}
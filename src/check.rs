@@ -0,0 +1,347 @@
+use crate::process::{ProcessQuery, find_matching_nodes};
+use std::fmt;
+use std::str::FromStr;
+use sysinfo::{ProcessesToUpdate, System};
+
+/// 監視プラグインの終了ステータス（Nagios / Icinga 互換）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Warning => "WARNING",
+            CheckStatus::Critical => "CRITICAL",
+            CheckStatus::Unknown => "UNKNOWN",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        *self as i32
+    }
+}
+
+/// Nagios のしきい値の範囲（`10`, `10:`, `~:10`, `10:20`, `@10:20`）
+///
+/// 範囲外（@ 付きは範囲内）の値でアラートになる
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdRange {
+    start: f64,
+    end: f64,
+    inside: bool,
+    text: String,
+}
+
+impl ThresholdRange {
+    /// 値がアラートの条件に当てはまるか
+    pub fn alerts(&self, value: f64) -> bool {
+        let within = self.start <= value && value <= self.end;
+        if self.inside { within } else { !within }
+    }
+}
+
+impl fmt::Display for ThresholdRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl FromStr for ThresholdRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid threshold range '{}'", s);
+        let (inside, range) = match s.strip_prefix('@') {
+            Some(range) => (true, range),
+            None => (false, s),
+        };
+        let number = |v: &str| v.parse::<f64>().map_err(|_| invalid());
+
+        let (start, end) = match range.split_once(':') {
+            Some((start, end)) => (
+                match start {
+                    "~" => f64::NEG_INFINITY,
+                    "" => 0.0,
+                    _ => number(start)?,
+                },
+                if end.is_empty() {
+                    f64::INFINITY
+                } else {
+                    number(end)?
+                },
+            ),
+            None => (0.0, number(range)?),
+        };
+        if start > end {
+            return Err(format!("{} (start is greater than end)", invalid()));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            inside,
+            text: s.to_string(),
+        })
+    }
+}
+
+/// clap 用のパーサ
+pub fn parse_range(s: &str) -> Result<ThresholdRange, String> {
+    s.parse()
+}
+
+/// 1つの指標の警告・危険のしきい値
+#[derive(Default)]
+pub struct Thresholds<'a> {
+    pub warning: Option<&'a ThresholdRange>,
+    pub critical: Option<&'a ThresholdRange>,
+}
+
+impl Thresholds<'_> {
+    fn status(&self, value: f64) -> CheckStatus {
+        if self.critical.is_some_and(|range| range.alerts(value)) {
+            CheckStatus::Critical
+        } else if self.warning.is_some_and(|range| range.alerts(value)) {
+            CheckStatus::Warning
+        } else {
+            CheckStatus::Ok
+        }
+    }
+}
+
+/// check サブコマンドの引数
+pub struct CheckOptions<'a> {
+    pub query: &'a ProcessQuery<'a>,
+    pub count: Thresholds<'a>,
+    pub process_memory_mb: Thresholds<'a>,
+    pub total_memory_mb: Thresholds<'a>,
+    pub cpu: Thresholds<'a>,
+}
+
+/// 評価した1つの指標
+struct Measurement<'a> {
+    label: &'static str, // perfdata のラベル
+    value: f64,
+    unit: &'static str,
+    thresholds: &'a Thresholds<'a>,
+}
+
+impl Measurement<'_> {
+    /// perfdata の1項目（'label'=value[UOM];warn;crit;min;）
+    fn perfdata(&self) -> String {
+        let range = |r: Option<&ThresholdRange>| r.map_or(String::new(), |r| r.to_string());
+        format!(
+            "{}={}{};{};{};0;",
+            self.label,
+            format_value(self.value),
+            self.unit,
+            range(self.thresholds.warning),
+            range(self.thresholds.critical)
+        )
+    }
+}
+
+/// perfdata 用の数値（整数はそのまま、小数は2桁）
+fn format_value(value: f64) -> String {
+    // 空の合計（-0.0）を "-0" と出力しない
+    let value = value + 0.0;
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// しきい値を評価して (ステータス, 1行の出力) を返す
+fn evaluate(name: &str, measurements: &[Measurement]) -> (CheckStatus, String) {
+    let mut status = CheckStatus::Ok;
+    let mut problems = vec![];
+    for measurement in measurements {
+        let current = measurement.thresholds.status(measurement.value);
+        if current != CheckStatus::Ok {
+            problems.push(format!(
+                "{} {}{} ({})",
+                measurement.label,
+                format_value(measurement.value),
+                measurement.unit,
+                current.label()
+            ));
+        }
+        status = status.max(current);
+    }
+
+    let summary = measurements
+        .iter()
+        .map(|m| format!("{} {}{}", m.label, format_value(m.value), m.unit))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let detail = if problems.is_empty() {
+        summary
+    } else {
+        problems.join(", ")
+    };
+    let perfdata = measurements
+        .iter()
+        .map(Measurement::perfdata)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    (
+        status,
+        format!(
+            "PROCESS {} - '{}': {} | {}",
+            status.label(),
+            name,
+            detail,
+            perfdata
+        ),
+    )
+}
+
+/// check のエントリーポイント（1行を出力してステータスを返す）
+///
+/// プロセス数のしきい値を指定しない場合は、一致するプロセスがなければ CRITICAL（`--critical-count 1:`）
+pub fn run_check(options: CheckOptions) -> CheckStatus {
+    let mut sys = System::new_all();
+    // CPU 使用率は2回の更新の差分から計算されるため、少し待ってからもう一度更新する
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_processes(ProcessesToUpdate::All, true);
+
+    let query = options.query;
+    let nodes = find_matching_nodes(&sys, query);
+    let memory_mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+
+    let default_count = "1:".parse::<ThresholdRange>().expect("valid range");
+    let count = match options.count {
+        Thresholds {
+            warning: None,
+            critical: None,
+        } => Thresholds {
+            warning: None,
+            critical: Some(&default_count),
+        },
+        thresholds => thresholds,
+    };
+
+    let measurements = [
+        Measurement {
+            label: "count",
+            value: nodes.len() as f64,
+            unit: "",
+            thresholds: &count,
+        },
+        Measurement {
+            label: "total_memory",
+            value: memory_mb(nodes.iter().map(|n| n.memory_by(query.memory_metric)).sum()),
+            unit: "MB",
+            thresholds: &options.total_memory_mb,
+        },
+        Measurement {
+            label: "max_process_memory",
+            value: memory_mb(
+                nodes
+                    .iter()
+                    .map(|n| n.memory_by(query.memory_metric))
+                    .max()
+                    .unwrap_or(0),
+            ),
+            unit: "MB",
+            thresholds: &options.process_memory_mb,
+        },
+        Measurement {
+            label: "cpu",
+            value: nodes.iter().map(|n| n.cpu_usage as f64).sum(),
+            unit: "%",
+            thresholds: &options.cpu,
+        },
+    ];
+
+    let (status, line) = evaluate(&query.matcher.to_string(), &measurements);
+    println!("{}", line);
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> ThresholdRange {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_threshold_range() {
+        // "10": 0〜10 の範囲外
+        assert!(!range("10").alerts(10.0));
+        assert!(range("10").alerts(11.0));
+        assert!(range("10").alerts(-1.0));
+        // "5:": 5 未満
+        assert!(range("5:").alerts(4.0));
+        assert!(!range("5:").alerts(100.0));
+        // "~:5": 5 より大きい
+        assert!(range("~:5").alerts(6.0));
+        assert!(!range("~:5").alerts(-100.0));
+        // "@10:20": 10〜20 の範囲内
+        assert!(range("@10:20").alerts(15.0));
+        assert!(!range("@10:20").alerts(21.0));
+
+        assert!("abc".parse::<ThresholdRange>().is_err());
+        assert!("20:10".parse::<ThresholdRange>().is_err());
+    }
+
+    #[test]
+    fn test_evaluate_status_and_perfdata() {
+        let (warning, critical) = (range("5:"), range("2:"));
+        let count = Thresholds {
+            warning: Some(&warning),
+            critical: Some(&critical),
+        };
+        let memory_critical = range("1024");
+        let memory = Thresholds {
+            warning: None,
+            critical: Some(&memory_critical),
+        };
+        let build = |count_value, memory_value| {
+            evaluate(
+                "php-fpm",
+                &[
+                    Measurement {
+                        label: "count",
+                        value: count_value,
+                        unit: "",
+                        thresholds: &count,
+                    },
+                    Measurement {
+                        label: "total_memory",
+                        value: memory_value,
+                        unit: "MB",
+                        thresholds: &memory,
+                    },
+                ],
+            )
+        };
+
+        let (status, line) = build(10.0, 512.5);
+        assert_eq!(status, CheckStatus::Ok);
+        assert_eq!(
+            line,
+            "PROCESS OK - 'php-fpm': count 10, total_memory 512.50MB \
+             | count=10;5:;2:;0; total_memory=512.50MB;;1024;0;"
+        );
+
+        let (status, line) = build(4.0, 512.0);
+        assert_eq!(status, CheckStatus::Warning);
+        assert!(line.starts_with("PROCESS WARNING - 'php-fpm': count 4 (WARNING) |"));
+
+        // 最も重いステータスが全体のステータス
+        let (status, line) = build(4.0, 2048.0);
+        assert_eq!(status, CheckStatus::Critical);
+        assert!(line.contains("count 4 (WARNING), total_memory 2048MB (CRITICAL)"));
+    }
+}
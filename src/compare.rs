@@ -0,0 +1,355 @@
+use crate::analyze::{
+    AnalysisResult, GroupBy, OutputFormat, analyze_groups, open_history, resolve_timestamp,
+};
+use crate::formatter;
+use crate::history::{ProcessSnapshot, RecordFilter};
+use crate::matcher::NameMatcher;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// analyze compare の引数
+pub struct CompareOptions<'a> {
+    pub db_path: &'a str,
+    pub baseline_db_path: Option<&'a str>,
+    pub name: Option<&'a NameMatcher>,
+    pub baseline_from: Option<&'a str>,
+    pub baseline_to: Option<&'a str>,
+    pub from: Option<&'a str>,
+    pub to: Option<&'a str>,
+    pub format: &'a OutputFormat,
+}
+
+/// 値の単位（表示形式の切り替えに使用）
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricUnit {
+    Bytes,
+    Percent,
+    Count,
+}
+
+/// 1つの指標の比較結果
+#[derive(Serialize)]
+pub struct MetricDelta {
+    pub metric: &'static str,
+    pub unit: MetricUnit,
+    pub baseline: f64,
+    pub current: f64,
+    pub delta: f64,
+    pub delta_percent: Option<f64>,
+}
+
+impl MetricDelta {
+    fn new(metric: &'static str, unit: MetricUnit, baseline: f64, current: f64) -> Self {
+        let delta = current - baseline;
+        Self {
+            metric,
+            unit,
+            baseline,
+            current,
+            delta,
+            // 基準値が 0 の場合は変化率を定義できない
+            delta_percent: (baseline != 0.0).then(|| delta / baseline * 100.0),
+        }
+    }
+}
+
+/// 比較対象の期間の情報
+#[derive(Serialize)]
+pub struct RangeSummary {
+    pub database: String,
+    pub from: String,
+    pub to: String,
+    pub records: usize,
+}
+
+/// プロセス名ごとの比較結果
+#[derive(Serialize)]
+pub struct NameComparison {
+    pub process_name: String,
+    pub status: &'static str, // "changed" | "added" | "removed"
+    pub baseline_records: usize,
+    pub current_records: usize,
+    pub metrics: Vec<MetricDelta>,
+}
+
+/// 比較レポート
+#[derive(Serialize)]
+pub struct CompareReport {
+    pub baseline: RangeSummary,
+    pub current: RangeSummary,
+    pub metrics: Vec<MetricDelta>,
+    pub by_name: Vec<NameComparison>,
+}
+
+/// analyze compare のエントリーポイント
+pub fn run_compare(options: CompareOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let baseline_from = options.baseline_from.map(resolve_timestamp).transpose()?;
+    let baseline_to = options.baseline_to.map(resolve_timestamp).transpose()?;
+    let from = options.from.map(resolve_timestamp).transpose()?;
+    let to = options.to.map(resolve_timestamp).transpose()?;
+
+    let baseline_db = options.baseline_db_path.unwrap_or(options.db_path);
+    let baseline = load(
+        baseline_db,
+        baseline_from.as_deref(),
+        baseline_to.as_deref(),
+        options.name,
+    )
+    .map_err(|e| format!("Baseline: {}", e))?;
+    let current = load(options.db_path, from.as_deref(), to.as_deref(), options.name)
+        .map_err(|e| format!("Current: {}", e))?;
+
+    let report = compare(
+        (baseline_db, &baseline),
+        (options.db_path, &current),
+    )?;
+
+    match options.format {
+        OutputFormat::Table => print_table(&report, options.name),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Csv => unreachable!("compare accepts only table or json"),
+    }
+
+    Ok(())
+}
+
+/// データベースから期間内のスナップショットを取得（空ならエラー）
+fn load(
+    db_path: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    name: Option<&NameMatcher>,
+) -> Result<Vec<ProcessSnapshot>, Box<dyn std::error::Error>> {
+    let history = open_history(db_path)?;
+    let snapshots = history
+        .query_snapshots(&RecordFilter {
+            from,
+            to,
+            name,
+            ..Default::default()
+        })
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    if snapshots.is_empty() {
+        return Err(format!("No records found in {} for the given range", db_path).into());
+    }
+    Ok(snapshots)
+}
+
+/// 2つの期間のスナップショットを比較
+fn compare(
+    (baseline_db, baseline): (&str, &[ProcessSnapshot]),
+    (current_db, current): (&str, &[ProcessSnapshot]),
+) -> Result<CompareReport, Box<dyn std::error::Error>> {
+    let baseline_analysis = AnalysisResult::from_snapshots(baseline)?;
+    let current_analysis = AnalysisResult::from_snapshots(current)?;
+
+    // プロセス名ごとの分析（名前順に並べる）
+    let mut names: BTreeMap<String, (Option<AnalysisResult>, Option<AnalysisResult>)> =
+        BTreeMap::new();
+    for group in analyze_groups(baseline, &GroupBy::Name)? {
+        names.entry(group.key).or_default().0 = Some(group.analysis);
+    }
+    for group in analyze_groups(current, &GroupBy::Name)? {
+        names.entry(group.key).or_default().1 = Some(group.analysis);
+    }
+
+    let by_name = names
+        .into_iter()
+        .map(|(process_name, pair)| match pair {
+            (Some(b), Some(c)) => NameComparison {
+                process_name,
+                status: "changed",
+                baseline_records: b.total_records,
+                current_records: c.total_records,
+                metrics: name_metrics(&b, &c),
+            },
+            (Some(b), None) => NameComparison {
+                process_name,
+                status: "removed",
+                baseline_records: b.total_records,
+                current_records: 0,
+                metrics: Vec::new(),
+            },
+            (None, Some(c)) => NameComparison {
+                process_name,
+                status: "added",
+                baseline_records: 0,
+                current_records: c.total_records,
+                metrics: Vec::new(),
+            },
+            (None, None) => unreachable!("every entry has at least one side"),
+        })
+        .collect();
+
+    Ok(CompareReport {
+        baseline: range_summary(baseline_db, &baseline_analysis),
+        current: range_summary(current_db, &current_analysis),
+        metrics: overall_metrics(&baseline_analysis, &current_analysis),
+        by_name,
+    })
+}
+
+fn range_summary(db_path: &str, analysis: &AnalysisResult) -> RangeSummary {
+    RangeSummary {
+        database: db_path.to_string(),
+        from: analysis.time_range.from.clone(),
+        to: analysis.time_range.to.clone(),
+        records: analysis.total_records,
+    }
+}
+
+/// 全体の比較指標
+fn overall_metrics(b: &AnalysisResult, c: &AnalysisResult) -> Vec<MetricDelta> {
+    use MetricUnit::*;
+    vec![
+        MetricDelta::new("memory_avg", Bytes, b.memory_stats.avg_bytes, c.memory_stats.avg_bytes),
+        MetricDelta::new("memory_p95", Bytes, b.memory_stats.p95_bytes as f64, c.memory_stats.p95_bytes as f64),
+        MetricDelta::new("memory_max", Bytes, b.memory_stats.max_bytes as f64, c.memory_stats.max_bytes as f64),
+        MetricDelta::new("cpu_avg", Percent, b.cpu_stats.avg_percent, c.cpu_stats.avg_percent),
+        MetricDelta::new("cpu_p95", Percent, b.cpu_stats.p95_percent as f64, c.cpu_stats.p95_percent as f64),
+        MetricDelta::new("cpu_max", Percent, b.cpu_stats.max_percent as f64, c.cpu_stats.max_percent as f64),
+        MetricDelta::new("process_count_avg", Count, b.process_count.avg, c.process_count.avg),
+        MetricDelta::new("process_count_max", Count, b.process_count.max as f64, c.process_count.max as f64),
+    ]
+}
+
+/// プロセス名ごとの比較指標
+fn name_metrics(b: &AnalysisResult, c: &AnalysisResult) -> Vec<MetricDelta> {
+    use MetricUnit::*;
+    vec![
+        MetricDelta::new("memory_avg", Bytes, b.memory_stats.avg_bytes, c.memory_stats.avg_bytes),
+        MetricDelta::new("memory_p95", Bytes, b.memory_stats.p95_bytes as f64, c.memory_stats.p95_bytes as f64),
+        MetricDelta::new("memory_max", Bytes, b.memory_stats.max_bytes as f64, c.memory_stats.max_bytes as f64),
+        MetricDelta::new("cpu_avg", Percent, b.cpu_stats.avg_percent, c.cpu_stats.avg_percent),
+        MetricDelta::new("process_count_avg", Count, b.process_count.avg, c.process_count.avg),
+    ]
+}
+
+/// 単位に応じて値を整形
+fn format_value(unit: MetricUnit, value: f64) -> String {
+    match unit {
+        MetricUnit::Bytes => formatter::format_bytes(value.abs() as u64),
+        MetricUnit::Percent => format!("{:.2}%", value.abs()),
+        MetricUnit::Count => format!("{:.1}", value.abs()),
+    }
+}
+
+/// 符号付きで差分を整形
+fn format_delta(metric: &MetricDelta) -> String {
+    let sign = if metric.delta < 0.0 { "-" } else { "+" };
+    let change = metric
+        .delta_percent
+        .map(|p| format!("{:+.1}%", p))
+        .unwrap_or_else(|| "n/a".to_string());
+    format!("{}{} ({})", sign, format_value(metric.unit, metric.delta), change)
+}
+
+/// テーブル形式で比較結果を出力
+fn print_table(report: &CompareReport, process_name_filter: Option<&NameMatcher>) {
+    println!("{}", "=".repeat(78));
+    println!("Comparison Report");
+    println!("{}", "=".repeat(78));
+
+    println!(
+        "\nBaseline: {} - {} ({} records, {})",
+        report.baseline.from, report.baseline.to, report.baseline.records, report.baseline.database
+    );
+    println!(
+        "Current:  {} - {} ({} records, {})",
+        report.current.from, report.current.to, report.current.records, report.current.database
+    );
+    if let Some(name) = process_name_filter {
+        println!("Filter:   process name {}", name.describe());
+    }
+
+    println!("\n{:<20} {:>14} {:>14} {:>26}", "Metric", "Baseline", "Current", "Delta");
+    println!("{}", "-".repeat(78));
+    for metric in &report.metrics {
+        print_metric_row(metric);
+    }
+
+    println!("\nBy Process Name:");
+    for entry in &report.by_name {
+        match entry.status {
+            "added" => println!("\n  {} (added, {} records)", entry.process_name, entry.current_records),
+            "removed" => println!("\n  {} (removed, {} records)", entry.process_name, entry.baseline_records),
+            _ => {
+                println!(
+                    "\n  {} ({} -> {} records)",
+                    entry.process_name, entry.baseline_records, entry.current_records
+                );
+                for metric in &entry.metrics {
+                    print!("  ");
+                    print_metric_row(metric);
+                }
+            }
+        }
+    }
+
+    println!("{}", "=".repeat(78));
+}
+
+fn print_metric_row(metric: &MetricDelta) {
+    println!(
+        "{:<20} {:>14} {:>14} {:>26}",
+        metric.metric,
+        format_value(metric.unit, metric.baseline),
+        format_value(metric.unit, metric.current),
+        format_delta(metric)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Local};
+
+    fn snapshot(name: &str, pid: u32, minutes: i64, memory_bytes: u64) -> ProcessSnapshot {
+        ProcessSnapshot {
+            timestamp: Local::now() + Duration::minutes(minutes),
+            process_name: name.to_string(),
+            pid,
+            parent_pid: None,
+            cpu_usage: 1.0,
+            memory_bytes,
+            smaps: None,
+            thread_count: 1,
+            status: sysinfo::ProcessStatus::Sleep,
+            host: None,
+        }
+    }
+
+    #[test]
+    fn test_metric_delta() {
+        let delta = MetricDelta::new("memory_avg", MetricUnit::Bytes, 200.0, 150.0);
+        assert_eq!(delta.delta, -50.0);
+        assert_eq!(delta.delta_percent, Some(-25.0));
+
+        // 基準値 0 では変化率なし
+        let delta = MetricDelta::new("cpu_avg", MetricUnit::Percent, 0.0, 5.0);
+        assert_eq!(delta.delta_percent, None);
+    }
+
+    #[test]
+    fn test_compare_by_name() {
+        let baseline = vec![snapshot("php-fpm", 1, 0, 100), snapshot("old", 2, 0, 50)];
+        let current = vec![snapshot("php-fpm", 1, 0, 150), snapshot("new", 3, 0, 70)];
+
+        let report = compare(("a.db", &baseline), ("a.db", &current)).unwrap();
+        assert_eq!(report.metrics[0].baseline, 75.0);
+        assert_eq!(report.metrics[0].current, 110.0);
+
+        let statuses: Vec<(&str, &str)> = report
+            .by_name
+            .iter()
+            .map(|n| (n.process_name.as_str(), n.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![("new", "added"), ("old", "removed"), ("php-fpm", "changed")]
+        );
+        assert_eq!(report.by_name[2].metrics[0].delta_percent, Some(50.0));
+    }
+}
@@ -0,0 +1,248 @@
+use crate::alert::AlertRule;
+use crate::analyze::parse_duration_secs;
+use crate::matcher::{MatchMode, NameMatcher};
+use crate::process::{MemoryMetric, SortOrder};
+use crate::targets::Target;
+use serde::Deserialize;
+
+/// 設定ファイルで interval を省略した場合の更新間隔（秒）
+pub const DEFAULT_INTERVAL_SECS: u64 = 5;
+
+/// 設定ファイル（TOML）の全体
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    interval: Option<u64>,
+    retention: Option<String>,
+    log: Option<String>,
+    on_alert: Option<String>,
+    #[serde(default, rename = "target")]
+    targets: Vec<TargetConfig>,
+}
+
+/// name = "php-fpm" と name = ["php-fpm", "httpd"] のどちらも受け付ける
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Names {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Names {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Names::One(name) => vec![name],
+            Names::Many(names) => names,
+        }
+    }
+}
+
+/// 設定ファイルの [[target]]（省略した項目は全体の設定・コマンドラインの値を使う）
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetConfig {
+    name: Names,
+    exclude: Option<Vec<String>>,
+    match_mode: Option<MatchMode>,
+    ignore_case: Option<bool>,
+    label: Option<String>,
+    min_memory_mb: Option<u64>,
+    memory_metric: Option<MemoryMetric>,
+    sort: Option<SortOrder>,
+    #[serde(default)]
+    alerts: Vec<String>,
+    log: Option<String>,
+    on_alert: Option<String>,
+}
+
+/// 設定ファイルで省略した項目に使うコマンドラインの値
+pub struct ConfigDefaults<'a> {
+    pub match_mode: MatchMode,
+    pub ignore_case: bool,
+    pub exclude: &'a [String],
+    pub sort_order: &'a SortOrder,
+    pub memory_metric: &'a MemoryMetric,
+    pub min_memory_mb: Option<u64>,
+    pub log: Option<&'a str>,
+    pub retention_secs: Option<i64>,
+}
+
+/// 読み込んで検証した設定
+#[derive(Debug)]
+pub struct Config {
+    pub interval_secs: u64,
+    pub retention_secs: Option<i64>,
+    pub targets: Vec<Target>,
+}
+
+/// 設定ファイルを読み込む
+pub fn load(path: &str, defaults: &ConfigDefaults) -> Result<Config, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parse(&text, defaults).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// 設定ファイルの内容を解析して検証
+fn parse(text: &str, defaults: &ConfigDefaults) -> Result<Config, String> {
+    let file: ConfigFile = toml::from_str(text).map_err(|e| e.to_string())?;
+
+    if file.targets.is_empty() {
+        return Err("no [[target]] is defined".to_string());
+    }
+    let interval_secs = file.interval.unwrap_or(DEFAULT_INTERVAL_SECS);
+    if interval_secs == 0 {
+        return Err("interval must be at least 1 second".to_string());
+    }
+    let retention_secs = match file.retention {
+        Some(ref retention) => {
+            Some(parse_duration_secs(retention).map_err(|e| format!("retention: {}", e))?)
+        }
+        None => defaults.retention_secs,
+    };
+
+    let mut targets: Vec<Target> = vec![];
+    for target in file.targets {
+        let names = target.name.into_vec();
+        if names.is_empty() {
+            return Err("target with an empty name list".to_string());
+        }
+        let label = target.label.unwrap_or_else(|| names.join(", "));
+        if targets.iter().any(|t| t.label == label) {
+            return Err(format!(
+                "duplicate target '{}' (set a different label)",
+                label
+            ));
+        }
+        let alerts = target
+            .alerts
+            .iter()
+            .map(|rule| rule.parse::<AlertRule>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("target '{}': {}", label, e))?;
+        let matcher = NameMatcher::new(
+            &names,
+            target.exclude.as_deref().unwrap_or(defaults.exclude),
+            target.match_mode.unwrap_or(defaults.match_mode),
+            target.ignore_case.unwrap_or(defaults.ignore_case),
+        )
+        .map_err(|e| format!("target '{}': {}", label, e))?;
+
+        targets.push(Target {
+            label,
+            matcher,
+            min_memory_mb: target.min_memory_mb.or(defaults.min_memory_mb),
+            memory_metric: target
+                .memory_metric
+                .unwrap_or_else(|| defaults.memory_metric.clone()),
+            sort_order: target.sort.unwrap_or_else(|| defaults.sort_order.clone()),
+            alerts,
+            log: target
+                .log
+                .or_else(|| file.log.clone())
+                .or_else(|| defaults.log.map(str::to_string)),
+            on_alert: target.on_alert.or_else(|| file.on_alert.clone()),
+            smaps: false,
+        });
+    }
+
+    Ok(Config {
+        interval_secs,
+        retention_secs,
+        targets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: ConfigDefaults = ConfigDefaults {
+        match_mode: MatchMode::Substring,
+        ignore_case: false,
+        exclude: &[],
+        sort_order: &SortOrder::Memory,
+        memory_metric: &MemoryMetric::Rss,
+        min_memory_mb: None,
+        log: None,
+        retention_secs: None,
+    };
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse(
+            r#"
+            interval = 10
+            retention = "7d"
+            log = "/var/log/monitor.db"
+
+            [[target]]
+            name = "php-fpm"
+            min_memory_mb = 10
+            memory_metric = "pss"
+            alerts = ["total_memory_mb > 2048", "count < 5 for 3"]
+            on_alert = "notify.sh"
+
+            [[target]]
+            name = ["httpd", "apache2"]
+            match_mode = "exact"
+            exclude = ["apache2"]
+            label = "apache"
+            sort = "cpu"
+            log = "/var/log/httpd.db"
+            "#,
+            &DEFAULTS,
+        )
+        .unwrap();
+
+        assert_eq!(config.interval_secs, 10);
+        assert_eq!(config.retention_secs, Some(7 * 86400));
+        let [php, apache] = config.targets.as_slice() else {
+            panic!("two targets")
+        };
+        assert_eq!(php.label, "php-fpm");
+        assert_eq!(php.memory_metric, MemoryMetric::Pss);
+        assert_eq!(php.alerts.len(), 2);
+        assert_eq!(php.log.as_deref(), Some("/var/log/monitor.db"));
+        assert_eq!(apache.label, "apache");
+        assert!(apache.matcher.is_match("httpd"));
+        assert!(!apache.matcher.is_match("httpd2") && !apache.matcher.is_match("apache2"));
+        assert!(php.matcher.is_match("php-fpm8.2"));
+        assert!(matches!(apache.sort_order, SortOrder::Cpu));
+        assert_eq!(apache.log.as_deref(), Some("/var/log/httpd.db"));
+        assert_eq!(apache.on_alert, None);
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(
+            parse("interval = 5", &DEFAULTS)
+                .unwrap_err()
+                .contains("no [[target]]")
+        );
+        assert!(parse("[[target]]\nname = \"a\"\nunknown = 1", &DEFAULTS).is_err());
+        assert!(
+            parse(
+                "[[target]]\nname = \"a\"\n[[target]]\nname = \"a\"",
+                &DEFAULTS
+            )
+            .unwrap_err()
+            .contains("duplicate target 'a'")
+        );
+        assert!(
+            parse(
+                "[[target]]\nname = \"a\"\nalerts = [\"memory > 1\"]",
+                &DEFAULTS
+            )
+            .unwrap_err()
+            .starts_with("target 'a': unknown metric")
+        );
+        assert!(
+            parse(
+                "[[target]]\nname = \"(\"\nmatch_mode = \"regex\"",
+                &DEFAULTS
+            )
+            .unwrap_err()
+            .starts_with("target '(': invalid pattern")
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::alert;
+use crate::config::{self, Config, ConfigDefaults};
+use crate::matcher::MatchMode;
+use crate::process::{MemoryMetric, SortOrder};
+use crate::targets::{TargetSet, TargetSetOptions};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::flag;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+
+/// シグナルを確認する間隔（待機中もこの間隔で終了・再読み込みに反応する）
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 設定ファイルで省略した項目の値（daemon はコマンドラインで指定しない）
+const DEFAULTS: ConfigDefaults = ConfigDefaults {
+    match_mode: MatchMode::Substring,
+    ignore_case: false,
+    exclude: &[],
+    sort_order: &SortOrder::Memory,
+    memory_metric: &MemoryMetric::Rss,
+    min_memory_mb: None,
+    log: None,
+    retention_secs: None,
+};
+
+/// daemon サブコマンドの引数
+pub struct DaemonOptions<'a> {
+    pub config_path: &'a str,
+    pub pidfile: Option<&'a str>,
+    pub error_log: Option<&'a str>,
+}
+
+/// デーモン自身のログの出力先（ファイルまたは標準エラー出力）
+struct DaemonLog {
+    path: Option<String>,
+    file: Option<File>,
+}
+
+impl DaemonLog {
+    fn open(path: Option<&str>) -> io::Result<Self> {
+        let mut log = Self {
+            path: path.map(str::to_string),
+            file: None,
+        };
+        log.reopen()?;
+        Ok(log)
+    }
+
+    /// ファイルを開き直す（logrotate でファイルが移動された後の SIGHUP 用）
+    fn reopen(&mut self) -> io::Result<()> {
+        if let Some(ref path) = self.path {
+            self.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, level: &str, message: &str) {
+        let line = format!(
+            "{} [{}] {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            level,
+            message
+        );
+        // ログを書けない場合に報告する先はないため、失敗は無視する
+        let _ = match self.file {
+            Some(ref mut file) => file.write_all(line.as_bytes()),
+            None => io::stderr().write_all(line.as_bytes()),
+        };
+    }
+
+    fn info(&mut self, message: &str) {
+        self.write("INFO", message);
+    }
+
+    fn warn(&mut self, message: &str) {
+        self.write("WARN", message);
+    }
+
+    fn error(&mut self, message: &str) {
+        self.write("ERROR", message);
+    }
+}
+
+/// PID ファイル（終了時に削除）
+#[derive(Debug)]
+struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// PID ファイルを作成（記録された PID のプロセスが動いている場合はエラー）
+    fn create(path: &str) -> Result<Self, String> {
+        let path = PathBuf::from(path);
+        if let Some(pid) = read_pid(&path) {
+            if is_running(pid) {
+                return Err(format!("Already running (PID {}, see {})", pid, path.display()));
+            }
+            // 前回の異常終了で残ったファイル
+            let _ = std::fs::remove_file(&path);
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to create pidfile {}: {}", path.display(), e))?;
+        writeln!(file, "{}", std::process::id())
+            .map_err(|e| format!("Failed to write pidfile {}: {}", path.display(), e))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn is_running(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut sys = System::new();
+    sys.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    sys.process(pid).is_some()
+}
+
+/// 設定から監視対象を作成し、記録を開始する
+fn start_targets(config: Config, log: &mut DaemonLog) -> (u64, TargetSet) {
+    let (targets, warnings) = TargetSet::new(
+        config.targets,
+        &TargetSetOptions {
+            interval_secs: config.interval_secs,
+            retention_secs: config.retention_secs,
+            hook_output: true,
+        },
+    );
+    for warning in warnings {
+        log.warn(&warning);
+    }
+    (config.interval_secs, targets)
+}
+
+/// 記録先のデータベースを閉じる
+fn close_targets(targets: TargetSet, log: &mut DaemonLog) {
+    for warning in targets.close() {
+        log.error(&warning);
+    }
+}
+
+/// 次の更新まで待機（終了・再読み込みのシグナルを受けたら途中で戻る）
+fn wait(interval: Duration, terminate: &AtomicBool, reload: &AtomicBool) {
+    let start = Instant::now();
+    while !terminate.load(Ordering::SeqCst) && !reload.load(Ordering::SeqCst) {
+        let elapsed = start.elapsed();
+        if elapsed >= interval {
+            break;
+        }
+        thread::sleep(SIGNAL_POLL_INTERVAL.min(interval - elapsed));
+    }
+}
+
+/// daemon のエントリーポイント（SIGTERM / SIGINT まで記録を続ける）
+///
+/// SIGHUP で設定ファイルを読み込み直す（読み込みに失敗した場合は前の設定で続ける）
+pub fn run_daemon(options: DaemonOptions) -> Result<(), String> {
+    let config = config::load(options.config_path, &DEFAULTS)?;
+    let mut log = DaemonLog::open(options.error_log)
+        .map_err(|e| format!("Failed to open {}: {}", options.error_log.unwrap_or_default(), e))?;
+
+    let terminate = Arc::new(AtomicBool::new(false));
+    let reload = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        // 終了処理中にもう一度受けた場合はすぐに終了する
+        flag::register_conditional_shutdown(signal, 1, Arc::clone(&terminate))
+            .and_then(|_| flag::register(signal, Arc::clone(&terminate)))
+            .map_err(|e| format!("Failed to register signal handler: {}", e))?;
+    }
+    flag::register(SIGHUP, Arc::clone(&reload))
+        .map_err(|e| format!("Failed to register signal handler: {}", e))?;
+
+    let _pidfile = options.pidfile.map(PidFile::create).transpose()?;
+
+    let (mut interval_secs, mut targets) = start_targets(config, &mut log);
+    log.info(&format!(
+        "Started (PID {}): {} target(s), interval {}s",
+        std::process::id(),
+        targets.len(),
+        interval_secs
+    ));
+
+    let mut sys = System::new_all();
+    while !terminate.load(Ordering::SeqCst) {
+        if reload.swap(false, Ordering::SeqCst) {
+            if let Err(e) = log.reopen() {
+                log.error(&format!("Failed to reopen log file: {}", e));
+            }
+            match config::load(options.config_path, &DEFAULTS) {
+                Ok(config) => {
+                    close_targets(targets, &mut log);
+                    (interval_secs, targets) = start_targets(config, &mut log);
+                    log.info(&format!(
+                        "Reloaded {}: {} target(s), interval {}s",
+                        options.config_path,
+                        targets.len(),
+                        interval_secs
+                    ));
+                }
+                Err(e) => log.error(&format!("Failed to reload configuration (keeping the previous one): {}", e)),
+            }
+        }
+
+        sys.refresh_processes(ProcessesToUpdate::All, true);
+        sys.refresh_memory();
+        sys.refresh_cpu_usage();
+
+        let result = targets.update(&sys);
+        for event in &result.alerts {
+            log.info(&alert::describe(event));
+        }
+        for warning in &result.warnings {
+            log.error(warning);
+        }
+
+        wait(Duration::from_secs(interval_secs), &terminate, &reload);
+    }
+
+    log.info("Shutting down");
+    close_targets(targets, &mut log);
+    log.info("Stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pidfile() {
+        let path = std::env::temp_dir().join(format!("rs-process-monitor-{}.pid", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let pidfile = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(Path::new(&path)), Some(std::process::id()));
+        // 記録された PID のプロセスが動いている間は作成できない
+        assert!(PidFile::create(&path).unwrap_err().starts_with("Already running"));
+        drop(pidfile);
+        assert!(!Path::new(&path).exists());
+
+        // 動いていないプロセスの PID が残っている場合は上書きする
+        std::fs::write(&path, "999999999\n").unwrap();
+        let pidfile = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(Path::new(&path)), Some(std::process::id()));
+        drop(pidfile);
+    }
+}
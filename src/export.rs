@@ -0,0 +1,549 @@
+use crate::analyze::{open_history, resolve_timestamp};
+use crate::formatter::SmapsMemory;
+use crate::history::{ProcessHistory, ProcessSnapshot, RecordFilter, RollupCounts};
+use crate::retention::DEFAULT_BATCH_SIZE;
+use crate::matcher::NameMatcher;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::str::FromStr;
+
+/// export / import のファイル形式
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum TransferFormat {
+    Csv,
+    Jsonl,
+}
+
+impl TransferFormat {
+    /// 拡張子から形式を推定（.csv / .jsonl / .ndjson）
+    fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => Some(TransferFormat::Csv),
+            "jsonl" | "ndjson" => Some(TransferFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// ファイル上の1行（1つのスナップショット）
+///
+/// CSV の列名・JSONL のキーはこのフィールド名と同じ。watch --output jsonl でも使う
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    timestamp: String, // RFC3339
+    process_name: String,
+    pid: u32,
+    parent_pid: Option<u32>,
+    cpu_usage: f32,
+    memory_bytes: u64,
+    thread_count: usize,
+    status: String,
+    pss_bytes: Option<u64>,
+    uss_bytes: Option<u64>,
+    shared_clean_bytes: Option<u64>,
+    shared_dirty_bytes: Option<u64>,
+    swap_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+}
+
+/// CSV のヘッダ（SnapshotRecord のフィールド順）
+const CSV_COLUMNS: [&str; 14] = [
+    "timestamp",
+    "process_name",
+    "pid",
+    "parent_pid",
+    "cpu_usage",
+    "memory_bytes",
+    "thread_count",
+    "status",
+    "pss_bytes",
+    "uss_bytes",
+    "shared_clean_bytes",
+    "shared_dirty_bytes",
+    "swap_bytes",
+    "host",
+];
+
+impl From<&ProcessSnapshot> for SnapshotRecord {
+    fn from(snapshot: &ProcessSnapshot) -> Self {
+        Self {
+            timestamp: snapshot.timestamp.to_rfc3339(),
+            process_name: snapshot.process_name.clone(),
+            pid: snapshot.pid,
+            parent_pid: snapshot.parent_pid,
+            cpu_usage: snapshot.cpu_usage,
+            memory_bytes: snapshot.memory_bytes,
+            thread_count: snapshot.thread_count,
+            status: format!("{:?}", snapshot.status),
+            pss_bytes: snapshot.smaps.map(|m| m.pss),
+            uss_bytes: snapshot.smaps.map(|m| m.uss),
+            shared_clean_bytes: snapshot.smaps.map(|m| m.shared_clean),
+            shared_dirty_bytes: snapshot.smaps.map(|m| m.shared_dirty),
+            swap_bytes: snapshot.smaps.map(|m| m.swap),
+            host: snapshot.host.clone(),
+        }
+    }
+}
+
+impl SnapshotRecord {
+    fn into_snapshot(self) -> Result<ProcessSnapshot, String> {
+        let timestamp = DateTime::parse_from_rfc3339(&self.timestamp)
+            .map_err(|e| format!("invalid timestamp '{}': {}", self.timestamp, e))?
+            .with_timezone(&Local);
+
+        // smaps 由来の値は PSS が記録されている場合のみ有効（記録時と同じ扱い）
+        let smaps = self.pss_bytes.map(|pss| SmapsMemory {
+            pss,
+            uss: self.uss_bytes.unwrap_or(0),
+            shared_clean: self.shared_clean_bytes.unwrap_or(0),
+            shared_dirty: self.shared_dirty_bytes.unwrap_or(0),
+            swap: self.swap_bytes.unwrap_or(0),
+        });
+
+        Ok(ProcessSnapshot {
+            timestamp,
+            process_name: self.process_name,
+            pid: self.pid,
+            parent_pid: self.parent_pid,
+            cpu_usage: self.cpu_usage,
+            memory_bytes: self.memory_bytes,
+            smaps,
+            thread_count: self.thread_count,
+            status: ProcessHistory::parse_status(&self.status),
+            host: self.host,
+        })
+    }
+
+    /// CSV の1行（CSV_COLUMNS の順）
+    fn to_csv(&self) -> String {
+        let optional = |v: Option<u64>| v.map_or(String::new(), |v| v.to_string());
+        [
+            self.timestamp.clone(),
+            csv_field(&self.process_name),
+            self.pid.to_string(),
+            self.parent_pid.map_or(String::new(), |p| p.to_string()),
+            self.cpu_usage.to_string(),
+            self.memory_bytes.to_string(),
+            self.thread_count.to_string(),
+            csv_field(&self.status),
+            optional(self.pss_bytes),
+            optional(self.uss_bytes),
+            optional(self.shared_clean_bytes),
+            optional(self.shared_dirty_bytes),
+            optional(self.swap_bytes),
+            self.host.as_deref().map_or(String::new(), csv_field),
+        ]
+        .join(",")
+    }
+
+    /// ヘッダの列名 → 値 から作成（列の順序は問わない、空欄は None）
+    fn from_csv(row: &HashMap<&str, &str>) -> Result<Self, String> {
+        let required = |column: &str| {
+            csv_value(row, column).ok_or(format!("missing value for '{}'", column))
+        };
+
+        Ok(Self {
+            timestamp: required("timestamp")?.to_string(),
+            process_name: required("process_name")?.to_string(),
+            pid: parse_number("pid", required("pid")?)?,
+            parent_pid: csv_number(row, "parent_pid")?,
+            cpu_usage: csv_number(row, "cpu_usage")?.unwrap_or(0.0),
+            memory_bytes: parse_number("memory_bytes", required("memory_bytes")?)?,
+            thread_count: csv_number(row, "thread_count")?.unwrap_or(0),
+            status: csv_value(row, "status").unwrap_or("Unknown").to_string(),
+            pss_bytes: csv_number(row, "pss_bytes")?,
+            uss_bytes: csv_number(row, "uss_bytes")?,
+            shared_clean_bytes: csv_number(row, "shared_clean_bytes")?,
+            shared_dirty_bytes: csv_number(row, "shared_dirty_bytes")?,
+            swap_bytes: csv_number(row, "swap_bytes")?,
+            host: csv_value(row, "host").map(str::to_string),
+        })
+    }
+}
+
+/// 列の値（列がない・空欄なら None）
+fn csv_value<'a>(row: &HashMap<&str, &'a str>, column: &str) -> Option<&'a str> {
+    row.get(column).copied().filter(|v| !v.is_empty())
+}
+
+/// 数値の列（列がない・空欄なら None）
+fn csv_number<T: FromStr>(row: &HashMap<&str, &str>, column: &str) -> Result<Option<T>, String> {
+    csv_value(row, column)
+        .map(|v| parse_number(column, v))
+        .transpose()
+}
+
+fn parse_number<T: FromStr>(column: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for '{}': '{}'", column, value))
+}
+
+/// カンマ・引用符を含む値は引用符で囲む
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// CSV の1件を分割（引用符で囲まれた値と "" のエスケープ、値の中の改行に対応）
+fn parse_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted value".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// history export の引数
+pub struct ExportOptions<'a> {
+    pub db_path: &'a str,
+    pub format: TransferFormat,
+    pub name: Option<&'a NameMatcher>,
+    pub from: Option<&'a str>,
+    pub to: Option<&'a str>,
+}
+
+/// history export のエントリーポイント（標準出力に書き出す）
+pub fn run_export(options: ExportOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let from = options.from.map(resolve_timestamp).transpose()?;
+    let to = options.to.map(resolve_timestamp).transpose()?;
+
+    let history = open_history(options.db_path)?;
+    let filter = RecordFilter {
+        from: from.as_deref(),
+        to: to.as_deref(),
+        name: options.name,
+        ..Default::default()
+    };
+
+    // 記録を1件ずつ書き出す（長期間の履歴でも全件をメモリに載せない）
+    let mut out = BufWriter::new(io::stdout().lock());
+    if options.format == TransferFormat::Csv {
+        writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+    }
+    let exported = history.for_each_snapshot(&filter, |snapshot| -> Result<(), Box<dyn std::error::Error>> {
+        let record = SnapshotRecord::from(&snapshot);
+        match options.format {
+            TransferFormat::Csv => writeln!(out, "{}", record.to_csv())?,
+            TransferFormat::Jsonl => writeln!(out, "{}", serde_json::to_string(&record)?)?,
+        }
+        Ok(())
+    })?;
+    out.flush()?;
+
+    eprintln!("Exported {} snapshot(s)", exported);
+    Ok(())
+}
+
+/// history import の引数
+pub struct ImportOptions<'a> {
+    pub db_path: &'a str,
+    pub files: &'a [String],
+    pub format: Option<TransferFormat>,
+}
+
+/// history import のエントリーポイント（データベースがなければ作成）
+pub fn run_import(options: ImportOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let mut history = ProcessHistory::new(options.db_path)?;
+
+    let mut range = None;
+    for path in options.files {
+        let format = options
+            .format
+            .or_else(|| TransferFormat::from_path(path))
+            .ok_or_else(|| {
+                format!("Cannot determine the format of {}. Specify --format csv|jsonl", path)
+            })?;
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let counts = import_file(&mut history, BufReader::new(file), format, &mut range)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        println!(
+            "Imported {} snapshot(s) from {} ({} already recorded, skipped)",
+            counts.imported, path, counts.duplicates
+        );
+    }
+
+    let counts = rebuild_imported_rollups(&history, range, Local::now().timestamp())
+        .map_err(|e| format!("Failed to rebuild rollups: {}", e))?;
+    println!(
+        "Rollups rebuilt: {} per-minute row(s), {} per-hour row(s)",
+        counts.minute, counts.hour
+    );
+    Ok(())
+}
+
+/// 取り込んだ範囲（最初と最後の記録の UNIX 時刻）の集計を作り直す
+///
+/// 範囲外の集計（生データを削除済みの期間を含む）はそのまま残す
+fn rebuild_imported_rollups(
+    history: &ProcessHistory,
+    range: Option<(i64, i64)>,
+    now: i64,
+) -> rusqlite::Result<RollupCounts> {
+    match range {
+        Some((first, last)) => history.rebuild_rollups(first, last, now),
+        None => history.update_rollups(now),
+    }
+}
+
+/// 1ファイル分の取り込み件数
+#[derive(Debug, Default, PartialEq)]
+struct ImportCounts {
+    imported: usize,
+    duplicates: usize, // 既に記録されていたため読み飛ばした件数
+}
+
+/// 1ファイルを読み込み、DEFAULT_BATCH_SIZE 件ずつ挿入
+///
+/// range は取り込んだ記録の最初と最後の時刻（UNIX 時刻）で広げる
+fn import_file(
+    history: &mut ProcessHistory,
+    reader: impl BufRead,
+    format: TransferFormat,
+    range: &mut Option<(i64, i64)>,
+) -> Result<ImportCounts, Box<dyn std::error::Error>> {
+    let mut records = read_records(reader, format);
+    let header = match format {
+        TransferFormat::Csv => match records.next() {
+            Some(record) => parse_csv_line(record?.1.trim_start_matches('\u{feff}'))?,
+            None => return Ok(ImportCounts::default()),
+        },
+        TransferFormat::Jsonl => vec![],
+    };
+
+    let mut batch = Vec::with_capacity(DEFAULT_BATCH_SIZE);
+    let mut counts = ImportCounts::default();
+    let mut flush = |batch: &mut Vec<ProcessSnapshot>| -> Result<(), Box<dyn std::error::Error>> {
+        let imported = history.import_snapshots(batch)?;
+        counts.imported += imported;
+        counts.duplicates += batch.len() - imported;
+        batch.clear();
+        Ok(())
+    };
+    for record in records {
+        let (line_number, line) = record?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = match format {
+            TransferFormat::Csv => parse_csv_line(&line).and_then(|values| {
+                let row = header
+                    .iter()
+                    .map(String::as_str)
+                    .zip(values.iter().map(String::as_str))
+                    .collect();
+                SnapshotRecord::from_csv(&row)
+            }),
+            TransferFormat::Jsonl => serde_json::from_str(&line).map_err(|e| e.to_string()),
+        };
+        let snapshot = record
+            .and_then(SnapshotRecord::into_snapshot)
+            .map_err(|e| format!("line {}: {}", line_number, e))?;
+
+        let epoch = snapshot.timestamp.timestamp();
+        *range = Some(range.map_or((epoch, epoch), |(first, last)| (first.min(epoch), last.max(epoch))));
+        batch.push(snapshot);
+        if batch.len() == DEFAULT_BATCH_SIZE {
+            flush(&mut batch)?;
+        }
+    }
+    flush(&mut batch)?;
+
+    Ok(counts)
+}
+
+/// 1件ずつの記録を (開始行番号, 内容) で返す
+///
+/// CSV は引用符で囲まれた値の中の改行を含めて1件とする
+fn read_records<'a>(
+    mut reader: impl BufRead + 'a,
+    format: TransferFormat,
+) -> Box<dyn Iterator<Item = io::Result<(usize, String)>> + 'a> {
+    match format {
+        TransferFormat::Jsonl => Box::new(reader.lines().enumerate().map(|(index, line)| Ok((index + 1, line?)))),
+        TransferFormat::Csv => {
+            let mut line_number = 0;
+            Box::new(std::iter::from_fn(move || {
+                read_csv_record(&mut reader, &mut line_number).transpose()
+            }))
+        }
+    }
+}
+
+/// CSV の1件を読み込む（引用符が閉じるまで次の行を続けて読む、末尾の改行は除く）
+fn read_csv_record(reader: &mut impl BufRead, line_number: &mut usize) -> io::Result<Option<(usize, String)>> {
+    let start = *line_number + 1;
+    let mut record = String::new();
+    loop {
+        if reader.read_line(&mut record)? == 0 {
+            // 閉じていない引用符は parse_csv_line でエラーにする
+            return Ok((!record.is_empty()).then_some((start, record)));
+        }
+        *line_number += 1;
+        // "" のエスケープも2文字なので、引用符の数が偶数なら閉じている
+        if record.matches('"').count().is_multiple_of(2) {
+            let len = record.trim_end_matches(['\n', '\r']).len();
+            record.truncate(len);
+            return Ok(Some((start, record)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RollupTable;
+    use sysinfo::ProcessStatus;
+
+    fn snapshot(process_name: &str) -> ProcessSnapshot {
+        ProcessSnapshot {
+            timestamp: DateTime::parse_from_rfc3339("2026-01-05T14:00:00+09:00")
+                .unwrap()
+                .with_timezone(&Local),
+            process_name: process_name.to_string(),
+            pid: 42,
+            parent_pid: Some(1),
+            cpu_usage: 1.5,
+            memory_bytes: 1024,
+            smaps: Some(SmapsMemory {
+                pss: 512,
+                uss: 256,
+                ..Default::default()
+            }),
+            thread_count: 3,
+            status: ProcessStatus::Sleep,
+            host: Some("web01".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let record = SnapshotRecord::from(&snapshot("php-fpm: pool \"www\", 1"));
+        let line = record.to_csv();
+        let values = parse_csv_line(&line).unwrap();
+        assert_eq!(values.len(), CSV_COLUMNS.len());
+
+        let row = CSV_COLUMNS.iter().copied().zip(values.iter().map(String::as_str)).collect();
+        assert_eq!(SnapshotRecord::from_csv(&row).unwrap(), record);
+    }
+
+    #[test]
+    fn test_import_both_formats() {
+        let record = SnapshotRecord::from(&snapshot("httpd"));
+        let other = SnapshotRecord::from(&ProcessSnapshot {
+            pid: 43,
+            ..snapshot("httpd")
+        });
+        let csv = format!("{}\n{}\n\n", CSV_COLUMNS.join(","), record.to_csv());
+        let jsonl = format!(
+            "{}\n{}\n",
+            serde_json::to_string(&record).unwrap(),
+            serde_json::to_string(&other).unwrap()
+        );
+
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let mut range = None;
+        let counts = import_file(&mut history, csv.as_bytes(), TransferFormat::Csv, &mut range).unwrap();
+        assert_eq!(counts, ImportCounts { imported: 1, duplicates: 0 });
+        // CSV で取り込み済みの記録は読み飛ばす
+        let counts = import_file(&mut history, jsonl.as_bytes(), TransferFormat::Jsonl, &mut range).unwrap();
+        assert_eq!(counts, ImportCounts { imported: 1, duplicates: 1 });
+        let epoch = snapshot("httpd").timestamp.timestamp();
+        assert_eq!(range, Some((epoch, epoch)));
+
+        let imported = history.query_snapshots(&RecordFilter::default()).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(imported[1].smaps.map(|m| m.uss), Some(256));
+        assert_eq!(imported[1].status, ProcessStatus::Sleep);
+        assert_eq!(imported[1].host.as_deref(), Some("web01"));
+
+        let bad = format!("{}\nnot-a-time,httpd,1,,0,1,1,Run,,,,,,\n", CSV_COLUMNS.join(","));
+        let err = import_file(&mut history, bad.as_bytes(), TransferFormat::Csv, &mut range).unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn test_import_csv_value_with_newline() {
+        let record = SnapshotRecord::from(&snapshot("php-fpm: pool\r\nwww"));
+        let next = SnapshotRecord::from(&snapshot("httpd"));
+        let csv = format!("{}\r\n{}\r\n{}\r\n", CSV_COLUMNS.join(","), record.to_csv(), next.to_csv());
+
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let counts = import_file(&mut history, csv.as_bytes(), TransferFormat::Csv, &mut None).unwrap();
+        assert_eq!(counts.imported, 2);
+        let imported = history.query_snapshots(&RecordFilter::default()).unwrap();
+        assert_eq!(imported[0].process_name, "php-fpm: pool\r\nwww");
+        assert_eq!(imported[1].process_name, "httpd");
+
+        // 閉じていない引用符は開始行の番号で報告する
+        let bad = format!("{}\n{}\n\"httpd,1\n", CSV_COLUMNS.join(","), next.to_csv());
+        let err = import_file(&mut history, bad.as_bytes(), TransferFormat::Csv, &mut None).unwrap_err();
+        assert_eq!(err.to_string(), "line 3: unterminated quoted value");
+    }
+
+    #[test]
+    fn test_import_keeps_pruned_rollups() {
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let start = DateTime::from_timestamp(1_767_589_200, 0) // 正時
+            .unwrap()
+            .with_timezone(&Local);
+        let at = |minutes: i64| ProcessSnapshot {
+            timestamp: start + chrono::Duration::minutes(minutes),
+            ..snapshot("httpd")
+        };
+        // 3時間分 + 次の期間の1件を記録し、最初の2時間の生データを削除
+        history.insert_snapshots(&(0..=180).map(at).collect::<Vec<_>>()).unwrap();
+        history
+            .prune_before(&(start + chrono::Duration::hours(2)).to_rfc3339(), 100)
+            .unwrap();
+
+        // 3時間目に別の記録を取り込む
+        let record = SnapshotRecord::from(&ProcessSnapshot {
+            timestamp: at(150).timestamp + chrono::Duration::seconds(30),
+            ..snapshot("httpd")
+        });
+        let jsonl = format!("{}\n", serde_json::to_string(&record).unwrap());
+        let mut range = None;
+        import_file(&mut history, jsonl.as_bytes(), TransferFormat::Jsonl, &mut range).unwrap();
+        let end = start + chrono::Duration::hours(3);
+        let counts = rebuild_imported_rollups(&history, range, end.timestamp()).unwrap();
+        assert_eq!(counts.hour, 1);
+
+        // 生データを削除済みの2時間分の集計は残る
+        let to = end.to_rfc3339();
+        let filter = RecordFilter {
+            to: Some(&to),
+            ..Default::default()
+        };
+        let (buckets, rollup) = history.query_buckets(&filter, 3600, 0).unwrap();
+        assert_eq!(rollup, Some(RollupTable::Hour));
+        let samples: Vec<usize> = buckets.iter().take(3).map(|b| b.samples).collect();
+        assert_eq!(samples, [60, 60, 61]);
+    }
+}
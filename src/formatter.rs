@@ -17,6 +17,24 @@ pub fn format_bytes(bytes: u64) -> String {
     }
 }
 
+/// 秒数を見やすい期間表記に変換（例: 1d 2h 3m 4s）
+pub fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+    let (hours, rem) = (rem / 3600, rem % 3600);
+    let (minutes, seconds) = (rem / 60, rem % 60);
+
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
 /// 文字列を指定長で切り詰める
 pub fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
@@ -82,12 +100,11 @@ pub fn get_thread_count(pid: u32) -> usize {
 
     if let Ok(content) = fs::read_to_string(&status_path) {
         for line in content.lines() {
-            if line.starts_with("Threads:") {
-                if let Some(count_str) = line.split_whitespace().nth(1) {
-                    if let Ok(count) = count_str.parse::<usize>() {
-                        return count;
-                    }
-                }
+            if line.starts_with("Threads:")
+                && let Some(count_str) = line.split_whitespace().nth(1)
+                && let Ok(count) = count_str.parse::<usize>()
+            {
+                return count;
             }
         }
     }
@@ -102,6 +119,79 @@ pub fn get_thread_count(_pid: u32) -> usize {
     1
 }
 
+/// /proc/{pid}/smaps_rollup から取得したメモリ内訳（バイト単位）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SmapsMemory {
+    pub pss: u64,
+    pub uss: u64,
+    pub shared_clean: u64,
+    pub shared_dirty: u64,
+    pub swap: u64,
+}
+
+impl SmapsMemory {
+    /// 共有メモリ（Shared_Clean + Shared_Dirty）
+    pub fn shared(&self) -> u64 {
+        self.shared_clean + self.shared_dirty
+    }
+}
+
+/// smaps_rollup の内容をパース
+///
+/// USS は Private_Clean + Private_Dirty として計算する
+pub fn parse_smaps_rollup(content: &str) -> Option<SmapsMemory> {
+    let mut memory = SmapsMemory::default();
+    let mut found_pss = false;
+    let mut private_clean = 0;
+    let mut private_dirty = 0;
+
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Ok(kb) = value.parse::<u64>() else {
+            continue;
+        };
+        let bytes = kb * 1024;
+
+        match key {
+            "Pss:" => {
+                memory.pss = bytes;
+                found_pss = true;
+            }
+            "Shared_Clean:" => memory.shared_clean = bytes,
+            "Shared_Dirty:" => memory.shared_dirty = bytes,
+            "Private_Clean:" => private_clean = bytes,
+            "Private_Dirty:" => private_dirty = bytes,
+            "Swap:" => memory.swap = bytes,
+            _ => {}
+        }
+    }
+
+    memory.uss = private_clean + private_dirty;
+    found_pss.then_some(memory)
+}
+
+/// プロセスのメモリ内訳（PSS/USS/Shared/Swap）を取得
+/// Linux: /proc/{pid}/smaps_rollup を読む（権限がない場合は None）
+/// macOS: 取得できないので None を返す
+#[cfg(target_os = "linux")]
+pub fn get_smaps_memory(pid: u32) -> Option<SmapsMemory> {
+    let content = std::fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)).ok()?;
+    parse_smaps_rollup(&content)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_smaps_memory(_pid: u32) -> Option<SmapsMemory> {
+    None
+}
+
+/// smaps 由来の値を整形（取得できなかった場合は "-"）
+pub fn format_optional_bytes(bytes: Option<u64>) -> String {
+    bytes.map(format_bytes).unwrap_or_else(|| "-".to_string())
+}
+
 /// プロセスの実際のPID（TGID）を取得
 #[cfg(target_os = "linux")]
 pub fn get_tgid(lwp: u32) -> u32 {
@@ -111,12 +201,11 @@ pub fn get_tgid(lwp: u32) -> u32 {
 
     if let Ok(content) = fs::read_to_string(&status_path) {
         for line in content.lines() {
-            if line.starts_with("Tgid:") {
-                if let Some(tgid_str) = line.split_whitespace().nth(1) {
-                    if let Ok(tgid) = tgid_str.parse::<u32>() {
-                        return tgid;
-                    }
-                }
+            if line.starts_with("Tgid:")
+                && let Some(tgid_str) = line.split_whitespace().nth(1)
+                && let Ok(tgid) = tgid_str.parse::<u32>()
+            {
+                return tgid;
             }
         }
     }
@@ -128,4 +217,44 @@ pub fn get_tgid(lwp: u32) -> u32 {
 #[cfg(not(target_os = "linux"))]
 pub fn get_tgid(lwp: u32) -> u32 {
     lwp
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_smaps_rollup() {
+        let content = "\
+55d0c0a00000-7ffd5b3fe000 ---p 00000000 00:00 0                          [rollup]
+Rss:               12288 kB
+Pss:                4096 kB
+Pss_Anon:           2048 kB
+Shared_Clean:       6144 kB
+Shared_Dirty:       2048 kB
+Private_Clean:      1024 kB
+Private_Dirty:      3072 kB
+Referenced:        12288 kB
+Swap:                512 kB
+SwapPss:             256 kB
+";
+        let memory = parse_smaps_rollup(content).unwrap();
+        assert_eq!(memory.pss, 4096 * 1024);
+        assert_eq!(memory.uss, 4096 * 1024);
+        assert_eq!(memory.shared(), 8192 * 1024);
+        assert_eq!(memory.swap, 512 * 1024);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(3 * 60 + 5), "3m 5s");
+        assert_eq!(format_duration(2 * 3600 + 60), "2h 1m 0s");
+        assert_eq!(format_duration(86400 + 1), "1d 0h 0m 1s");
+    }
+
+    #[test]
+    fn test_parse_smaps_rollup_empty() {
+        assert_eq!(parse_smaps_rollup(""), None);
+    }
+}
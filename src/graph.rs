@@ -103,20 +103,26 @@ mod tests {
             timestamp: Local::now(),
             process_name: "test".to_string(),
             pid: 1234,
+            parent_pid: None,
             cpu_usage: 10.5,
             memory_bytes: 1024 * 1024,
+            smaps: None,
             thread_count: 1,
             status: ProcessStatus::Run,
+            host: None,
         };
 
         let snapshot2 = ProcessSnapshot {
             timestamp: Local::now(),
             process_name: "test2".to_string(),
             pid: 5678,
+            parent_pid: None,
             cpu_usage: 5.5,
             memory_bytes: 2 * 1024 * 1024,
+            smaps: None,
             thread_count: 1,
             status: ProcessStatus::Run,
+            host: None,
         };
 
         graph.push_snapshot(&[snapshot1.clone(), snapshot2.clone()]);
@@ -135,10 +141,13 @@ mod tests {
             timestamp: timestamp1,
             process_name: "test1".to_string(),
             pid: 1,
+            parent_pid: None,
             cpu_usage: 10.0,
             memory_bytes: 1024,
+            smaps: None,
             thread_count: 1,
             status: ProcessStatus::Run,
+            host: None,
         };
 
         let timestamp2 = timestamp1 + chrono::Duration::seconds(1);
@@ -146,10 +155,13 @@ mod tests {
             timestamp: timestamp2,
             process_name: "test2".to_string(),
             pid: 2,
+            parent_pid: None,
             cpu_usage: 20.0,
             memory_bytes: 2048,
+            smaps: None,
             thread_count: 1,
             status: ProcessStatus::Run,
+            host: None,
         };
 
         let timestamp3 = timestamp2 + chrono::Duration::seconds(1);
@@ -157,10 +169,13 @@ mod tests {
             timestamp: timestamp3,
             process_name: "test3".to_string(),
             pid: 3,
+            parent_pid: None,
             cpu_usage: 30.0,
             memory_bytes: 3072,
+            smaps: None,
             thread_count: 1,
             status: ProcessStatus::Run,
+            host: None,
         };
 
         // Add the first snapshot
@@ -0,0 +1,152 @@
+use crate::analyze::percentile;
+use crate::formatter;
+use crate::history::SystemSnapshot;
+use serde::Serialize;
+
+/// ホストの余裕（記録期間中のホスト全体のメモリ・CPU・負荷）
+#[derive(Serialize)]
+pub struct HostHeadroom {
+    pub samples: usize,
+    pub cpu_count: usize,
+    pub total_memory_bytes: u64,
+    pub avg_used_memory_bytes: f64,
+    pub p95_used_memory_bytes: u64,
+    pub max_used_memory_bytes: u64,
+    pub min_available_memory_bytes: u64,
+    pub avg_available_memory_bytes: f64,
+    pub min_available_percent: f64,
+    pub total_swap_bytes: u64,
+    pub max_used_swap_bytes: u64,
+    pub avg_load_average_1: f64,
+    pub max_load_average_1: f64,
+    pub avg_cpu_percent: f64,
+    pub p95_cpu_percent: f32,
+    pub max_cpu_percent: f32,
+    /// 最小の空きメモリに、p95 メモリのプロセスがあと何個収まるか
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_processes_at_p95: Option<u64>,
+}
+
+impl HostHeadroom {
+    /// ホストのスナップショットから集計（空なら None）
+    pub fn from_snapshots(snapshots: &[SystemSnapshot], process_p95_bytes: u64) -> Option<Self> {
+        let last = snapshots.last()?;
+        let count = snapshots.len() as f64;
+
+        let mut used: Vec<u64> = snapshots.iter().map(|s| s.used_memory_bytes).collect();
+        used.sort_unstable();
+        let mut cpu: Vec<f32> = snapshots.iter().map(|s| s.cpu_usage).collect();
+        cpu.sort_by(|a, b| a.total_cmp(b));
+
+        let min_available = snapshots.iter().map(|s| s.available_memory_bytes).min()?;
+        let total_memory = last.total_memory_bytes;
+
+        Some(Self {
+            samples: snapshots.len(),
+            cpu_count: last.cpu_count,
+            total_memory_bytes: total_memory,
+            avg_used_memory_bytes: used.iter().sum::<u64>() as f64 / count,
+            p95_used_memory_bytes: percentile(&used, 95.0),
+            max_used_memory_bytes: used[used.len() - 1],
+            min_available_memory_bytes: min_available,
+            avg_available_memory_bytes: snapshots
+                .iter()
+                .map(|s| s.available_memory_bytes as f64)
+                .sum::<f64>()
+                / count,
+            min_available_percent: if total_memory == 0 {
+                0.0
+            } else {
+                min_available as f64 / total_memory as f64 * 100.0
+            },
+            total_swap_bytes: last.total_swap_bytes,
+            max_used_swap_bytes: snapshots.iter().map(|s| s.used_swap_bytes).max()?,
+            avg_load_average_1: snapshots.iter().map(|s| s.load_average_1).sum::<f64>() / count,
+            max_load_average_1: snapshots.iter().map(|s| s.load_average_1).fold(0.0, f64::max),
+            avg_cpu_percent: cpu.iter().map(|&c| c as f64).sum::<f64>() / count,
+            p95_cpu_percent: percentile(&cpu, 95.0),
+            max_cpu_percent: cpu[cpu.len() - 1],
+            additional_processes_at_p95: (process_p95_bytes > 0)
+                .then(|| min_available / process_p95_bytes),
+        })
+    }
+}
+
+/// テーブル形式でホストの余裕を出力
+pub fn print_table(headroom: &HostHeadroom) {
+    println!("\nHost Headroom ({} samples):", headroom.samples);
+    println!(
+        "  Memory:     {} total, used avg {} / p95 {} / max {}",
+        formatter::format_bytes(headroom.total_memory_bytes),
+        formatter::format_bytes(headroom.avg_used_memory_bytes as u64),
+        formatter::format_bytes(headroom.p95_used_memory_bytes),
+        formatter::format_bytes(headroom.max_used_memory_bytes)
+    );
+    println!(
+        "  Available:  min {} ({:.1}%), avg {}",
+        formatter::format_bytes(headroom.min_available_memory_bytes),
+        headroom.min_available_percent,
+        formatter::format_bytes(headroom.avg_available_memory_bytes as u64)
+    );
+    if headroom.total_swap_bytes == 0 {
+        println!("  Swap:       N/A");
+    } else {
+        println!(
+            "  Swap:       max {} used of {}",
+            formatter::format_bytes(headroom.max_used_swap_bytes),
+            formatter::format_bytes(headroom.total_swap_bytes)
+        );
+    }
+    println!(
+        "  Load (1m):  avg {:.2}, max {:.2} ({} CPUs)",
+        headroom.avg_load_average_1, headroom.max_load_average_1, headroom.cpu_count
+    );
+    println!(
+        "  CPU:        avg {:.2}%, p95 {:.2}%, max {:.2}%",
+        headroom.avg_cpu_percent, headroom.p95_cpu_percent, headroom.max_cpu_percent
+    );
+    if let Some(additional) = headroom.additional_processes_at_p95 {
+        println!(
+            "  Room for:   {} more process(es) at p95 memory within the minimum available memory",
+            additional
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn snapshot(used_mb: u64, cpu_usage: f32) -> SystemSnapshot {
+        SystemSnapshot {
+            timestamp: Local::now(),
+            total_memory_bytes: 1000 * MB,
+            used_memory_bytes: used_mb * MB,
+            available_memory_bytes: (1000 - used_mb) * MB,
+            total_swap_bytes: 0,
+            used_swap_bytes: 0,
+            load_average_1: 1.0,
+            load_average_5: 1.0,
+            load_average_15: 1.0,
+            cpu_count: 4,
+            cpu_usage,
+        }
+    }
+
+    #[test]
+    fn test_headroom_from_snapshots() {
+        let snapshots = vec![snapshot(400, 10.0), snapshot(600, 30.0), snapshot(500, 20.0)];
+        let headroom = HostHeadroom::from_snapshots(&snapshots, 50 * MB).unwrap();
+
+        assert_eq!(headroom.max_used_memory_bytes, 600 * MB);
+        assert_eq!(headroom.min_available_memory_bytes, 400 * MB);
+        assert_eq!(headroom.min_available_percent, 40.0);
+        assert_eq!(headroom.avg_cpu_percent, 20.0);
+        assert_eq!(headroom.additional_processes_at_p95, Some(8));
+
+        assert!(HostHeadroom::from_snapshots(&[], 50 * MB).is_none());
+    }
+}
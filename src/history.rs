@@ -1,6 +1,13 @@
+use crate::analyze::percentile;
+use crate::formatter::SmapsMemory;
 use chrono::{DateTime, Local};
-use rusqlite::{Connection, Result, params};
-use sysinfo::ProcessStatus;
+use crate::matcher::NameMatcher;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Result, params, params_from_iter};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use sysinfo::{ProcessStatus, System};
 
 /// プロセス情報のスナップショット（1つのプロセスの記録単位）
 #[derive(Debug, Clone)]
@@ -8,83 +15,1140 @@ pub struct ProcessSnapshot {
     pub timestamp: DateTime<Local>,
     pub process_name: String,
     pub pid: u32,
+    pub parent_pid: Option<u32>,
     pub cpu_usage: f32,
     pub memory_bytes: u64,
+    pub smaps: Option<SmapsMemory>,
     pub thread_count: usize,
     pub status: ProcessStatus,
+    pub host: Option<String>, // 記録元のホスト（history merge で取り込んだ記録のみ）
+}
+
+/// ホスト全体の状態のスナップショット（1回の記録ごと）
+#[derive(Debug, Clone)]
+pub struct SystemSnapshot {
+    pub timestamp: DateTime<Local>,
+    pub total_memory_bytes: u64,
+    pub used_memory_bytes: u64,
+    pub available_memory_bytes: u64,
+    pub total_swap_bytes: u64,
+    pub used_swap_bytes: u64,
+    pub load_average_1: f64,
+    pub load_average_5: f64,
+    pub load_average_15: f64,
+    pub cpu_count: usize,
+    pub cpu_usage: f32, // 全CPUの平均使用率（%）
+}
+
+impl SystemSnapshot {
+    /// 現在の System の値から作成（メモリ・CPU は呼び出し側で更新しておく）
+    pub fn capture(sys: &System) -> Self {
+        let load = System::load_average();
+        Self {
+            timestamp: Local::now(),
+            total_memory_bytes: sys.total_memory(),
+            used_memory_bytes: sys.used_memory(),
+            available_memory_bytes: sys.available_memory(),
+            total_swap_bytes: sys.total_swap(),
+            used_swap_bytes: sys.used_swap(),
+            load_average_1: load.one,
+            load_average_5: load.five,
+            load_average_15: load.fifteen,
+            cpu_count: sys.cpus().len(),
+            cpu_usage: sys.global_cpu_usage(),
+        }
+    }
+}
+
+/// プロセスのライフサイクルイベントの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ProcessEventKind {
+    #[serde(rename = "process_started")]
+    Started,
+    #[serde(rename = "process_exited")]
+    Exited,
+}
+
+impl ProcessEventKind {
+    /// データベースに保存する文字列
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessEventKind::Started => "process_started",
+            ProcessEventKind::Exited => "process_exited",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "process_started" => Some(ProcessEventKind::Started),
+            "process_exited" => Some(ProcessEventKind::Exited),
+            _ => None,
+        }
+    }
+}
+
+/// プロセスの起動・終了イベント
+#[derive(Debug, Clone)]
+pub struct ProcessEvent {
+    pub timestamp: DateTime<Local>,
+    pub kind: ProcessEventKind,
+    pub pid: u32,
+    pub process_name: String,
+    pub parent_pid: Option<u32>,
+    pub memory_bytes: u64,          // 最後に観測したメモリ（RSS）
+    pub lifetime_secs: Option<i64>, // 終了イベントのみ
+}
+
+/// アラートの状態の変化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+impl AlertState {
+    /// データベースに保存する文字列
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+
+/// アラートの発火・解除
+#[derive(Debug, Clone)]
+pub struct AlertEvent {
+    pub timestamp: DateTime<Local>,
+    pub state: AlertState,
+    pub rule: String,   // 例: "total_memory_mb > 2048 for 3"
+    pub metric: String, // 例: "total_memory_mb"
+    pub value: f64,
+    pub threshold: f64,
+    pub name_filter: String,
+}
+
+/// 新しく記録を始めるセッションの設定（ホスト名・バージョンは記録時に取得）
+pub struct NewSession<'a> {
+    pub name_filter: Option<&'a str>,
+    pub min_memory_mb: Option<u64>,
+    pub interval_secs: u64,
+}
+
+/// 監視セッション（watch/TUI の1回の実行）
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub id: i64,
+    pub started_at: String,       // ISO 8601
+    pub ended_at: Option<String>, // 最後の記録時刻
+    pub hostname: Option<String>,
+    pub name_filter: Option<String>,
+    pub min_memory_mb: Option<u64>,
+    pub interval_secs: u64,
+    pub tool_version: String,
+    pub snapshots: usize,
+}
+
+/// 記録のクエリ条件（None の条件は絞り込まない）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordFilter<'a> {
+    pub from: Option<&'a str>,
+    pub to: Option<&'a str>,
+    pub name: Option<&'a NameMatcher>,
+    pub session: Option<i64>,
+    pub host: Option<&'a str>,
+}
+
+/// 時間窓ごとの集計値
+#[derive(Debug, Serialize)]
+pub struct BucketStats {
+    pub start: String, // ISO 8601（窓の開始時刻）
+    pub samples: usize,
+    pub avg_total_memory_bytes: f64,
+    pub peak_total_memory_bytes: u64,
+    pub avg_total_cpu_percent: f64,
+    pub peak_total_cpu_percent: f64,
+    pub avg_process_count: f64,
+    pub max_process_count: usize,
+    pub peak_process_memory_bytes: u64,
+}
+
+impl BucketStats {
+    /// 同じ時間窓の集計値を合成（平均は記録回数で重み付け）
+    fn combine(&mut self, other: &BucketStats) {
+        let total = (self.samples + other.samples).max(1) as f64;
+        let weighted = |a: f64, b: f64| (a * self.samples as f64 + b * other.samples as f64) / total;
+        self.avg_total_memory_bytes = weighted(self.avg_total_memory_bytes, other.avg_total_memory_bytes);
+        self.avg_total_cpu_percent = weighted(self.avg_total_cpu_percent, other.avg_total_cpu_percent);
+        self.avg_process_count = weighted(self.avg_process_count, other.avg_process_count);
+        self.peak_total_memory_bytes = self.peak_total_memory_bytes.max(other.peak_total_memory_bytes);
+        self.peak_total_cpu_percent = self.peak_total_cpu_percent.max(other.peak_total_cpu_percent);
+        self.max_process_count = self.max_process_count.max(other.max_process_count);
+        self.peak_process_memory_bytes = self.peak_process_memory_bytes.max(other.peak_process_memory_bytes);
+        self.samples += other.samples;
+    }
+}
+
+/// 集計済み（ダウンサンプリング）テーブルの粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollupTable {
+    Minute,
+    Hour,
+}
+
+impl RollupTable {
+    /// 粗い順（analyze は条件を満たす最初のテーブルを使う）
+    pub const COARSEST_FIRST: [RollupTable; 2] = [RollupTable::Hour, RollupTable::Minute];
+
+    pub fn table(&self) -> &'static str {
+        match self {
+            RollupTable::Minute => "rollup_minute",
+            RollupTable::Hour => "rollup_hour",
+        }
+    }
+
+    pub fn period_secs(&self) -> i64 {
+        match self {
+            RollupTable::Minute => 60,
+            RollupTable::Hour => 3600,
+        }
+    }
+}
+
+/// 集計テーブルに書き込んだ行数
+#[derive(Debug, Default)]
+pub struct RollupCounts {
+    pub minute: usize,
+    pub hour: usize,
+}
+
+/// 1期間・1プロセス名分の集計中の値
+#[derive(Default)]
+struct RollupAccumulator {
+    memory: Vec<u64>,
+    cpu: Vec<f32>,
+    // タイムスタンプ（1回の記録）ごとの (合計メモリ, 合計CPU, プロセス数)
+    ticks: HashMap<String, (u64, f64, usize)>,
+}
+
+impl RollupAccumulator {
+    fn push(&mut self, timestamp: String, memory_bytes: u64, cpu_usage: f32) {
+        self.memory.push(memory_bytes);
+        self.cpu.push(cpu_usage);
+        let tick = self.ticks.entry(timestamp).or_default();
+        tick.0 += memory_bytes;
+        tick.1 += cpu_usage as f64;
+        tick.2 += 1;
+    }
+
+    fn write(mut self, conn: &Connection, table: &str, period_start: i64, process_name: &str) -> Result<()> {
+        self.memory.sort_unstable();
+        self.cpu.sort_by(|a, b| a.total_cmp(b));
+        let samples = self.memory.len() as f64;
+        let ticks = self.ticks.len() as f64;
+        let tick_values = self.ticks.values();
+
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} VALUES
+                 (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+                table
+            ),
+            params![
+                period_start,
+                process_name,
+                self.memory.len() as i64,
+                self.ticks.len() as i64,
+                self.memory[0] as i64,
+                self.memory.iter().sum::<u64>() as f64 / samples,
+                self.memory[self.memory.len() - 1] as i64,
+                percentile(&self.memory, 95.0) as i64,
+                self.cpu[0],
+                self.cpu.iter().map(|&c| c as f64).sum::<f64>() / samples,
+                self.cpu[self.cpu.len() - 1],
+                percentile(&self.cpu, 95.0),
+                tick_values.clone().map(|t| t.0 as f64).sum::<f64>() / ticks,
+                tick_values.clone().map(|t| t.0).max().unwrap_or(0) as i64,
+                tick_values.clone().map(|t| t.1).sum::<f64>() / ticks,
+                tick_values.clone().map(|t| t.1).fold(0.0, f64::max),
+                tick_values.clone().map(|t| t.2 as f64).sum::<f64>() / ticks,
+                tick_values.map(|t| t.2).max().unwrap_or(0) as i64,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// 1期間分の集計で既存の行を置き換える（書き込んだ行数）
+///
+/// 生データが集計時より少ない期間は、一部が削除済みのため既存の行を残す
+fn write_rollup_period(
+    conn: &Connection,
+    rollup: RollupTable,
+    period_start: i64,
+    groups: HashMap<String, RollupAccumulator>,
+) -> Result<usize> {
+    let samples: usize = groups.values().map(|acc| acc.memory.len()).sum();
+    let recorded: i64 = conn.query_row(
+        &format!("SELECT COALESCE(SUM(samples), 0) FROM {} WHERE period_start = ?1", rollup.table()),
+        params![period_start],
+        |row| row.get(0),
+    )?;
+    if (samples as i64) < recorded {
+        return Ok(0);
+    }
+
+    conn.execute(
+        &format!("DELETE FROM {} WHERE period_start = ?1", rollup.table()),
+        params![period_start],
+    )?;
+    let written = groups.len();
+    for (name, acc) in groups {
+        acc.write(conn, rollup.table(), period_start, &name)?;
+    }
+    Ok(written)
+}
+
+/// UNIX 時刻を記録と同じ形式（ローカルオフセット付き RFC3339）に変換
+fn local_timestamp(epoch: i64) -> String {
+    DateTime::from_timestamp(epoch, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .to_rfc3339()
+}
+
+/// 古い記録の削除件数（テーブルごと）
+#[derive(Debug, Default)]
+pub struct PruneCounts {
+    pub snapshots: usize,
+    pub events: usize,
+    pub system_snapshots: usize,
+    pub sessions: usize,
+    pub alerts: usize,
+}
+
+/// history merge で取り込んだ件数（重複を除く）
+#[derive(Debug, Default)]
+pub struct MergeCounts {
+    pub snapshots: usize,
+    pub events: usize,
+    pub system_snapshots: usize,
+    pub sessions: usize,
+    pub alerts: usize,
+    pub range: Option<(i64, i64)>, // 取り込んだスナップショットの最初と最後の時刻（UNIX 時刻）
+}
+
+/// 履歴データベースを開く際のエラー
+#[derive(Debug)]
+pub enum HistoryError {
+    Sqlite(rusqlite::Error),
+    /// このバージョンより新しいツールで作成されたデータベース
+    NewerSchema { found: i64, supported: i64 },
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::Sqlite(e) => write!(f, "{}", e),
+            HistoryError::NewerSchema { found, supported } => write!(
+                f,
+                "database schema version {} is newer than the supported version {}. \
+                 Upgrade rs-process-monitor to use this database",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(e: rusqlite::Error) -> Self {
+        HistoryError::Sqlite(e)
+    }
+}
+
+/// 現在のスキーマバージョン（適用済みマイグレーションの数）
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// スキーマのマイグレーション（先頭から順に適用し、n 番目を適用するとバージョン n）
+///
+/// 既存の要素は変更せず、スキーマを変えるときは末尾に追加する
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    migrate_v1_snapshots,
+    migrate_v2_snapshot_details,
+    migrate_v3_process_events,
+    migrate_v4_rollups,
+    migrate_v5_system_snapshots,
+    migrate_v6_sessions,
+    migrate_v7_hosts,
+    migrate_v8_alerts,
+];
+
+/// v1: スナップショットのテーブルとインデックス
+fn migrate_v1_snapshots(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS process_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            process_name TEXT NOT NULL,
+            pid INTEGER NOT NULL,
+            cpu_usage REAL NOT NULL,
+            memory_bytes INTEGER NOT NULL,
+            thread_count INTEGER NOT NULL,
+            status TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_timestamp ON process_snapshots(timestamp);
+        CREATE INDEX IF NOT EXISTS idx_pid ON process_snapshots(pid);
+        CREATE INDEX IF NOT EXISTS idx_process_name ON process_snapshots(process_name);",
+    )
+}
+
+/// v2: 親PIDと smaps_rollup 由来のメモリ内訳
+fn migrate_v2_snapshot_details(conn: &Connection) -> Result<()> {
+    // バージョン管理導入前のファイルには既に追加済みの場合がある
+    for column in [
+        "parent_pid",
+        "pss_bytes",
+        "uss_bytes",
+        "shared_clean_bytes",
+        "shared_dirty_bytes",
+        "swap_bytes",
+    ] {
+        add_column_if_missing(conn, "process_snapshots", column, "INTEGER")?;
+    }
+    Ok(())
+}
+
+/// v3: プロセスの起動・終了イベント
+fn migrate_v3_process_events(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS process_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            event TEXT NOT NULL,
+            pid INTEGER NOT NULL,
+            process_name TEXT NOT NULL,
+            parent_pid INTEGER,
+            memory_bytes INTEGER NOT NULL,
+            lifetime_secs INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_events_timestamp ON process_events(timestamp);",
+    )
+}
+
+/// v4: 1分・1時間ごとのプロセス名別集計（period_start は UNIX 時刻）
+fn migrate_v4_rollups(conn: &Connection) -> Result<()> {
+    for rollup in RollupTable::COARSEST_FIRST {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                period_start INTEGER NOT NULL,
+                process_name TEXT NOT NULL,
+                samples INTEGER NOT NULL,
+                ticks INTEGER NOT NULL,
+                min_memory_bytes INTEGER NOT NULL,
+                avg_memory_bytes REAL NOT NULL,
+                max_memory_bytes INTEGER NOT NULL,
+                p95_memory_bytes INTEGER NOT NULL,
+                min_cpu_percent REAL NOT NULL,
+                avg_cpu_percent REAL NOT NULL,
+                max_cpu_percent REAL NOT NULL,
+                p95_cpu_percent REAL NOT NULL,
+                avg_total_memory_bytes REAL NOT NULL,
+                max_total_memory_bytes INTEGER NOT NULL,
+                avg_total_cpu_percent REAL NOT NULL,
+                max_total_cpu_percent REAL NOT NULL,
+                avg_process_count REAL NOT NULL,
+                max_process_count INTEGER NOT NULL,
+                PRIMARY KEY (period_start, process_name)
+            )",
+            rollup.table()
+        ))?;
+    }
+    Ok(())
+}
+
+/// v5: ホスト全体のメモリ・スワップ・負荷
+fn migrate_v5_system_snapshots(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS system_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            total_memory_bytes INTEGER NOT NULL,
+            used_memory_bytes INTEGER NOT NULL,
+            available_memory_bytes INTEGER NOT NULL,
+            total_swap_bytes INTEGER NOT NULL,
+            used_swap_bytes INTEGER NOT NULL,
+            load_average_1 REAL NOT NULL,
+            load_average_5 REAL NOT NULL,
+            load_average_15 REAL NOT NULL,
+            cpu_count INTEGER NOT NULL,
+            cpu_usage REAL NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_system_timestamp ON system_snapshots(timestamp);",
+    )
+}
+
+/// v6: 監視セッションと、各記録のセッション ID
+fn migrate_v6_sessions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at TEXT NOT NULL,
+            ended_at TEXT,
+            hostname TEXT,
+            name_filter TEXT,
+            min_memory_mb INTEGER,
+            interval_secs INTEGER NOT NULL,
+            tool_version TEXT NOT NULL
+        );",
+    )?;
+    // 既存の記録はセッション不明（NULL）
+    for table in ["process_snapshots", "process_events", "system_snapshots"] {
+        add_column_if_missing(conn, table, "session_id", "INTEGER")?;
+    }
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_session ON process_snapshots(session_id);",
+    )
+}
+
+/// v7: 記録元のホスト（history merge で付与、自ホストの記録は NULL）
+fn migrate_v7_hosts(conn: &Connection) -> Result<()> {
+    for table in ["process_snapshots", "process_events", "system_snapshots"] {
+        add_column_if_missing(conn, table, "host", "TEXT")?;
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_host ON process_snapshots(host);")
+}
+
+/// v8: アラートの発火・解除
+fn migrate_v8_alerts(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS alerts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            state TEXT NOT NULL,
+            rule TEXT NOT NULL,
+            metric TEXT NOT NULL,
+            value REAL NOT NULL,
+            threshold REAL NOT NULL,
+            name_filter TEXT,
+            session_id INTEGER,
+            host TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_alerts_timestamp ON alerts(timestamp);",
+    )
+}
+
+/// カラムが存在しなければ追加
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// 取り込み元（source）のテーブルの列名（テーブルがなければ空）
+fn source_columns(conn: &Connection, table: &str) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1, 'source')")?;
+    stmt.query_map(params![table], |row| row.get(0))?.collect()
+}
+
+/// 取り込み元の列の式（古いスキーマにない列は NULL）
+fn source_column(columns: &HashSet<String>, alias: &str, column: &str) -> String {
+    if columns.contains(column) {
+        format!("{}.{}", alias, column)
+    } else {
+        "NULL".to_string()
+    }
+}
+
+/// 読み取り専用で ATTACH するための URI（パスの `%` `?` `#` はエスケープ）
+fn read_only_uri(path: &str) -> String {
+    let escaped = path.replace('%', "%25").replace('?', "%3f").replace('#', "%23");
+    format!("file:{}?mode=ro", escaped)
 }
 
 /// 履歴データベース管理
 pub struct ProcessHistory {
     conn: Connection,
+    session_id: Option<i64>, // start_session 後の記録に付与
 }
 
 impl ProcessHistory {
     /// 新規データベース接続を作成（ファイルが存在しなければ作成）
-    pub fn new(db_path: &str) -> Result<Self> {
+    ///
+    /// 古いバージョンで作成されたファイルはその場で最新のスキーマに移行する
+    pub fn new(db_path: &str) -> std::result::Result<Self, HistoryError> {
         let conn = Connection::open(db_path)?;
-        let history = Self { conn };
-        history.init_schema()?;
+        let mut history = Self {
+            conn,
+            session_id: None,
+        };
+        history.migrate()?;
         Ok(history)
     }
 
-    /// テーブルとインデックスを作成
-    fn init_schema(&self) -> Result<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS process_snapshots (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                timestamp TEXT NOT NULL,
-                process_name TEXT NOT NULL,
-                pid INTEGER NOT NULL,
-                cpu_usage REAL NOT NULL,
-                memory_bytes INTEGER NOT NULL,
-                thread_count INTEGER NOT NULL,
-                status TEXT NOT NULL
-            )",
+    /// 接続を閉じる（未完了の書き込みがあればエラーを返す）
+    pub fn close(self) -> Result<()> {
+        self.conn.close().map_err(|(_, e)| e)
+    }
+
+    /// 未適用のマイグレーションを順番に適用（PRAGMA user_version で管理）
+    fn migrate(&mut self) -> std::result::Result<(), HistoryError> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version > SCHEMA_VERSION {
+            return Err(HistoryError::NewerSchema {
+                found: version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            // 1つのマイグレーションとバージョン更新を同じトランザクションで行う
+            let tx = self.conn.transaction()?;
+            migration(&tx)?;
+            tx.pragma_update(None, "user_version", index as i64 + 1)?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// 複数のスナップショットを一括挿入（トランザクション使用）
+    pub fn insert_snapshots(&mut self, snapshots: &[ProcessSnapshot]) -> Result<()> {
+        self.write_snapshots(snapshots, false)?;
+
+        // 今回の記録より前に完了した期間を集計テーブルに反映
+        if let Some(latest) = snapshots.iter().map(|s| s.timestamp).max() {
+            self.update_rollups(latest.timestamp())?;
+        }
+        Ok(())
+    }
+
+    /// スナップショットを取り込む（集計テーブルは更新しない、挿入した件数を返す）
+    ///
+    /// 同じ時刻・PID・プロセス名・ホストの記録が既にあれば重複として読み飛ばす。
+    /// すべて挿入した後に取り込んだ範囲を rebuild_rollups で作り直す
+    pub fn import_snapshots(&mut self, snapshots: &[ProcessSnapshot]) -> Result<usize> {
+        self.write_snapshots(snapshots, true)
+    }
+
+    fn write_snapshots(&mut self, snapshots: &[ProcessSnapshot], skip_existing: bool) -> Result<usize> {
+        if snapshots.is_empty() {
+            return Ok(0);
+        }
+
+        let mut sql = String::from(
+            "INSERT INTO process_snapshots
+             (timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status, parent_pid,
+              pss_bytes, uss_bytes, shared_clean_bytes, shared_dirty_bytes, swap_bytes, session_id, host)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15",
+        );
+        if skip_existing {
+            sql.push_str(
+                " WHERE NOT EXISTS (
+                     SELECT 1 FROM process_snapshots t
+                     WHERE t.timestamp = ?1 AND t.pid = ?3 AND t.process_name = ?2 AND t.host IS ?15
+                 )",
+            );
+        }
+
+        let tx = self.conn.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut stmt = tx.prepare(&sql)?;
+            for snapshot in snapshots {
+                // ProcessStatus を文字列に変換
+                let status_str = format!("{:?}", snapshot.status);
+
+                inserted += stmt.execute(params![
+                    snapshot.timestamp.to_rfc3339(),
+                    snapshot.process_name,
+                    snapshot.pid,
+                    snapshot.cpu_usage,
+                    snapshot.memory_bytes as i64,
+                    snapshot.thread_count as i64,
+                    status_str,
+                    snapshot.parent_pid,
+                    snapshot.smaps.map(|m| m.pss as i64),
+                    snapshot.smaps.map(|m| m.uss as i64),
+                    snapshot.smaps.map(|m| m.shared_clean as i64),
+                    snapshot.smaps.map(|m| m.shared_dirty as i64),
+                    snapshot.smaps.map(|m| m.swap as i64),
+                    self.session_id,
+                    snapshot.host,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// 集計テーブルを更新（until より前に完了した期間のみ、前回の続きから）
+    pub fn update_rollups(&self, until_epoch: i64) -> Result<RollupCounts> {
+        Ok(RollupCounts {
+            minute: self.update_rollup(RollupTable::Minute, until_epoch)?,
+            hour: self.update_rollup(RollupTable::Hour, until_epoch)?,
+        })
+    }
+
+    /// from〜to（UNIX 時刻）を含む期間の集計を生データから作り直し、続けて未反映の期間を反映する
+    ///
+    /// 生データが残っていない期間と、集計時より生データが少ない期間（--retention や
+    /// history prune で削除済み）の集計は作り直さずに残す
+    pub fn rebuild_rollups(&self, from_epoch: i64, to_epoch: i64, until_epoch: i64) -> Result<RollupCounts> {
+        let rebuild = |rollup: RollupTable| -> Result<usize> {
+            let period = rollup.period_secs();
+            let start = from_epoch.div_euclid(period) * period;
+            // 反映済みの範囲より後は update_rollup で反映する
+            let end = ((to_epoch.div_euclid(period) + 1) * period)
+                .min(self.rollup_covered_until(rollup)?.unwrap_or(start));
+            let rebuilt = if start < end {
+                self.write_rollups(rollup, start, end)?
+            } else {
+                0
+            };
+            Ok(rebuilt + self.update_rollup(rollup, until_epoch)?)
+        };
+        Ok(RollupCounts {
+            minute: rebuild(RollupTable::Minute)?,
+            hour: rebuild(RollupTable::Hour)?,
+        })
+    }
+
+    fn update_rollup(&self, rollup: RollupTable, until_epoch: i64) -> Result<usize> {
+        let period = rollup.period_secs();
+        let end = until_epoch.div_euclid(period) * period;
+        let start = match self.rollup_covered_until(rollup)? {
+            Some(covered_until) => covered_until,
+            None => match self.first_snapshot_epoch()? {
+                Some(first) => first.div_euclid(period) * period,
+                None => return Ok(0),
+            },
+        };
+        if start >= end {
+            return Ok(0);
+        }
+        self.write_rollups(rollup, start, end)
+    }
+
+    /// start〜end（UNIX 時刻、期間の境界）の生データを期間ごとに集計して書き込む
+    fn write_rollups(&self, rollup: RollupTable, start: i64, end: i64) -> Result<usize> {
+        let period = rollup.period_secs();
+        let tx = self.conn.unchecked_transaction()?;
+        let mut written = 0;
+        {
+            let mut stmt = tx.prepare(
+                "SELECT timestamp, process_name, memory_bytes, cpu_usage FROM process_snapshots
+                 WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp ASC",
+            )?;
+            let mut rows = stmt.query(params![local_timestamp(start), local_timestamp(end)])?;
+
+            // タイムスタンプ順なので、期間が変わった時点で前の期間は確定する
+            let mut current: Option<i64> = None;
+            let mut groups: HashMap<String, RollupAccumulator> = HashMap::new();
+            while let Some(row) = rows.next()? {
+                let timestamp: String = row.get(0)?;
+                let period_start =
+                    Self::parse_timestamp(&timestamp, 0)?.timestamp().div_euclid(period) * period;
+
+                if let Some(previous) = current
+                    && previous != period_start
+                {
+                    written += write_rollup_period(&tx, rollup, previous, std::mem::take(&mut groups))?;
+                }
+                current = Some(period_start);
+
+                groups.entry(row.get(1)?).or_default().push(
+                    timestamp,
+                    row.get::<_, i64>(2)? as u64,
+                    row.get(3)?,
+                );
+            }
+            if let Some(previous) = current {
+                written += write_rollup_period(&tx, rollup, previous, groups)?;
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    /// 集計テーブルが反映済みの範囲の終端（UNIX 時刻）
+    fn rollup_covered_until(&self, rollup: RollupTable) -> Result<Option<i64>> {
+        let last: Option<i64> = self.conn.query_row(
+            &format!("SELECT MAX(period_start) FROM {}", rollup.table()),
             [],
+            |row| row.get(0),
         )?;
+        Ok(last.map(|t| t + rollup.period_secs()))
+    }
+
+    /// 最も古い記録の時刻（UNIX 時刻）
+    pub fn first_snapshot_epoch(&self) -> Result<Option<i64>> {
+        let first: Option<String> =
+            self.conn
+                .query_row("SELECT MIN(timestamp) FROM process_snapshots", [], |row| row.get(0))?;
+        first
+            .map(|t| Self::parse_timestamp(&t, 0).map(|t| t.timestamp()))
+            .transpose()
+    }
 
-        // インデックス作成
+    /// 起動・終了イベントが1件でも記録されているか（記録中のライフサイクル追跡の有無）
+    pub fn has_events(&self) -> Result<bool> {
+        self.conn
+            .query_row("SELECT EXISTS (SELECT 1 FROM process_events)", [], |row| row.get(0))
+    }
+
+    /// セッションを開始し、以降の記録にそのセッション ID を付与する
+    pub fn start_session(&mut self, session: &NewSession) -> Result<i64> {
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_timestamp ON process_snapshots(timestamp)",
-            [],
+            "INSERT INTO sessions
+             (started_at, hostname, name_filter, min_memory_mb, interval_secs, tool_version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                Local::now().to_rfc3339(),
+                System::host_name(),
+                session.name_filter,
+                session.min_memory_mb.map(|mb| mb as i64),
+                session.interval_secs as i64,
+                env!("CARGO_PKG_VERSION"),
+            ],
         )?;
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_pid ON process_snapshots(pid)",
-            [],
+        let id = self.conn.last_insert_rowid();
+        self.session_id = Some(id);
+        Ok(id)
+    }
+
+    /// 記録されたセッションの一覧（古い順、スナップショット件数付き）
+    pub fn query_sessions(&self) -> Result<Vec<Session>> {
+        self.select_sessions("", [])
+    }
+
+    /// ID を指定してセッションを取得
+    pub fn get_session(&self, id: i64) -> Result<Option<Session>> {
+        Ok(self
+            .select_sessions("WHERE s.id = ?1", [id])?
+            .into_iter()
+            .next())
+    }
+
+    fn select_sessions(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Session>> {
+        let sql = format!(
+            "SELECT s.id, s.started_at, s.ended_at, s.hostname, s.name_filter, s.min_memory_mb,
+                    s.interval_secs, s.tool_version,
+                    (SELECT COUNT(*) FROM process_snapshots p WHERE p.session_id = s.id)
+             FROM sessions s {} ORDER BY s.id ASC",
+            filter
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let sessions = stmt
+            .query_map(params, |row| {
+                Ok(Session {
+                    id: row.get(0)?,
+                    started_at: row.get(1)?,
+                    ended_at: row.get(2)?,
+                    hostname: row.get(3)?,
+                    name_filter: row.get(4)?,
+                    min_memory_mb: row.get::<_, Option<i64>>(5)?.map(|mb| mb as u64),
+                    interval_secs: row.get::<_, i64>(6)? as u64,
+                    tool_version: row.get(7)?,
+                    snapshots: row.get::<_, i64>(8)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
+    /// 別の履歴データベースの記録を取り込む（1つのトランザクション）
+    ///
+    /// ホストが未設定の記録には `host` を付与する。同じホスト・時刻・PID（システム記録は
+    /// ホスト・時刻）の記録が既にあれば重複として読み飛ばす。セッションは新しい ID で取り込む。
+    /// 取り込み元は読み取り専用で開き、移行はしない（古いスキーマにない列は NULL、テーブルは読み飛ばす）。
+    /// 時刻はこのホストのローカルオフセットに揃える（記録の範囲指定や並べ替えは RFC3339 の文字列で比較するため）
+    pub fn merge_from(&mut self, source_path: &str, host: &str) -> std::result::Result<MergeCounts, HistoryError> {
+        let version: i64 = Connection::open_with_flags(source_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(HistoryError::NewerSchema {
+                found: version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+
+        self.register_local_time()?;
+        self.conn
+            .execute("ATTACH DATABASE ?1 AS source", params![read_only_uri(source_path)])?;
+        let result = self.merge_attached(host);
+        self.conn.execute_batch("DETACH DATABASE source")?;
+        Ok(result?)
+    }
+
+    fn merge_attached(&mut self, host: &str) -> Result<MergeCounts> {
+        let tx = self.conn.transaction()?;
+        let mut counts = MergeCounts::default();
+
+        // セッション: 同じ開始時刻・ホスト名・フィルタのものは既存の ID に対応付ける
+        tx.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS session_map (source_id INTEGER PRIMARY KEY, target_id INTEGER);
+             DELETE FROM temp.session_map;",
         )?;
+        let sessions: Vec<(i64, String, Option<String>, Option<String>)> = if source_columns(&tx, "sessions")?.is_empty() {
+            vec![]
+        } else {
+            let mut stmt =
+                tx.prepare("SELECT id, local_time(started_at), hostname, name_filter FROM source.sessions")?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+                .collect::<Result<_>>()?
+        };
+
+        for (source_id, started_at, hostname, name_filter) in sessions {
+            let existing: Option<i64> = tx
+                .query_row(
+                    "SELECT id FROM main.sessions
+                     WHERE started_at = ?1 AND hostname IS ?2 AND name_filter IS ?3",
+                    params![started_at, hostname, name_filter],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let target_id = match existing {
+                Some(id) => id,
+                None => {
+                    tx.execute(
+                        "INSERT INTO main.sessions
+                         (started_at, ended_at, hostname, name_filter, min_memory_mb, interval_secs, tool_version)
+                         SELECT local_time(started_at), local_time(ended_at), hostname, name_filter, min_memory_mb,
+                                interval_secs, tool_version
+                         FROM source.sessions WHERE id = ?1",
+                        params![source_id],
+                    )?;
+                    counts.sessions += 1;
+                    tx.last_insert_rowid()
+                }
+            };
+            tx.execute(
+                "INSERT INTO temp.session_map (source_id, target_id) VALUES (?1, ?2)",
+                params![source_id, target_id],
+            )?;
+        }
+
+        let last_id: i64 =
+            tx.query_row("SELECT COALESCE(MAX(id), 0) FROM main.process_snapshots", [], |row| row.get(0))?;
+        let columns = source_columns(&tx, "process_snapshots")?;
+        if !columns.is_empty() {
+            let col = |name| source_column(&columns, "s", name);
+            counts.snapshots = tx.execute(
+                &format!(
+                    "INSERT INTO main.process_snapshots
+                     (timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status, parent_pid,
+                      pss_bytes, uss_bytes, shared_clean_bytes, shared_dirty_bytes, swap_bytes, session_id, host)
+                     SELECT local_time(s.timestamp), s.process_name, s.pid, s.cpu_usage, s.memory_bytes, s.thread_count,
+                            s.status, {}, {}, {}, {}, {}, {}, m.target_id, COALESCE({host}, ?1)
+                     FROM source.process_snapshots s
+                     LEFT JOIN temp.session_map m ON m.source_id = {}
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM main.process_snapshots t
+                         WHERE t.timestamp = local_time(s.timestamp) AND t.pid = s.pid
+                           AND t.process_name = s.process_name AND t.host IS COALESCE({host}, ?1)
+                     )",
+                    col("parent_pid"),
+                    col("pss_bytes"),
+                    col("uss_bytes"),
+                    col("shared_clean_bytes"),
+                    col("shared_dirty_bytes"),
+                    col("swap_bytes"),
+                    col("session_id"),
+                    host = col("host"),
+                ),
+                params![host],
+            )?;
+        }
+
+        let columns = source_columns(&tx, "process_events")?;
+        if !columns.is_empty() {
+            let col = |name| source_column(&columns, "e", name);
+            counts.events = tx.execute(
+                &format!(
+                    "INSERT INTO main.process_events
+                     (timestamp, event, pid, process_name, parent_pid, memory_bytes, lifetime_secs, session_id, host)
+                     SELECT local_time(e.timestamp), e.event, e.pid, e.process_name, e.parent_pid, e.memory_bytes,
+                            e.lifetime_secs, m.target_id, COALESCE({host}, ?1)
+                     FROM source.process_events e
+                     LEFT JOIN temp.session_map m ON m.source_id = {}
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM main.process_events t
+                         WHERE t.timestamp = local_time(e.timestamp) AND t.event = e.event AND t.pid = e.pid
+                           AND t.host IS COALESCE({host}, ?1)
+                     )",
+                    col("session_id"),
+                    host = col("host"),
+                ),
+                params![host],
+            )?;
+        }
+
+        let columns = source_columns(&tx, "system_snapshots")?;
+        if !columns.is_empty() {
+            let col = |name| source_column(&columns, "y", name);
+            counts.system_snapshots = tx.execute(
+                &format!(
+                    "INSERT INTO main.system_snapshots
+                     (timestamp, total_memory_bytes, used_memory_bytes, available_memory_bytes,
+                      total_swap_bytes, used_swap_bytes, load_average_1, load_average_5, load_average_15,
+                      cpu_count, cpu_usage, session_id, host)
+                     SELECT local_time(y.timestamp), y.total_memory_bytes, y.used_memory_bytes, y.available_memory_bytes,
+                            y.total_swap_bytes, y.used_swap_bytes, y.load_average_1, y.load_average_5,
+                            y.load_average_15, y.cpu_count, y.cpu_usage, m.target_id, COALESCE({host}, ?1)
+                     FROM source.system_snapshots y
+                     LEFT JOIN temp.session_map m ON m.source_id = {}
+                     WHERE NOT EXISTS (
+                         SELECT 1 FROM main.system_snapshots t
+                         WHERE t.timestamp = local_time(y.timestamp) AND t.host IS COALESCE({host}, ?1)
+                     )",
+                    col("session_id"),
+                    host = col("host"),
+                ),
+                params![host],
+            )?;
+        }
+
+        // alerts は session_id・host と同時に追加されたテーブル
+        if !source_columns(&tx, "alerts")?.is_empty() {
+            counts.alerts = tx.execute(
+                "INSERT INTO main.alerts
+                 (timestamp, state, rule, metric, value, threshold, name_filter, session_id, host)
+                 SELECT local_time(a.timestamp), a.state, a.rule, a.metric, a.value, a.threshold, a.name_filter,
+                        m.target_id, COALESCE(a.host, ?1)
+                 FROM source.alerts a
+                 LEFT JOIN temp.session_map m ON m.source_id = a.session_id
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM main.alerts t
+                     WHERE t.timestamp = local_time(a.timestamp) AND t.rule = a.rule
+                       AND t.name_filter IS a.name_filter AND t.host IS COALESCE(a.host, ?1)
+                 )",
+                params![host],
+            )?;
+        }
+
+        // 取り込んだスナップショットの時刻の範囲（集計テーブルの作り直しに使う）
+        {
+            let mut stmt = tx.prepare("SELECT timestamp FROM main.process_snapshots WHERE id > ?1")?;
+            let mut rows = stmt.query(params![last_id])?;
+            while let Some(row) = rows.next()? {
+                let epoch = Self::parse_timestamp(&row.get::<_, String>(0)?, 0)?.timestamp();
+                counts.range = Some(counts.range.map_or((epoch, epoch), |(first, last)| {
+                    (first.min(epoch), last.max(epoch))
+                }));
+            }
+        }
+
+        tx.commit()?;
+        Ok(counts)
+    }
+
+    /// ホスト全体のスナップショットを挿入
+    ///
+    /// 毎回の記録で呼ばれるため、セッションの終了時刻（最後の記録時刻）もここで更新する。
+    /// Ctrl+C で終了した場合も直前の記録時刻が残る
+    pub fn insert_system_snapshot(&self, snapshot: &SystemSnapshot) -> Result<()> {
+        let timestamp = snapshot.timestamp.to_rfc3339();
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_process_name ON process_snapshots(process_name)",
-            [],
+            "INSERT INTO system_snapshots
+             (timestamp, total_memory_bytes, used_memory_bytes, available_memory_bytes,
+              total_swap_bytes, used_swap_bytes, load_average_1, load_average_5, load_average_15,
+              cpu_count, cpu_usage, session_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                timestamp,
+                snapshot.total_memory_bytes as i64,
+                snapshot.used_memory_bytes as i64,
+                snapshot.available_memory_bytes as i64,
+                snapshot.total_swap_bytes as i64,
+                snapshot.used_swap_bytes as i64,
+                snapshot.load_average_1,
+                snapshot.load_average_5,
+                snapshot.load_average_15,
+                snapshot.cpu_count as i64,
+                snapshot.cpu_usage,
+                self.session_id,
+            ],
         )?;
 
+        if let Some(session_id) = self.session_id {
+            self.conn.execute(
+                "UPDATE sessions SET ended_at = ?1 WHERE id = ?2",
+                params![timestamp, session_id],
+            )?;
+        }
         Ok(())
     }
 
-    /// 複数のスナップショットを一括挿入（トランザクション使用）
-    pub fn insert_snapshots(&mut self, snapshots: &[ProcessSnapshot]) -> Result<()> {
-        if snapshots.is_empty() {
+    /// 起動・終了イベントを一括挿入（トランザクション使用）
+    pub fn insert_events(&mut self, events: &[ProcessEvent]) -> Result<()> {
+        if events.is_empty() {
             return Ok(());
         }
 
         let tx = self.conn.transaction()?;
 
-        for snapshot in snapshots {
-            // ProcessStatus を文字列に変換
-            let status_str = format!("{:?}", snapshot.status);
+        for event in events {
+            tx.execute(
+                "INSERT INTO process_events
+                 (timestamp, event, pid, process_name, parent_pid, memory_bytes, lifetime_secs, session_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    event.timestamp.to_rfc3339(),
+                    event.kind.as_str(),
+                    event.pid,
+                    event.process_name,
+                    event.parent_pid,
+                    event.memory_bytes as i64,
+                    event.lifetime_secs,
+                    self.session_id,
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// アラートの発火・解除を一括挿入（トランザクション使用）
+    pub fn insert_alerts(&mut self, alerts: &[AlertEvent]) -> Result<()> {
+        if alerts.is_empty() {
+            return Ok(());
+        }
 
+        let tx = self.conn.transaction()?;
+
+        for alert in alerts {
             tx.execute(
-                "INSERT INTO process_snapshots
-                 (timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                "INSERT INTO alerts
+                 (timestamp, state, rule, metric, value, threshold, name_filter, session_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
                 params![
-                    snapshot.timestamp.to_rfc3339(),
-                    snapshot.process_name,
-                    snapshot.pid,
-                    snapshot.cpu_usage,
-                    snapshot.memory_bytes as i64,
-                    snapshot.thread_count as i64,
-                    status_str,
+                    alert.timestamp.to_rfc3339(),
+                    alert.state.as_str(),
+                    alert.rule,
+                    alert.metric,
+                    alert.value,
+                    alert.threshold,
+                    alert.name_filter,
+                    self.session_id,
                 ],
             )?;
         }
@@ -93,80 +1157,483 @@ impl ProcessHistory {
         Ok(())
     }
 
-    /// スナップショットをクエリ（オプションのフィルタ付き）
-    pub fn query_snapshots(
-        &self,
-        from: Option<&str>,
-        to: Option<&str>,
-        name: Option<&str>,
-    ) -> Result<Vec<ProcessSnapshot>> {
-        // SQLクエリを構築
-        let mut sql = String::from(
-            "SELECT timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status \
-             FROM process_snapshots WHERE 1=1"
+    /// cutoff より古い記録をバッチ単位で削除
+    ///
+    /// 1回の削除を batch_size 行に抑え、長時間のロックで記録側を止めないようにする
+    pub fn prune_before(&self, cutoff: &str, batch_size: usize) -> Result<PruneCounts> {
+        Ok(PruneCounts {
+            snapshots: self.delete_in_batches("process_snapshots", cutoff, batch_size)?,
+            events: self.delete_in_batches("process_events", cutoff, batch_size)?,
+            system_snapshots: self.delete_in_batches("system_snapshots", cutoff, batch_size)?,
+            alerts: self.delete_in_batches("alerts", cutoff, batch_size)?,
+            // 最後の記録が cutoff より前のセッション（記録はすべて削除済み）
+            sessions: self.conn.execute(
+                "DELETE FROM sessions WHERE COALESCE(ended_at, started_at) < ?1",
+                params![cutoff],
+            )?,
+        })
+    }
+
+    fn delete_in_batches(&self, table: &str, cutoff: &str, batch_size: usize) -> Result<usize> {
+        let sql = format!(
+            "DELETE FROM {table} WHERE id IN \
+             (SELECT id FROM {table} WHERE timestamp < ?1 LIMIT ?2)"
         );
+        let mut total = 0;
+        loop {
+            let deleted = self.conn.execute(&sql, params![cutoff, batch_size as i64])?;
+            total += deleted;
+            if deleted < batch_size {
+                return Ok(total);
+            }
+        }
+    }
 
+    /// データベースのサイズと未使用領域（バイト）
+    pub fn storage_bytes(&self) -> Result<(u64, u64)> {
+        let pragma = |name: &str| -> Result<i64> {
+            self.conn
+                .query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+        };
+        let page_size = pragma("page_size")? as u64;
+        Ok((
+            pragma("page_count")? as u64 * page_size,
+            pragma("freelist_count")? as u64 * page_size,
+        ))
+    }
+
+    /// 未使用領域を解放してファイルを縮小
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")
+    }
+
+    /// RFC3339 の時刻をローカルオフセットに変換する SQL 関数 local_time() を登録（NULL はそのまま）
+    fn register_local_time(&self) -> Result<()> {
+        self.conn.create_scalar_function(
+            "local_time",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let timestamp: Option<String> = ctx.get(0)?;
+                timestamp
+                    .map(|t| {
+                        DateTime::parse_from_rfc3339(&t)
+                            .map(|t| t.with_timezone(&Local).to_rfc3339())
+                            .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+                    })
+                    .transpose()
+            },
+        )
+    }
+
+    /// プロセス名の条件を SQL 関数 process_name_matches() として登録（None はすべてに一致）
+    fn register_name_filter(&self, matcher: Option<&NameMatcher>) -> Result<()> {
+        let matcher = matcher.cloned();
+        self.conn.create_scalar_function(
+            "process_name_matches",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| {
+                let name: String = ctx.get(0)?;
+                Ok(matcher.as_ref().is_none_or(|m| m.is_match(&name)))
+            },
+        )
+    }
+
+    /// フィルタ条件から WHERE 句とパラメータを構築
+    fn build_filter(&self, filter: &RecordFilter) -> Result<(String, Vec<String>)> {
+        let mut clause = String::from("WHERE 1=1");
         let mut params: Vec<String> = vec![];
 
-        if let Some(from_time) = from {
-            sql.push_str(" AND timestamp >= ?");
+        if let Some(from_time) = filter.from {
+            clause.push_str(" AND timestamp >= ?");
             params.push(from_time.to_string());
         }
 
-        if let Some(to_time) = to {
-            sql.push_str(" AND timestamp <= ?");
+        if let Some(to_time) = filter.to {
+            clause.push_str(" AND timestamp <= ?");
             params.push(to_time.to_string());
         }
 
-        if let Some(process_name) = name {
-            sql.push_str(" AND process_name LIKE ?");
-            params.push(format!("%{}%", process_name));
+        if filter.name.is_some() {
+            self.register_name_filter(filter.name)?;
+            clause.push_str(" AND process_name_matches(process_name)");
+        }
+
+        if let Some(session_id) = filter.session {
+            clause.push_str(" AND session_id = ?");
+            params.push(session_id.to_string());
         }
 
-        sql.push_str(" ORDER BY timestamp ASC");
+        if let Some(host) = filter.host {
+            clause.push_str(" AND host = ?");
+            params.push(host.to_string());
+        }
+
+        Ok((clause, params))
+    }
+
+    /// スナップショットをクエリ（オプションのフィルタ付き）
+    pub fn query_snapshots(&self, filter: &RecordFilter) -> Result<Vec<ProcessSnapshot>> {
+        let mut snapshots = vec![];
+        self.for_each_snapshot(filter, |snapshot| {
+            snapshots.push(snapshot);
+            Ok::<_, rusqlite::Error>(())
+        })?;
+        Ok(snapshots)
+    }
+
+    /// スナップショットを1件ずつ読み出して `visit` に渡す（全件をメモリに載せない）
+    ///
+    /// 戻り値は読み出した件数。`visit` がエラーを返した時点で中断する
+    pub fn for_each_snapshot<E: From<rusqlite::Error>>(
+        &self,
+        filter: &RecordFilter,
+        mut visit: impl FnMut(ProcessSnapshot) -> std::result::Result<(), E>,
+    ) -> std::result::Result<usize, E> {
+        // SQLクエリを構築
+        let (filter, params) = self.build_filter(filter)?;
+        let sql = format!(
+            "SELECT timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status, parent_pid, \
+             pss_bytes, uss_bytes, shared_clean_bytes, shared_dirty_bytes, swap_bytes, host \
+             FROM process_snapshots {} ORDER BY timestamp ASC",
+            filter
+        );
 
         // クエリを実行
         let mut stmt = self.conn.prepare(&sql)?;
-
-        let snapshots = match params.len() {
-            0 => stmt.query_map([], Self::row_to_snapshot)?,
-            1 => stmt.query_map([&params[0]], Self::row_to_snapshot)?,
-            2 => stmt.query_map([&params[0], &params[1]], Self::row_to_snapshot)?,
-            3 => stmt.query_map([&params[0], &params[1], &params[2]], Self::row_to_snapshot)?,
-            _ => unreachable!(),
+        let mut count = 0;
+        for snapshot in stmt.query_map(params_from_iter(params.iter()), Self::row_to_snapshot)? {
+            visit(snapshot?)?;
+            count += 1;
         }
-        .collect::<Result<Vec<_>>>()?;
+
+        Ok(count)
+    }
+
+    /// ホスト全体のスナップショットをクエリ（プロセス名の条件は使わない）
+    pub fn query_system_snapshots(&self, filter: &RecordFilter) -> Result<Vec<SystemSnapshot>> {
+        let (filter, params) = self.build_filter(&RecordFilter {
+            name: None,
+            ..*filter
+        })?;
+        let sql = format!(
+            "SELECT timestamp, total_memory_bytes, used_memory_bytes, available_memory_bytes, \
+             total_swap_bytes, used_swap_bytes, load_average_1, load_average_5, load_average_15, \
+             cpu_count, cpu_usage \
+             FROM system_snapshots {} ORDER BY timestamp ASC",
+            filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let snapshots = stmt
+            .query_map(params_from_iter(params.iter()), |row| {
+                let timestamp_str: String = row.get(0)?;
+                Ok(SystemSnapshot {
+                    timestamp: Self::parse_timestamp(&timestamp_str, 0)?,
+                    total_memory_bytes: row.get::<_, i64>(1)? as u64,
+                    used_memory_bytes: row.get::<_, i64>(2)? as u64,
+                    available_memory_bytes: row.get::<_, i64>(3)? as u64,
+                    total_swap_bytes: row.get::<_, i64>(4)? as u64,
+                    used_swap_bytes: row.get::<_, i64>(5)? as u64,
+                    load_average_1: row.get(6)?,
+                    load_average_5: row.get(7)?,
+                    load_average_15: row.get(8)?,
+                    cpu_count: row.get::<_, i64>(9)? as usize,
+                    cpu_usage: row.get(10)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(snapshots)
     }
 
+    /// 起動・終了イベントをクエリ（オプションのフィルタ付き）
+    pub fn query_events(&self, filter: &RecordFilter) -> Result<Vec<ProcessEvent>> {
+        let (filter, params) = self.build_filter(filter)?;
+        let sql = format!(
+            "SELECT timestamp, event, pid, process_name, parent_pid, memory_bytes, lifetime_secs \
+             FROM process_events {} ORDER BY timestamp ASC",
+            filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let events = stmt
+            .query_map(params_from_iter(params.iter()), |row| {
+                // 未知のイベント種別（新しいバージョンで追加されたもの等）は読み飛ばす
+                let event_str: String = row.get(1)?;
+                let Some(kind) = ProcessEventKind::parse(&event_str) else {
+                    return Ok(None);
+                };
+                let timestamp_str: String = row.get(0)?;
+                Ok(Some(ProcessEvent {
+                    timestamp: Self::parse_timestamp(&timestamp_str, 0)?,
+                    kind,
+                    pid: row.get(2)?,
+                    process_name: row.get(3)?,
+                    parent_pid: row.get(4)?,
+                    memory_bytes: row.get::<_, i64>(5)? as u64,
+                    lifetime_secs: row.get(6)?,
+                }))
+            })?
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Ok(events)
+    }
+
+    /// 固定幅の時間窓ごとに集計
+    ///
+    /// 時間窓と範囲が集計テーブルの期間に揃っていれば、最も粗い集計テーブルを使い、
+    /// 集計済みの範囲より後だけを生データから集計する。使った集計テーブルも返す
+    /// 集計テーブルはセッション・ホストを区別しないため、それらの指定時は常に生データから集計する
+    pub fn query_buckets(
+        &self,
+        filter: &RecordFilter,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<(Vec<BucketStats>, Option<RollupTable>)> {
+        let epoch = |ts: Option<&str>| {
+            ts.map(|t| Self::parse_timestamp(t, 0).map(|t| t.timestamp()))
+                .transpose()
+        };
+        let (from_epoch, to_epoch) = (epoch(filter.from)?, epoch(filter.to)?);
+
+        let rollup = if filter.session.is_some() || filter.host.is_some() {
+            None
+        } else {
+            self.choose_rollup(from_epoch, to_epoch, bucket_secs, utc_offset_secs)?
+        };
+        let Some((rollup, covered_until)) = rollup else {
+            let buckets = self.query_raw_buckets(filter, bucket_secs, utc_offset_secs)?;
+            return Ok((buckets, None));
+        };
+
+        let rollup_end = to_epoch.map_or(covered_until, |t| t.min(covered_until));
+        let mut buckets = self.query_rollup_buckets(
+            rollup,
+            from_epoch,
+            rollup_end,
+            filter.name,
+            bucket_secs,
+            utc_offset_secs,
+        )?;
+
+        // 集計済みの範囲より後（to ちょうどの記録を含む）は生データから
+        if to_epoch.is_none_or(|t| t >= covered_until) {
+            let raw_from = local_timestamp(covered_until);
+            let tail = RecordFilter {
+                from: Some(&raw_from),
+                ..*filter
+            };
+            let raw = self.query_raw_buckets(&tail, bucket_secs, utc_offset_secs)?;
+            // 境界をまたぐ時間窓は1つにまとめる
+            for bucket in raw {
+                match buckets.last_mut() {
+                    Some(last) if last.start == bucket.start => last.combine(&bucket),
+                    _ => buckets.push(bucket),
+                }
+            }
+        }
+
+        Ok((buckets, Some(rollup)))
+    }
+
+    /// 時間窓・UTC オフセット・範囲の端がすべて期間に揃う、最も粗い集計テーブルを選ぶ
+    fn choose_rollup(
+        &self,
+        from: Option<i64>,
+        to: Option<i64>,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Option<(RollupTable, i64)>> {
+        for rollup in RollupTable::COARSEST_FIRST {
+            let period = rollup.period_secs();
+            let aligned = |t: Option<i64>| t.is_none_or(|t| t % period == 0);
+            if bucket_secs % period != 0
+                || utc_offset_secs % period != 0
+                || !aligned(from)
+                || !aligned(to)
+            {
+                continue;
+            }
+
+            if let Some(covered_until) = self.rollup_covered_until(rollup)?
+                && from.is_none_or(|f| f < covered_until)
+            {
+                return Ok(Some((rollup, covered_until)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 集計テーブルから時間窓ごとに集計（[from, to) の期間）
+    ///
+    /// 複数のプロセス名が一致する場合、合計値は名前ごとの値の和で近似する（ピークは上限値）
+    fn query_rollup_buckets(
+        &self,
+        rollup: RollupTable,
+        from: Option<i64>,
+        to: i64,
+        name: Option<&NameMatcher>,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Vec<BucketStats>> {
+        self.register_name_filter(name)?;
+        let sql = format!(
+            "WITH periods AS (
+                SELECT period_start,
+                       MAX(ticks) AS ticks,
+                       SUM(avg_total_memory_bytes) AS avg_total_memory,
+                       SUM(max_total_memory_bytes) AS max_total_memory,
+                       SUM(avg_total_cpu_percent) AS avg_total_cpu,
+                       SUM(max_total_cpu_percent) AS max_total_cpu,
+                       SUM(avg_process_count) AS avg_process_count,
+                       SUM(max_process_count) AS max_process_count,
+                       MAX(max_memory_bytes) AS max_process_memory
+                FROM {}
+                WHERE period_start >= ?1 AND period_start < ?2 AND process_name_matches(process_name)
+                GROUP BY period_start
+            )
+            SELECT ((period_start + ?3) / ?4) * ?4 - ?3 AS bucket_start,
+                   SUM(ticks),
+                   SUM(avg_total_memory * ticks) / SUM(ticks), MAX(max_total_memory),
+                   SUM(avg_total_cpu * ticks) / SUM(ticks), MAX(max_total_cpu),
+                   SUM(avg_process_count * ticks) / SUM(ticks), MAX(max_process_count),
+                   MAX(max_process_memory)
+            FROM periods
+            GROUP BY bucket_start
+            ORDER BY bucket_start ASC",
+            rollup.table()
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let buckets = stmt
+            .query_map(
+                params![
+                    from.unwrap_or(i64::MIN),
+                    to,
+                    utc_offset_secs,
+                    bucket_secs,
+                ],
+                Self::row_to_bucket,
+            )?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(buckets)
+    }
+
+    /// 生データから時間窓ごとに集計（集計は SQL 側で実行）
+    ///
+    /// 1回の記録（同一タイムスタンプ）ごとに合計を出し、それを時間窓ごとに平均・最大化する。
+    /// 窓の境界は `utc_offset_secs` でローカル時刻に揃える（1d なら現地の0時区切り）
+    fn query_raw_buckets(
+        &self,
+        filter: &RecordFilter,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Vec<BucketStats>> {
+        let (filter, mut params) = self.build_filter(filter)?;
+        let sql = format!(
+            "WITH ticks AS (
+                SELECT CAST(strftime('%s', timestamp) AS INTEGER) AS epoch,
+                       SUM(memory_bytes) AS total_memory,
+                       SUM(cpu_usage) AS total_cpu,
+                       COUNT(DISTINCT pid) AS process_count,
+                       MAX(memory_bytes) AS max_process_memory
+                FROM process_snapshots {}
+                GROUP BY timestamp
+            )
+            SELECT ((epoch + ?) / ?) * ? - ? AS bucket_start,
+                   COUNT(*),
+                   AVG(total_memory), MAX(total_memory),
+                   AVG(total_cpu), MAX(total_cpu),
+                   AVG(process_count), MAX(process_count),
+                   MAX(max_process_memory)
+            FROM ticks
+            GROUP BY bucket_start
+            ORDER BY bucket_start ASC",
+            filter
+        );
+        params.extend([
+            utc_offset_secs.to_string(),
+            bucket_secs.to_string(),
+            bucket_secs.to_string(),
+            utc_offset_secs.to_string(),
+        ]);
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let buckets = stmt
+            .query_map(params_from_iter(params.iter()), Self::row_to_bucket)?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(buckets)
+    }
+
+    /// 時間窓ごとの集計結果の行を BucketStats に変換
+    fn row_to_bucket(row: &rusqlite::Row) -> Result<BucketStats> {
+        Ok(BucketStats {
+            start: local_timestamp(row.get(0)?),
+            samples: row.get::<_, i64>(1)? as usize,
+            avg_total_memory_bytes: row.get(2)?,
+            peak_total_memory_bytes: row.get::<_, i64>(3)? as u64,
+            avg_total_cpu_percent: row.get(4)?,
+            peak_total_cpu_percent: row.get(5)?,
+            avg_process_count: row.get(6)?,
+            max_process_count: row.get::<_, i64>(7)? as usize,
+            peak_process_memory_bytes: row.get::<_, i64>(8)? as u64,
+        })
+    }
+
     /// データベースの行をProcessSnapshotに変換
     fn row_to_snapshot(row: &rusqlite::Row) -> Result<ProcessSnapshot> {
         let timestamp_str: String = row.get(0)?;
-        let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
-                0,
-                rusqlite::types::Type::Text,
-                Box::new(e),
-            ))?
-            .with_timezone(&chrono::Local);
+        let timestamp = Self::parse_timestamp(&timestamp_str, 0)?;
 
         let status_str: String = row.get(6)?;
         let status = Self::parse_status(&status_str);
 
+        // smaps 由来のカラムは PSS が記録されている場合のみ有効
+        let smaps = match row.get::<_, Option<i64>>(8)? {
+            Some(pss) => Some(SmapsMemory {
+                pss: pss as u64,
+                uss: row.get::<_, Option<i64>>(9)?.unwrap_or(0) as u64,
+                shared_clean: row.get::<_, Option<i64>>(10)?.unwrap_or(0) as u64,
+                shared_dirty: row.get::<_, Option<i64>>(11)?.unwrap_or(0) as u64,
+                swap: row.get::<_, Option<i64>>(12)?.unwrap_or(0) as u64,
+            }),
+            None => None,
+        };
+
         Ok(ProcessSnapshot {
             timestamp,
             process_name: row.get(1)?,
             pid: row.get(2)?,
+            parent_pid: row.get(7)?,
             cpu_usage: row.get(3)?,
             memory_bytes: row.get::<_, i64>(4)? as u64,
+            smaps,
             thread_count: row.get::<_, i64>(5)? as usize,
             status,
+            host: row.get(13)?,
         })
     }
 
+    /// RFC3339 のタイムスタンプ文字列をローカル時刻に変換
+    fn parse_timestamp(s: &str, column: usize) -> Result<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Local))
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
+                column,
+                rusqlite::types::Type::Text,
+                Box::new(e),
+            ))
+    }
+
     /// ステータス文字列をProcessStatusに変換
-    fn parse_status(status_str: &str) -> ProcessStatus {
+    pub fn parse_status(status_str: &str) -> ProcessStatus {
         match status_str {
             "Run" => ProcessStatus::Run,
             "Sleep" => ProcessStatus::Sleep,
@@ -176,3 +1643,407 @@ impl ProcessHistory {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テストごとに別の一時ファイルを使う
+    fn temp_db(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "rs-process-monitor-{}-{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn user_version(conn: &Connection) -> i64 {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_database_is_current_version() {
+        let history = ProcessHistory::new(":memory:").unwrap();
+        assert_eq!(user_version(&history.conn), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migrates_legacy_database_in_place() {
+        let path = temp_db("legacy");
+        {
+            // バージョン管理導入前の最初のスキーマ
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE process_snapshots (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp TEXT NOT NULL,
+                    process_name TEXT NOT NULL,
+                    pid INTEGER NOT NULL,
+                    cpu_usage REAL NOT NULL,
+                    memory_bytes INTEGER NOT NULL,
+                    thread_count INTEGER NOT NULL,
+                    status TEXT NOT NULL
+                );
+                INSERT INTO process_snapshots
+                    (timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status)
+                VALUES ('2026-01-05T14:00:00+09:00', 'httpd', 1, 0.5, 1024, 1, 'Sleep');",
+            )
+            .unwrap();
+        }
+
+        let history = ProcessHistory::new(&path).unwrap();
+        assert_eq!(user_version(&history.conn), SCHEMA_VERSION);
+
+        let snapshots = history.query_snapshots(&RecordFilter::default()).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].parent_pid, None);
+        assert!(history.query_events(&RecordFilter::default()).unwrap().is_empty());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prune_before_deletes_in_batches() {
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let start = DateTime::parse_from_rfc3339("2026-01-05T14:00:00+09:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let snapshots: Vec<ProcessSnapshot> = (0..5)
+            .map(|i| ProcessSnapshot {
+                timestamp: start + chrono::Duration::hours(i),
+                process_name: "httpd".to_string(),
+                pid: 1,
+                parent_pid: None,
+                cpu_usage: 0.0,
+                memory_bytes: 1024,
+                smaps: None,
+                thread_count: 1,
+                status: ProcessStatus::Sleep,
+                host: None,
+            })
+            .collect();
+        history.insert_snapshots(&snapshots).unwrap();
+
+        // 14:00〜16:00 の3件が対象（バッチサイズより多い）
+        let counts = history
+            .prune_before(&(start + chrono::Duration::hours(3)).to_rfc3339(), 2)
+            .unwrap();
+        assert_eq!(counts.snapshots, 3);
+        assert_eq!(history.query_snapshots(&RecordFilter::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rollup_buckets_match_raw() {
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let start = DateTime::from_timestamp(1_767_589_200, 0) // 正時
+            .unwrap()
+            .with_timezone(&Local);
+        // 2時間分 + 次の期間の1件（30秒ごと、2プロセス）
+        let snapshots: Vec<ProcessSnapshot> = (0..=240)
+            .flat_map(|i| {
+                (1..=2).map(move |pid| ProcessSnapshot {
+                    timestamp: start + chrono::Duration::seconds(30 * i),
+                    process_name: "php-fpm".to_string(),
+                    pid,
+                    parent_pid: None,
+                    cpu_usage: (i % 7) as f32,
+                    memory_bytes: 1024 * (100 + (i * pid as i64) % 50) as u64,
+                    smaps: None,
+                    thread_count: 1,
+                    status: ProcessStatus::Sleep,
+                    host: None,
+                })
+            })
+            .collect();
+        history.insert_snapshots(&snapshots).unwrap();
+
+        let to = (start + chrono::Duration::hours(2)).to_rfc3339();
+        let filter = RecordFilter {
+            to: Some(&to),
+            ..Default::default()
+        };
+        let raw = history.query_raw_buckets(&filter, 1800, 0).unwrap();
+        let (rolled, rollup) = history.query_buckets(&filter, 1800, 0).unwrap();
+
+        assert_eq!(rollup, Some(RollupTable::Minute));
+        assert_eq!(raw.len(), rolled.len());
+        for (r, b) in raw.iter().zip(&rolled) {
+            assert_eq!(r.start, b.start);
+            assert_eq!(r.samples, b.samples);
+            assert!((r.avg_total_memory_bytes - b.avg_total_memory_bytes).abs() < 1e-6);
+            assert_eq!(r.peak_total_memory_bytes, b.peak_total_memory_bytes);
+            assert!((r.avg_total_cpu_percent - b.avg_total_cpu_percent).abs() < 1e-6);
+            assert_eq!(r.peak_process_memory_bytes, b.peak_process_memory_bytes);
+        }
+
+        // 時間単位の時間窓では時間ごとの集計テーブルを使う
+        let (_, rollup) = history.query_buckets(&RecordFilter::default(), 3600, 0).unwrap();
+        assert_eq!(rollup, Some(RollupTable::Hour));
+        // 期間に揃わない時間窓は生データ
+        let (_, rollup) = history.query_buckets(&RecordFilter::default(), 90, 0).unwrap();
+        assert_eq!(rollup, None);
+    }
+
+    fn rollup_samples(history: &ProcessHistory, rollup: RollupTable) -> Vec<(i64, i64)> {
+        let mut stmt = history
+            .conn
+            .prepare(&format!("SELECT period_start, samples FROM {} ORDER BY period_start", rollup.table()))
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rebuild_keeps_pruned_rollups() {
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let start = DateTime::from_timestamp(1_767_589_200, 0) // 正時
+            .unwrap()
+            .with_timezone(&Local);
+        // 3時間分 + 次の期間の1件（1分ごと）
+        let snapshots: Vec<ProcessSnapshot> = (0..=180)
+            .map(|i| ProcessSnapshot {
+                timestamp: start + chrono::Duration::minutes(i),
+                process_name: "httpd".to_string(),
+                pid: 1,
+                parent_pid: None,
+                cpu_usage: 0.0,
+                memory_bytes: 1024,
+                smaps: None,
+                thread_count: 1,
+                status: ProcessStatus::Sleep,
+                host: None,
+            })
+            .collect();
+        history.insert_snapshots(&snapshots).unwrap();
+        let before = rollup_samples(&history, RollupTable::Hour);
+        assert_eq!(before.len(), 3);
+
+        // 1時間目はすべて、2時間目は半分の生データを削除
+        history
+            .prune_before(&(start + chrono::Duration::minutes(90)).to_rfc3339(), 100)
+            .unwrap();
+        let first = history.first_snapshot_epoch().unwrap().unwrap();
+        let end = (start + chrono::Duration::hours(3)).timestamp();
+        let counts = history.rebuild_rollups(first, end, end).unwrap();
+
+        // 生データがすべて残っている3時間目のみ作り直す
+        assert_eq!(counts.hour, 1);
+        assert_eq!(rollup_samples(&history, RollupTable::Hour), before);
+        assert_eq!(rollup_samples(&history, RollupTable::Minute).len(), 180);
+    }
+
+    #[test]
+    fn test_snapshots_are_tagged_with_session() {
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let snapshot = ProcessSnapshot {
+            timestamp: Local::now(),
+            process_name: "httpd".to_string(),
+            pid: 1,
+            parent_pid: None,
+            cpu_usage: 0.0,
+            memory_bytes: 1024,
+            smaps: None,
+            thread_count: 1,
+            status: ProcessStatus::Sleep,
+            host: None,
+        };
+        // セッション開始前の記録はセッション不明
+        history.insert_snapshots(std::slice::from_ref(&snapshot)).unwrap();
+
+        let id = history
+            .start_session(&NewSession {
+                name_filter: Some("httpd"),
+                min_memory_mb: Some(10),
+                interval_secs: 5,
+            })
+            .unwrap();
+        history.insert_snapshots(&[snapshot.clone(), snapshot]).unwrap();
+
+        let filter = RecordFilter {
+            session: Some(id),
+            ..Default::default()
+        };
+        assert_eq!(history.query_snapshots(&filter).unwrap().len(), 2);
+        assert_eq!(history.query_snapshots(&RecordFilter::default()).unwrap().len(), 3);
+
+        let session = history.get_session(id).unwrap().unwrap();
+        assert_eq!(session.name_filter.as_deref(), Some("httpd"));
+        assert_eq!(session.min_memory_mb, Some(10));
+        assert_eq!(session.snapshots, 2);
+        assert_eq!(session.ended_at, None);
+        assert!(history.get_session(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_merge_tags_host_and_skips_duplicates() {
+        let source_path = temp_db("merge-source");
+        {
+            let mut source = ProcessHistory::new(&source_path).unwrap();
+            source
+                .start_session(&NewSession {
+                    name_filter: Some("httpd"),
+                    min_memory_mb: None,
+                    interval_secs: 5,
+                })
+                .unwrap();
+            let snapshot = ProcessSnapshot {
+                timestamp: Local::now(),
+                process_name: "httpd".to_string(),
+                pid: 1,
+                parent_pid: None,
+                cpu_usage: 0.0,
+                memory_bytes: 1024,
+                smaps: None,
+                thread_count: 1,
+                status: ProcessStatus::Sleep,
+                host: None,
+            };
+            let other_pid = ProcessSnapshot {
+                pid: 2,
+                ..snapshot.clone()
+            };
+            source.insert_snapshots(&[snapshot, other_pid]).unwrap();
+            source
+                .insert_alerts(&[AlertEvent {
+                    timestamp: Local::now(),
+                    state: AlertState::Firing,
+                    rule: "count < 3".to_string(),
+                    metric: "count".to_string(),
+                    value: 2.0,
+                    threshold: 3.0,
+                    name_filter: "httpd".to_string(),
+                }])
+                .unwrap();
+        }
+
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let counts = history.merge_from(&source_path, "web01").unwrap();
+        assert_eq!((counts.snapshots, counts.sessions, counts.alerts), (2, 1, 1));
+
+        // 同じファイルをもう一度取り込んでも重複しない
+        let counts = history.merge_from(&source_path, "web01").unwrap();
+        assert_eq!((counts.snapshots, counts.sessions, counts.alerts), (0, 0, 0));
+
+        let filter = RecordFilter {
+            host: Some("web01"),
+            ..Default::default()
+        };
+        let merged = history.query_snapshots(&filter).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].host.as_deref(), Some("web01"));
+        assert_eq!(history.query_sessions().unwrap()[0].snapshots, 2);
+
+        let _ = std::fs::remove_file(&source_path);
+    }
+
+    #[test]
+    fn test_merge_normalizes_timestamp_offsets() {
+        let source_path = temp_db("merge-offset");
+        {
+            let source = ProcessHistory::new(&source_path).unwrap();
+            // 文字列の順序と実際の順序が逆になる、オフセットの異なる2件
+            source
+                .conn
+                .execute_batch(
+                    "INSERT INTO process_snapshots
+                        (timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status)
+                     VALUES ('2026-01-05T14:30:00+09:00', 'httpd', 1, 0.0, 1024, 1, 'Sleep'),
+                            ('2026-01-05T01:00:00-05:00', 'httpd', 2, 0.0, 1024, 1, 'Sleep');",
+                )
+                .unwrap();
+        }
+
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let counts = history.merge_from(&source_path, "web01").unwrap();
+        let first = DateTime::parse_from_rfc3339("2026-01-05T14:30:00+09:00").unwrap();
+        let last = DateTime::parse_from_rfc3339("2026-01-05T01:00:00-05:00").unwrap();
+        assert_eq!(counts.range, Some((first.timestamp(), last.timestamp())));
+
+        let merged = history.query_snapshots(&RecordFilter::default()).unwrap();
+        assert_eq!(merged.iter().map(|s| s.pid).collect::<Vec<_>>(), [1, 2]);
+        let stored: Vec<String> = history
+            .conn
+            .prepare("SELECT timestamp FROM process_snapshots ORDER BY timestamp")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(stored, [local_timestamp(first.timestamp()), local_timestamp(last.timestamp())]);
+
+        // 取り込み直しても重複しない
+        assert_eq!(history.merge_from(&source_path, "web01").unwrap().snapshots, 0);
+
+        let _ = std::fs::remove_file(&source_path);
+    }
+
+    #[test]
+    fn test_merge_reads_older_source_without_migrating() {
+        let source_path = temp_db("merge-v1");
+        {
+            let conn = Connection::open(&source_path).unwrap();
+            migrate_v1_snapshots(&conn).unwrap();
+            conn.pragma_update(None, "user_version", 1).unwrap();
+            conn.execute_batch(
+                "INSERT INTO process_snapshots
+                    (timestamp, process_name, pid, cpu_usage, memory_bytes, thread_count, status)
+                 VALUES ('2026-01-05T14:30:00+09:00', 'httpd', 1, 0.0, 1024, 1, 'Sleep');",
+            )
+            .unwrap();
+        }
+
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let counts = history.merge_from(&source_path, "web01").unwrap();
+        assert_eq!((counts.snapshots, counts.events, counts.sessions), (1, 0, 0));
+        let merged = history.query_snapshots(&RecordFilter::default()).unwrap();
+        assert_eq!(merged[0].host.as_deref(), Some("web01"));
+        assert!(merged[0].parent_pid.is_none());
+
+        // 取り込み元は移行されない
+        let source = Connection::open(&source_path).unwrap();
+        assert_eq!(user_version(&source), 1);
+        let tables: i64 = source
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'process_events'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(tables, 0);
+
+        let _ = std::fs::remove_file(&source_path);
+    }
+
+    #[test]
+    fn test_merge_rejects_newer_source() {
+        let source_path = temp_db("merge-newer");
+        {
+            let conn = Connection::open(&source_path).unwrap();
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        }
+
+        let mut history = ProcessHistory::new(":memory:").unwrap();
+        let result = history.merge_from(&source_path, "web01");
+        assert!(matches!(result, Err(HistoryError::NewerSchema { .. })));
+
+        let _ = std::fs::remove_file(&source_path);
+    }
+
+    #[test]
+    fn test_rejects_newer_database() {
+        let path = temp_db("newer");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+                .unwrap();
+        }
+
+        let result = ProcessHistory::new(&path);
+        assert!(matches!(result, Err(HistoryError::NewerSchema { .. })));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
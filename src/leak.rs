@@ -0,0 +1,430 @@
+use crate::formatter;
+use crate::history::{ProcessEvent, ProcessEventKind, ProcessSnapshot};
+use crate::matcher::NameMatcher;
+use serde::Serialize;
+use chrono::Duration;
+use std::collections::HashMap;
+
+/// リーク検出の判定条件
+#[derive(Clone, Debug, Serialize)]
+pub struct LeakCriteria {
+    pub min_growth_bytes_per_hour: f64,
+    pub min_r_squared: f64,
+    pub min_duration_secs: i64,
+    pub limit_bytes: Option<u64>,
+}
+
+/// リークの疑いがあるプロセス
+#[derive(Serialize)]
+pub struct LeakCandidate {
+    pub pid: u32,
+    pub process_name: String,
+    pub samples: usize,
+    pub first_seen: String, // ISO 8601
+    pub last_seen: String,  // ISO 8601
+    pub lifetime_secs: i64,
+    pub start_memory_bytes: u64,
+    pub end_memory_bytes: u64,
+    pub growth_bytes_per_hour: f64,
+    pub r_squared: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hours_to_limit: Option<f64>,
+}
+
+/// リーク検出レポート
+#[derive(Serialize)]
+pub struct LeakReport {
+    pub criteria: LeakCriteria,
+    pub analyzed_pids: usize,
+    pub analyzed_series: usize, // PID の再利用で分けた後の系列数
+    pub leaks: Vec<LeakCandidate>,
+}
+
+/// 線形回帰の結果
+#[derive(Debug, PartialEq)]
+struct LinearFit {
+    slope: f64,
+    intercept: f64,
+    r_squared: f64,
+}
+
+/// 最小二乗法で y = slope * x + intercept を当てはめる
+///
+/// 点が2つ未満、または x がすべて同じ場合は None
+fn linear_regression(points: &[(f64, f64)]) -> Option<LinearFit> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let syy: f64 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    // y が一定なら完全に説明できている（傾き 0）とみなす
+    let r_squared = if syy == 0.0 { 1.0 } else { (sxy * sxy) / (sxx * syy) };
+
+    Some(LinearFit {
+        slope,
+        intercept,
+        r_squared,
+    })
+}
+
+/// 記録間隔の何倍の空白があれば、PID が再利用された別プロセスとみなすか
+const REUSE_GAP_INTERVALS: i32 = 3;
+
+/// 直前の記録に対してこの割合未満までメモリが減ったら、PID が再利用された別プロセスとみなす
+const REUSE_MEMORY_DROP_RATIO: f64 = 0.5;
+
+/// PID ごとにメモリの増加傾向を当てはめ、しきい値を超えるものを抽出
+///
+/// 同じ PID の記録は、PID が再利用された箇所で別プロセスの系列に分けてから判定する。
+/// 起動・終了イベント（`events`）が記録されていれば起動イベントと親PIDの変化で分け、
+/// ない場合（古いデータベース等）は親PIDの変化・メモリの急減・記録の空白から推定する
+pub fn detect_leaks(
+    snapshots: &[ProcessSnapshot],
+    events: Option<&[ProcessEvent]>,
+    criteria: LeakCriteria,
+) -> LeakReport {
+    // 入力の順序（タイムスタンプ昇順）を保ったまま PID ごとに振り分ける
+    let mut by_pid: HashMap<u32, Vec<&ProcessSnapshot>> = HashMap::new();
+    for snapshot in snapshots {
+        by_pid.entry(snapshot.pid).or_default().push(snapshot);
+    }
+    let analyzed_pids = by_pid.len();
+
+    let series: Vec<(u32, Vec<&ProcessSnapshot>)> = by_pid
+        .into_iter()
+        .flat_map(|(pid, records)| {
+            let runs = match events {
+                Some(events) => split_at_events(pid, records, events),
+                None => split_reused_pid(records),
+            };
+            runs.into_iter().map(move |run| (pid, run))
+        })
+        .collect();
+
+    let analyzed_series = series.len();
+    let mut leaks: Vec<LeakCandidate> = series
+        .into_iter()
+        .filter_map(|(pid, records)| evaluate_pid(pid, &records, &criteria))
+        .collect();
+
+    leaks.sort_by(|a, b| b.growth_bytes_per_hour.total_cmp(&a.growth_bytes_per_hour));
+
+    LeakReport {
+        criteria,
+        analyzed_pids,
+        analyzed_series,
+        leaks,
+    }
+}
+
+/// 1つの PID の記録（タイムスタンプ昇順）を、起動イベントと親PIDの変化で分割
+///
+/// 起動イベントは同じ更新のスナップショットの直後に記録されるため、
+/// イベント時刻以前で最後の記録から新しい系列とする
+fn split_at_events<'a>(
+    pid: u32,
+    records: Vec<&'a ProcessSnapshot>,
+    events: &[ProcessEvent],
+) -> Vec<Vec<&'a ProcessSnapshot>> {
+    let starts: Vec<usize> = events
+        .iter()
+        .filter(|e| e.pid == pid && e.kind == ProcessEventKind::Started)
+        .filter_map(|e| records.iter().rposition(|r| r.timestamp <= e.timestamp))
+        .collect();
+
+    let mut runs: Vec<Vec<&ProcessSnapshot>> = vec![];
+    for (index, record) in records.into_iter().enumerate() {
+        let reused = runs.last().and_then(|run| run.last()).is_some_and(|previous| {
+            starts.contains(&index) || record.parent_pid != previous.parent_pid
+        });
+        match runs.last_mut() {
+            Some(run) if !reused => run.push(record),
+            _ => runs.push(vec![record]),
+        }
+    }
+    runs
+}
+
+/// 記録間隔（連続する記録時刻の差の中央値）
+fn recording_interval(records: &[&ProcessSnapshot]) -> Option<Duration> {
+    let mut gaps: Vec<Duration> = records
+        .windows(2)
+        .map(|pair| pair[1].timestamp - pair[0].timestamp)
+        .collect();
+    if gaps.is_empty() {
+        return None;
+    }
+    gaps.sort();
+    Some(gaps[gaps.len() / 2])
+}
+
+/// 1つの PID の記録（タイムスタンプ昇順）を、PID が再利用されたと推定される箇所で分割
+fn split_reused_pid(records: Vec<&ProcessSnapshot>) -> Vec<Vec<&ProcessSnapshot>> {
+    let max_gap = recording_interval(&records).map(|interval| interval * REUSE_GAP_INTERVALS);
+    let mut runs: Vec<Vec<&ProcessSnapshot>> = vec![];
+    for record in records {
+        let reused = runs.last().and_then(|run| run.last()).is_some_and(|previous| {
+            record.parent_pid != previous.parent_pid
+                || (record.memory_bytes as f64) < previous.memory_bytes as f64 * REUSE_MEMORY_DROP_RATIO
+                || max_gap.is_some_and(|gap| record.timestamp - previous.timestamp > gap)
+        });
+        match runs.last_mut() {
+            Some(run) if !reused => run.push(record),
+            _ => runs.push(vec![record]),
+        }
+    }
+    runs
+}
+
+/// 1つの PID について判定
+fn evaluate_pid(
+    pid: u32,
+    records: &[&ProcessSnapshot],
+    criteria: &LeakCriteria,
+) -> Option<LeakCandidate> {
+    let first = records.first()?;
+    let last = records.last()?;
+
+    let duration_secs = (last.timestamp - first.timestamp).num_seconds();
+    if duration_secs < criteria.min_duration_secs {
+        return None;
+    }
+
+    // x: 最初の記録からの経過時間（時間）, y: メモリ（バイト）
+    let points: Vec<(f64, f64)> = records
+        .iter()
+        .map(|s| {
+            let hours = (s.timestamp - first.timestamp).num_milliseconds() as f64 / 3_600_000.0;
+            (hours, s.memory_bytes as f64)
+        })
+        .collect();
+    let fit = linear_regression(&points)?;
+
+    if fit.slope < criteria.min_growth_bytes_per_hour || fit.r_squared < criteria.min_r_squared {
+        return None;
+    }
+
+    // 回帰直線上の現在値から上限到達までの時間を推定
+    let hours_to_limit = criteria.limit_bytes.map(|limit| {
+        let elapsed_hours = duration_secs as f64 / 3600.0;
+        let fitted_now = fit.intercept + fit.slope * elapsed_hours;
+        ((limit as f64 - fitted_now) / fit.slope).max(0.0)
+    });
+
+    Some(LeakCandidate {
+        pid,
+        process_name: last.process_name.clone(),
+        samples: records.len(),
+        first_seen: first.timestamp.to_rfc3339(),
+        last_seen: last.timestamp.to_rfc3339(),
+        lifetime_secs: duration_secs,
+        start_memory_bytes: first.memory_bytes,
+        end_memory_bytes: last.memory_bytes,
+        growth_bytes_per_hour: fit.slope,
+        r_squared: fit.r_squared,
+        hours_to_limit,
+    })
+}
+
+/// テーブル形式でリーク検出結果を出力
+pub fn print_table(report: &LeakReport, process_name_filter: Option<&NameMatcher>) {
+    println!("{}", "=".repeat(114));
+    println!("Memory Leak Report");
+    println!("{}", "=".repeat(114));
+
+    if let Some(name) = process_name_filter {
+        println!("Filter: process name {}", name.describe());
+    }
+    println!(
+        "Criteria: growth >= {}/h, R² >= {:.2}, lifetime >= {}",
+        formatter::format_bytes(report.criteria.min_growth_bytes_per_hour as u64),
+        report.criteria.min_r_squared,
+        formatter::format_duration(report.criteria.min_duration_secs)
+    );
+    if let Some(limit) = report.criteria.limit_bytes {
+        println!("Limit: {}", formatter::format_bytes(limit));
+    }
+    println!(
+        "Suspected: {} of {} series ({} PID(s))\n",
+        report.leaks.len(),
+        report.analyzed_series,
+        report.analyzed_pids
+    );
+
+    if report.leaks.is_empty() {
+        println!("No processes with a steady memory growth were found.");
+        println!("{}", "=".repeat(114));
+        return;
+    }
+
+    println!(
+        "{:<8} {:<20} {:>8} {:>12} {:>12} {:>12} {:>6} {:>14} {:>14}",
+        "PID", "Name", "Samples", "Start", "End", "Growth/h", "R²", "Lifetime", "To Limit"
+    );
+    println!("{}", "-".repeat(114));
+
+    for leak in &report.leaks {
+        println!(
+            "{:<8} {:<20} {:>8} {:>12} {:>12} {:>12} {:>6.3} {:>14} {:>14}",
+            leak.pid,
+            formatter::truncate_string(&leak.process_name, 20),
+            leak.samples,
+            formatter::format_bytes(leak.start_memory_bytes),
+            formatter::format_bytes(leak.end_memory_bytes),
+            formatter::format_bytes(leak.growth_bytes_per_hour as u64),
+            leak.r_squared,
+            formatter::format_duration(leak.lifetime_secs),
+            leak.hours_to_limit
+                .map(|h| formatter::format_duration((h * 3600.0) as i64))
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+
+    println!("{}", "=".repeat(114));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn records(pid: u32, memory: &[u64]) -> Vec<ProcessSnapshot> {
+        let start = Local::now();
+        memory
+            .iter()
+            .enumerate()
+            .map(|(i, &memory_bytes)| ProcessSnapshot {
+                timestamp: start + Duration::minutes(10 * i as i64),
+                process_name: "php-fpm".to_string(),
+                pid,
+                parent_pid: None,
+                cpu_usage: 0.0,
+                memory_bytes,
+                smaps: None,
+                thread_count: 1,
+                status: sysinfo::ProcessStatus::Sleep,
+                host: None,
+            })
+            .collect()
+    }
+
+    fn criteria() -> LeakCriteria {
+        LeakCriteria {
+            min_growth_bytes_per_hour: MB as f64,
+            min_r_squared: 0.8,
+            min_duration_secs: 1800,
+            limit_bytes: Some(100 * MB),
+        }
+    }
+
+    #[test]
+    fn test_linear_regression_perfect_fit() {
+        let fit = linear_regression(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+        assert_eq!(fit.slope, 2.0);
+        assert_eq!(fit.intercept, 1.0);
+        assert_eq!(fit.r_squared, 1.0);
+    }
+
+    #[test]
+    fn test_linear_regression_degenerate() {
+        assert_eq!(linear_regression(&[(0.0, 1.0)]), None);
+        assert_eq!(linear_regression(&[(1.0, 1.0), (1.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn test_detect_leaks_flags_steady_growth() {
+        // 10分ごとに 1MB 増加 = 6MB/h、60MB から開始
+        let mut snapshots = records(100, &[60 * MB, 61 * MB, 62 * MB, 63 * MB, 64 * MB]);
+        // 一定のプロセスは検出されない
+        snapshots.extend(records(200, &[50 * MB; 5]));
+
+        let report = detect_leaks(&snapshots, None, criteria());
+        assert_eq!(report.analyzed_pids, 2);
+        assert_eq!(report.leaks.len(), 1);
+
+        let leak = &report.leaks[0];
+        assert_eq!(leak.pid, 100);
+        assert!((leak.growth_bytes_per_hour - 6.0 * MB as f64).abs() < 1.0);
+        // 64MB -> 100MB は 36MB / 6MB/h = 6時間
+        assert!((leak.hours_to_limit.unwrap() - 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_detect_leaks_skips_short_lifetime() {
+        // 20分しか記録がないプロセスは対象外
+        let snapshots = records(100, &[60 * MB, 70 * MB, 80 * MB]);
+        let report = detect_leaks(&snapshots, None, criteria());
+        assert!(report.leaks.is_empty());
+    }
+
+    #[test]
+    fn test_detect_leaks_splits_reused_pid() {
+        // 増え続けたワーカーが終了し、同じ PID で別のワーカー（親PIDが異なる）が起動
+        let mut snapshots = records(100, &[60 * MB, 61 * MB, 62 * MB, 63 * MB, 64 * MB]);
+        snapshots.extend(records(100, &[20 * MB; 5]).into_iter().map(|s| ProcessSnapshot {
+            timestamp: s.timestamp + Duration::minutes(50),
+            parent_pid: Some(2),
+            ..s
+        }));
+
+        let report = detect_leaks(&snapshots, None, criteria());
+        assert_eq!((report.analyzed_pids, report.analyzed_series), (1, 2));
+        assert_eq!(report.leaks.len(), 1);
+        assert_eq!(report.leaks[0].samples, 5);
+        assert_eq!(report.leaks[0].lifetime_secs, 2400);
+
+        // 親PIDが同じでも、メモリの急減や記録の空白で分ける
+        let drop = records(100, &[60 * MB, 61 * MB, 62 * MB, 20 * MB, 21 * MB]);
+        assert_eq!(detect_leaks(&drop, None, criteria()).analyzed_series, 2);
+        let mut gap = records(100, &[50 * MB; 3]);
+        gap.push(ProcessSnapshot {
+            timestamp: gap[2].timestamp + Duration::hours(2),
+            ..gap[2].clone()
+        });
+        assert_eq!(detect_leaks(&gap, None, criteria()).analyzed_series, 2);
+    }
+
+    #[test]
+    fn test_detect_leaks_splits_at_start_events() {
+        let started = |timestamp| ProcessEvent {
+            timestamp,
+            kind: ProcessEventKind::Started,
+            pid: 100,
+            process_name: "php-fpm".to_string(),
+            parent_pid: None,
+            memory_bytes: 0,
+            lifetime_secs: None,
+        };
+
+        // 記録が2時間途切れても、起動イベントがなければ同じプロセスとして判定する
+        let mut paused = records(100, &[60 * MB, 61 * MB, 62 * MB]);
+        paused.extend(records(100, &[74 * MB, 75 * MB]).into_iter().map(|s| ProcessSnapshot {
+            timestamp: s.timestamp + Duration::hours(2),
+            ..s
+        }));
+        let report = detect_leaks(&paused, Some(&[]), criteria());
+        assert_eq!(report.analyzed_series, 1);
+        assert_eq!(report.leaks.len(), 1);
+
+        // 親PIDが同じでも、起動イベントの直前の記録（同じ更新）から別の系列にする
+        let reused = records(100, &[60 * MB, 61 * MB, 62 * MB, 63 * MB, 64 * MB]);
+        let event = started(reused[3].timestamp + Duration::milliseconds(1));
+        let report = detect_leaks(&reused, Some(&[event]), criteria());
+        assert_eq!(report.analyzed_series, 2);
+    }
+}
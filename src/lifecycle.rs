@@ -0,0 +1,378 @@
+use crate::formatter;
+use crate::history::{ProcessEvent, ProcessEventKind};
+use crate::matcher::NameMatcher;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use sysinfo::System;
+
+/// 追跡中のプロセス（前回の更新時点の情報）
+#[derive(Debug, Clone)]
+struct TrackedProcess {
+    process_name: String,
+    parent_pid: Option<u32>,
+    memory_bytes: u64,
+    start_time: u64, // UNIX 時刻（秒）。PID の再利用を判別するために使用
+}
+
+/// 更新ごとの TGID 集合を比較して起動・終了イベントを生成する
+pub struct LifecycleTracker {
+    known: HashMap<u32, TrackedProcess>,
+    initialized: bool,
+}
+
+impl LifecycleTracker {
+    pub fn new() -> Self {
+        Self {
+            known: HashMap::new(),
+            initialized: false,
+        }
+    }
+
+    /// 名前が一致するプロセスの集合を前回と比較し、イベントを返す
+    ///
+    /// min-memory 等の表示用フィルタは適用しない（しきい値の上下で起動・終了扱いにしないため）
+    pub fn update(&mut self, sys: &System, matcher: &NameMatcher) -> Vec<ProcessEvent> {
+        let current = sys
+            .processes()
+            .values()
+            .filter(|p| matcher.is_match(&p.name().to_string_lossy()))
+            // スレッド（LWP != TGID）は除外
+            .filter(|p| formatter::get_tgid(p.pid().as_u32()) == p.pid().as_u32())
+            .map(|p| {
+                (
+                    p.pid().as_u32(),
+                    TrackedProcess {
+                        process_name: p.name().to_string_lossy().to_string(),
+                        parent_pid: p.parent().map(|pid| pid.as_u32()),
+                        memory_bytes: p.memory(),
+                        start_time: p.start_time(),
+                    },
+                )
+            })
+            .collect();
+
+        self.diff(current, Local::now())
+    }
+
+    fn diff(
+        &mut self,
+        current: HashMap<u32, TrackedProcess>,
+        now: DateTime<Local>,
+    ) -> Vec<ProcessEvent> {
+        let mut events = Vec::new();
+
+        // 初回は基準となる集合を記録するだけ（既存プロセスを起動扱いにしない）
+        if !self.initialized {
+            self.known = current;
+            self.initialized = true;
+            return events;
+        }
+
+        for (&pid, previous) in &self.known {
+            // 同じ PID でも起動時刻が異なれば別プロセス（PID の再利用）
+            let exited = match current.get(&pid) {
+                Some(process) => process.start_time != previous.start_time,
+                None => true,
+            };
+            if exited {
+                events.push(event(now, ProcessEventKind::Exited, pid, previous));
+            }
+        }
+
+        for (&pid, process) in &current {
+            let started = match self.known.get(&pid) {
+                Some(previous) => previous.start_time != process.start_time,
+                None => true,
+            };
+            if started {
+                events.push(event(now, ProcessEventKind::Started, pid, process));
+            }
+        }
+
+        events.sort_by_key(|e| (e.kind == ProcessEventKind::Started, e.pid));
+        self.known = current;
+        events
+    }
+}
+
+fn event(
+    now: DateTime<Local>,
+    kind: ProcessEventKind,
+    pid: u32,
+    process: &TrackedProcess,
+) -> ProcessEvent {
+    let lifetime_secs = match kind {
+        ProcessEventKind::Started => None,
+        ProcessEventKind::Exited => Some((now.timestamp() - process.start_time as i64).max(0)),
+    };
+
+    ProcessEvent {
+        timestamp: now,
+        kind,
+        pid,
+        process_name: process.process_name.clone(),
+        parent_pid: process.parent_pid,
+        memory_bytes: process.memory_bytes,
+        lifetime_secs,
+    }
+}
+
+/// プロセス名ごとのイベント集計
+#[derive(Serialize)]
+pub struct NameEvents {
+    pub process_name: String,
+    pub started: usize,
+    pub exited: usize,
+    pub avg_lifetime_secs: Option<f64>,
+}
+
+/// イベント1件（JSON 出力用）
+#[derive(Serialize)]
+pub struct EventRecord {
+    pub timestamp: String, // ISO 8601
+    pub event: ProcessEventKind,
+    pub pid: u32,
+    pub process_name: String,
+    pub parent_pid: Option<u32>,
+    pub memory_bytes: u64,
+    pub lifetime_secs: Option<i64>,
+}
+
+/// ライフサイクルイベントのレポート
+#[derive(Serialize)]
+pub struct EventReport {
+    pub from: String,
+    pub to: String,
+    pub span_secs: i64,
+    pub started: usize,
+    pub exited: usize,
+    pub started_per_hour: f64,
+    pub exited_per_hour: f64,
+    pub avg_lifetime_secs: Option<f64>,
+    pub min_lifetime_secs: Option<i64>,
+    pub max_lifetime_secs: Option<i64>,
+    pub by_name: Vec<NameEvents>,
+    pub events: Vec<EventRecord>,
+}
+
+/// イベントから再起動の頻度と平均寿命を集計
+///
+/// 期間は指定があればそれを、なければ最初と最後のイベントの間を使う。
+/// イベントも期間の指定もない場合は現在時刻を期間とし、件数 0 のレポートを返す
+pub fn build_report(
+    events: &[ProcessEvent],
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+) -> EventReport {
+    let to = to.or_else(|| events.last().map(|e| e.timestamp)).unwrap_or_else(Local::now);
+    let from = from.or_else(|| events.first().map(|e| e.timestamp)).unwrap_or(to);
+    let span_secs = (to - from).num_seconds().max(0);
+    // 期間が 0 の場合は 1 時間あたりの値を出せないので 1 秒とみなす
+    let hours = span_secs.max(1) as f64 / 3600.0;
+
+    let count = |kind| events.iter().filter(|e| e.kind == kind).count();
+    let started = count(ProcessEventKind::Started);
+    let exited = count(ProcessEventKind::Exited);
+
+    let lifetimes: Vec<i64> = events.iter().filter_map(|e| e.lifetime_secs).collect();
+
+    let mut names: BTreeMap<&str, (usize, usize, Vec<i64>)> = BTreeMap::new();
+    for e in events {
+        let entry = names.entry(&e.process_name).or_default();
+        match e.kind {
+            ProcessEventKind::Started => entry.0 += 1,
+            ProcessEventKind::Exited => entry.1 += 1,
+        }
+        entry.2.extend(e.lifetime_secs);
+    }
+
+    EventReport {
+        from: from.to_rfc3339(),
+        to: to.to_rfc3339(),
+        span_secs,
+        started,
+        exited,
+        started_per_hour: started as f64 / hours,
+        exited_per_hour: exited as f64 / hours,
+        avg_lifetime_secs: mean(&lifetimes),
+        min_lifetime_secs: lifetimes.iter().copied().min(),
+        max_lifetime_secs: lifetimes.iter().copied().max(),
+        by_name: names
+            .into_iter()
+            .map(|(name, (started, exited, lifetimes))| NameEvents {
+                process_name: name.to_string(),
+                started,
+                exited,
+                avg_lifetime_secs: mean(&lifetimes),
+            })
+            .collect(),
+        events: events
+            .iter()
+            .map(|e| EventRecord {
+                timestamp: e.timestamp.to_rfc3339(),
+                event: e.kind,
+                pid: e.pid,
+                process_name: e.process_name.clone(),
+                parent_pid: e.parent_pid,
+                memory_bytes: e.memory_bytes,
+                lifetime_secs: e.lifetime_secs,
+            })
+            .collect(),
+    }
+}
+
+fn mean(values: &[i64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<i64>() as f64 / values.len() as f64)
+}
+
+fn format_lifetime(secs: Option<f64>) -> String {
+    secs.map(|s| formatter::format_duration(s as i64))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// テーブル形式でイベントレポートを出力
+pub fn print_table(report: &EventReport, process_name_filter: Option<&NameMatcher>, recent: usize) {
+    println!("{}", "=".repeat(90));
+    println!("Process Lifecycle Report");
+    println!("{}", "=".repeat(90));
+
+    if let Some(name) = process_name_filter {
+        println!("Filter: process name {}", name.describe());
+    }
+    println!("Time Range: {} - {}", report.from, report.to);
+    println!("Duration:   {}\n", formatter::format_duration(report.span_secs));
+
+    println!("Churn:");
+    println!("  Started:  {:>6} ({:.2}/h)", report.started, report.started_per_hour);
+    println!("  Exited:   {:>6} ({:.2}/h)", report.exited, report.exited_per_hour);
+
+    if report.events.is_empty() {
+        println!("\nNo lifecycle events found. Events are recorded by --watch/--tui with --log.");
+        println!("{}", "=".repeat(90));
+        return;
+    }
+
+    println!("\nLifetime of exited processes:");
+    println!("  Avg: {}", format_lifetime(report.avg_lifetime_secs));
+    println!("  Min: {}", format_lifetime(report.min_lifetime_secs.map(|s| s as f64)));
+    println!("  Max: {}", format_lifetime(report.max_lifetime_secs.map(|s| s as f64)));
+
+    println!("\n{:<24} {:>10} {:>10} {:>16}", "Name", "Started", "Exited", "Avg Lifetime");
+    println!("{}", "-".repeat(90));
+    for name in &report.by_name {
+        println!(
+            "{:<24} {:>10} {:>10} {:>16}",
+            formatter::truncate_string(&name.process_name, 24),
+            name.started,
+            name.exited,
+            format_lifetime(name.avg_lifetime_secs)
+        );
+    }
+
+    // 直近のイベント
+    let skip = report.events.len().saturating_sub(recent);
+    println!("\nRecent events:");
+    println!(
+        "{:<20} {:<8} {:<8} {:<20} {:>8} {:>12} {:>10}",
+        "Timestamp", "Event", "PID", "Name", "PPID", "Memory", "Lifetime"
+    );
+    println!("{}", "-".repeat(90));
+    for e in report.events.iter().skip(skip) {
+        let event = match e.event {
+            ProcessEventKind::Started => "started",
+            ProcessEventKind::Exited => "exited",
+        };
+        // 秒までの表示に短縮（2026-01-05T14:00:00... → 2026-01-05 14:00:00）
+        let timestamp = e.timestamp.get(..19).unwrap_or(&e.timestamp).replace('T', " ");
+        println!(
+            "{:<20} {:<8} {:<8} {:<20} {:>8} {:>12} {:>10}",
+            timestamp,
+            event,
+            e.pid,
+            formatter::truncate_string(&e.process_name, 20),
+            e.parent_pid.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string()),
+            formatter::format_bytes(e.memory_bytes),
+            e.lifetime_secs
+                .map(formatter::format_duration)
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+
+    println!("{}", "=".repeat(90));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn tracked(name: &str, start_time: u64) -> TrackedProcess {
+        TrackedProcess {
+            process_name: name.to_string(),
+            parent_pid: Some(1),
+            memory_bytes: 1024,
+            start_time,
+        }
+    }
+
+    #[test]
+    fn test_diff_detects_start_exit_and_pid_reuse() {
+        let now = Local::now();
+        let mut tracker = LifecycleTracker::new();
+
+        // 初回はイベントなし
+        let initial = HashMap::from([(10, tracked("php-fpm", 100)), (11, tracked("php-fpm", 100))]);
+        assert!(tracker.diff(initial, now).is_empty());
+
+        // 10 が終了、11 は PID 再利用、12 が起動
+        let start = now.timestamp() as u64 - 60;
+        let next = HashMap::from([(11, tracked("php-fpm", start)), (12, tracked("php-fpm", start))]);
+        let events = tracker.diff(next, now);
+        let summary: Vec<(ProcessEventKind, u32)> = events.iter().map(|e| (e.kind, e.pid)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (ProcessEventKind::Exited, 10),
+                (ProcessEventKind::Exited, 11),
+                (ProcessEventKind::Started, 11),
+                (ProcessEventKind::Started, 12),
+            ]
+        );
+        assert_eq!(events[0].lifetime_secs, Some(now.timestamp() - 100));
+        assert_eq!(events[2].lifetime_secs, None);
+    }
+
+    #[test]
+    fn test_build_report() {
+        let start = Local::now();
+        let mk = |minutes: i64, kind, lifetime_secs| ProcessEvent {
+            timestamp: start + Duration::minutes(minutes),
+            kind,
+            pid: 1,
+            process_name: "php-fpm".to_string(),
+            parent_pid: None,
+            memory_bytes: 0,
+            lifetime_secs,
+        };
+        let events = vec![
+            mk(0, ProcessEventKind::Exited, Some(600)),
+            mk(0, ProcessEventKind::Started, None),
+            mk(30, ProcessEventKind::Exited, Some(1800)),
+            mk(30, ProcessEventKind::Started, None),
+        ];
+
+        let report = build_report(&events, None, Some(start + Duration::hours(2)));
+        assert_eq!(report.span_secs, 7200);
+        assert_eq!(report.started, 2);
+        assert_eq!(report.exited_per_hour, 1.0);
+        assert_eq!(report.avg_lifetime_secs, Some(1200.0));
+        assert_eq!(report.by_name.len(), 1);
+
+        // イベントがなくても件数 0 のレポートを返す
+        let empty = build_report(&[], None, None);
+        assert_eq!((empty.started, empty.exited, empty.span_secs), (0, 0, 0));
+        assert_eq!(empty.started_per_hour, 0.0);
+        assert!(empty.by_name.is_empty());
+    }
+}
@@ -1,17 +1,42 @@
+mod alert;
 mod formatter;
 mod process;
 mod monitor;
 mod tui;
 mod history;
 mod analyze;
+mod check;
+mod config;
+mod daemon;
 mod graph;
 mod tree;
+mod recommend;
+mod leak;
+mod matcher;
+mod compare;
+mod lifecycle;
+mod retention;
+mod rollup;
+mod headroom;
+mod session;
+mod export;
+mod merge;
+mod report;
+mod serve;
+mod targets;
 
-use analyze::OutputFormat;
-use clap::{Parser, Subcommand};
-use monitor::{watch_mode, MonitorArgs};
-use process::{show_process_by_pid, show_processes_by_name, show_processes_by_name_tree, SortOrder};
+use analyze::{AnalyzeOptions, GroupBy, GroupSort, OutputFormat};
+use leak::LeakCriteria;
+use matcher::{MatchArgs, NameMatcher};
+use clap::{ArgGroup, Parser, Subcommand};
+use compare::CompareOptions;
+use monitor::{watch_mode, JsonlMode, MonitorArgs, WatchOutput};
+use config::{Config, ConfigDefaults};
+use process::{show_process_by_pid, show_processes_by_name, show_processes_by_name_tree, MemoryMetric, ProcessQuery, SortOrder};
+use recommend::{ApacheMpm, RecommendOptions, RecommendTarget};
+use report::{print_report, ReportOptions, SingleShotFormat};
 use sysinfo::{ProcessesToUpdate, System};
+use targets::Target;
 
 /// プロセス監視ツール
 #[derive(Parser, Debug)]
@@ -29,31 +54,406 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Analyze historical process data
-    Analyze(AnalyzeArgs),
+    Analyze(Box<AnalyzeArgs>),
+
+    /// Maintain history databases
+    History(HistoryArgs),
+
+    /// Expose matching processes as Prometheus metrics over HTTP
+    Serve(ServeArgs),
+
+    /// Nagios/Icinga-compatible check (exit 0/1/2/3 with perfdata)
+    Check(Box<CheckArgs>),
+
+    /// Record the targets of a config file headless until SIGTERM (SIGHUP reloads the config)
+    Daemon(DaemonArgs),
+}
+
+/// daemon サブコマンドの引数
+#[derive(Parser, Debug)]
+struct DaemonArgs {
+    /// TOML file declaring the targets to record (re-read on SIGHUP)
+    #[arg(long)]
+    config: String,
+
+    /// Write the process ID to this file (removed on shutdown)
+    #[arg(long)]
+    pidfile: Option<String>,
+
+    /// Append the daemon's own messages to this file instead of stderr (reopened on SIGHUP)
+    #[arg(long)]
+    error_log: Option<String>,
+}
+
+/// check サブコマンドの引数（しきい値は Nagios の範囲形式: 10, 10:, ~:10, 10:20, @10:20）
+#[derive(Parser, Debug)]
+struct CheckArgs {
+    /// Process name to check (repeatable, matched per --match-mode)
+    #[arg(short, long, required = true)]
+    name: Vec<String>,
+
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// Exclude processes using less memory than this (MB)
+    #[arg(long)]
+    min_memory_mb: Option<u64>,
+
+    /// Memory metric used for the memory thresholds
+    #[arg(long, default_value = "rss", value_enum)]
+    memory_metric: MemoryMetric,
+
+    /// Warning range for the number of processes
+    #[arg(long, value_parser = check::parse_range)]
+    warning_count: Option<check::ThresholdRange>,
+
+    /// Critical range for the number of processes (default without count thresholds: 1:)
+    #[arg(long, value_parser = check::parse_range)]
+    critical_count: Option<check::ThresholdRange>,
+
+    /// Warning range for the memory of the largest process (MB)
+    #[arg(long, value_parser = check::parse_range)]
+    warning_memory_mb: Option<check::ThresholdRange>,
+
+    /// Critical range for the memory of the largest process (MB)
+    #[arg(long, value_parser = check::parse_range)]
+    critical_memory_mb: Option<check::ThresholdRange>,
+
+    /// Warning range for the total memory of matching processes (MB)
+    #[arg(long, value_parser = check::parse_range)]
+    warning_total_memory_mb: Option<check::ThresholdRange>,
+
+    /// Critical range for the total memory of matching processes (MB)
+    #[arg(long, value_parser = check::parse_range)]
+    critical_total_memory_mb: Option<check::ThresholdRange>,
+
+    /// Warning range for the total CPU usage (%)
+    #[arg(long, value_parser = check::parse_range)]
+    warning_cpu: Option<check::ThresholdRange>,
+
+    /// Critical range for the total CPU usage (%)
+    #[arg(long, value_parser = check::parse_range)]
+    critical_cpu: Option<check::ThresholdRange>,
+}
+
+/// serve サブコマンドの引数
+#[derive(Parser, Debug)]
+struct ServeArgs {
+    /// Address to listen on (metrics are served at /metrics)
+    #[arg(long, default_value = "127.0.0.1:9256")]
+    listen: String,
+
+    /// Process name to export (repeatable, matched per --match-mode)
+    #[arg(short, long, required_unless_present = "config")]
+    name: Vec<String>,
+
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// TOML file declaring several targets (exported with a target label)
+    #[arg(long, conflicts_with = "name")]
+    config: Option<String>,
+
+    /// Exclude processes using less memory than this (MB)
+    #[arg(long)]
+    min_memory_mb: Option<u64>,
+
+    /// Memory metric used for the minimum memory filter and per-name totals
+    #[arg(long, default_value = "rss", value_enum)]
+    memory_metric: MemoryMetric,
+
+    /// Refresh every N seconds in the background instead of on each scrape (default with --config: its interval)
+    #[arg(long)]
+    interval: Option<u64>,
+}
+
+/// history サブコマンドの引数
+#[derive(Parser, Debug)]
+struct HistoryArgs {
+    #[command(subcommand)]
+    command: HistoryCommands,
+}
+
+/// history のサブコマンド
+#[derive(Subcommand, Debug)]
+enum HistoryCommands {
+    /// Delete records older than a cutoff
+    Prune(PruneArgs),
+
+    /// Build per-minute and per-hour rollup tables from recorded snapshots
+    Rollup(RollupArgs),
+
+    /// List recorded watch/TUI sessions
+    Sessions(SessionsArgs),
+
+    /// Write recorded snapshots to stdout as CSV or JSON Lines
+    Export(ExportArgs),
+
+    /// Load CSV or JSON Lines files written by export into a history database
+    Import(ImportArgs),
+
+    /// Combine history databases from several hosts into one
+    Merge(MergeArgs),
+}
+
+/// history merge の引数
+#[derive(Parser, Debug)]
+struct MergeArgs {
+    /// Path to the combined history database (created if it does not exist)
+    #[arg(long)]
+    log: String,
+
+    /// Databases to merge, as PATH or LABEL=PATH (default label: file name without extension)
+    #[arg(required = true)]
+    sources: Vec<String>,
+}
+
+/// history export の引数
+#[derive(Parser, Debug)]
+struct ExportArgs {
+    /// Path to history database
+    #[arg(long)]
+    log: String,
+
+    /// Output format
+    #[arg(long, default_value = "csv", value_enum)]
+    format: export::TransferFormat,
+
+    /// Filter by process name (repeatable, matched per --match-mode)
+    #[arg(long)]
+    name: Vec<String>,
+
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// Start time (same forms as analyze --from)
+    #[arg(long, allow_hyphen_values = true)]
+    from: Option<String>,
+
+    /// End time (same forms as analyze --from)
+    #[arg(long, allow_hyphen_values = true)]
+    to: Option<String>,
+}
+
+/// history import の引数
+#[derive(Parser, Debug)]
+struct ImportArgs {
+    /// Path to history database (created if it does not exist)
+    #[arg(long)]
+    log: String,
+
+    /// Input format (default: from the file extension, .csv or .jsonl)
+    #[arg(long, value_enum)]
+    format: Option<export::TransferFormat>,
+
+    /// Files to import
+    #[arg(required = true)]
+    files: Vec<String>,
+}
+
+/// history sessions の引数
+#[derive(Parser, Debug)]
+struct SessionsArgs {
+    /// Path to history database
+    #[arg(long)]
+    log: String,
+
+    /// Output format
+    #[arg(long, default_value = "table", value_enum)]
+    format: CompareFormatArg,
+}
+
+/// history rollup の引数
+#[derive(Parser, Debug)]
+struct RollupArgs {
+    /// Path to history database
+    #[arg(long)]
+    log: String,
+
+    /// Rebuild rollups for periods that still have raw snapshots (pruned periods are kept)
+    #[arg(long)]
+    rebuild: bool,
+}
+
+/// history prune の引数
+#[derive(Parser, Debug)]
+struct PruneArgs {
+    /// Path to history database
+    #[arg(long)]
+    log: String,
+
+    /// Delete records older than this (e.g., 12h, 7d)
+    #[arg(long, value_parser = analyze::parse_duration_secs)]
+    older_than: i64,
+
+    /// Rows deleted per statement
+    #[arg(long, default_value_t = retention::DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+
+    /// Rebuild the database file afterwards to return freed space to the OS
+    #[arg(long)]
+    vacuum: bool,
 }
 
 /// analyze サブコマンドの引数
 #[derive(Parser, Debug)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct AnalyzeArgs {
+    #[command(subcommand)]
+    command: Option<AnalyzeCommands>,
+
     /// Path to history database
     #[arg(long, required = true)]
+    log: Option<String>,
+
+    /// Filter by process name (repeatable, matched per --match-mode)
+    #[arg(long)]
+    name: Vec<String>,
+
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// Start time (ISO 8601, local "2026-01-05 14:00", or relative: -30m, today, "yesterday 09:00")
+    #[arg(long, allow_hyphen_values = true)]
+    from: Option<String>,
+
+    /// End time (same forms as --from)
+    #[arg(long, allow_hyphen_values = true)]
+    to: Option<String>,
+
+    /// Analyze only the most recent period (e.g., 30m, 2h, 1d)
+    #[arg(long, conflicts_with_all = ["from", "to"], value_parser = analyze::parse_duration_secs)]
+    last: Option<i64>,
+
+    /// Analyze only records of this session (see: history sessions)
+    #[arg(long)]
+    session: Option<i64>,
+
+    /// Analyze only records merged from this host label (see: history merge)
+    #[arg(long)]
+    host: Option<String>,
+
+    /// Output format
+    #[arg(long, default_value = "table", value_enum)]
+    format: OutputFormatArg,
+
+    /// Suggest server settings from recorded worker memory (requires --name)
+    #[arg(long, value_enum, requires = "name")]
+    recommend: Option<RecommendTarget>,
+
+    /// Memory reserved for OS and other services when recommending (percent of total)
+    #[arg(long, default_value = "20", requires = "recommend")]
+    reserve_percent: f64,
+
+    /// Apache MPM used for --recommend apache
+    #[arg(long, default_value = "prefork", value_enum, requires = "recommend")]
+    mpm: ApacheMpm,
+
+    /// ThreadsPerChild used for --recommend apache --mpm event
+    #[arg(long, default_value = "25", requires = "recommend", value_parser = clap::value_parser!(u64).range(1..))]
+    threads_per_child: u64,
+
+    /// Produce one section per process name, PID, or source host
+    #[arg(long, value_enum, conflicts_with = "recommend")]
+    group_by: Option<GroupBy>,
+
+    /// Sort order of groups
+    #[arg(long, default_value = "memory", value_enum, requires = "group_by")]
+    group_sort: GroupSort,
+
+    /// Show only the first N groups
+    #[arg(long, requires = "group_by")]
+    top: Option<usize>,
+
+    /// Aggregate into fixed time windows (e.g., 1m, 5m, 1h, 1d)
+    #[arg(long, conflicts_with_all = ["recommend", "group_by"])]
+    bucket: Option<String>,
+
+    /// Detect steadily growing memory per PID (linear trend)
+    #[arg(long, conflicts_with_all = ["recommend", "group_by", "bucket"])]
+    leaks: bool,
+
+    /// Minimum memory growth rate to flag as a leak (MB per hour)
+    #[arg(long, default_value = "1", requires = "leaks")]
+    leak_min_rate_mb: f64,
+
+    /// Minimum R² of the linear trend to flag as a leak (0.0-1.0)
+    #[arg(long, default_value = "0.8", requires = "leaks")]
+    leak_min_r2: f64,
+
+    /// Minimum recorded lifetime of a PID to be evaluated (e.g., 30m, 1h)
+    #[arg(long, default_value = "1h", requires = "leaks", value_parser = analyze::parse_duration_secs)]
+    leak_min_duration: i64,
+
+    /// Memory limit used to project the time until a leaking process reaches it (MB)
+    #[arg(long, requires = "leaks")]
+    leak_limit_mb: Option<u64>,
+
+    /// Report process start/exit events (churn rate and worker lifetime)
+    #[arg(long, conflicts_with_all = ["recommend", "group_by", "bucket", "leaks"])]
+    events: bool,
+}
+
+/// analyze のサブコマンド
+#[derive(Subcommand, Debug)]
+enum AnalyzeCommands {
+    /// Compare a baseline time range against a current one
+    Compare(CompareArgs),
+}
+
+/// analyze compare の引数
+#[derive(Parser, Debug)]
+struct CompareArgs {
+    /// Path to history database (current range, and baseline unless --baseline-log is given)
+    #[arg(long)]
     log: String,
 
-    /// Filter by process name
+    /// Path to a separate history database for the baseline range
     #[arg(long)]
-    name: Option<String>,
+    baseline_log: Option<String>,
 
-    /// Start time (ISO 8601: 2026-01-05T14:00:00+09:00)
+    /// Filter by process name (repeatable, matched per --match-mode)
     #[arg(long)]
+    name: Vec<String>,
+
+    #[command(flatten)]
+    matching: MatchArgs,
+
+    /// Baseline start time (same forms as analyze --from)
+    #[arg(long, allow_hyphen_values = true)]
+    baseline_from: Option<String>,
+
+    /// Baseline end time
+    #[arg(long, allow_hyphen_values = true)]
+    baseline_to: Option<String>,
+
+    /// Current start time
+    #[arg(long, allow_hyphen_values = true)]
     from: Option<String>,
 
-    /// End time (ISO 8601: 2026-01-05T16:00:00+09:00)
-    #[arg(long)]
+    /// Current end time
+    #[arg(long, allow_hyphen_values = true)]
     to: Option<String>,
 
     /// Output format
     #[arg(long, default_value = "table", value_enum)]
-    format: OutputFormatArg,
+    format: CompareFormatArg,
+}
+
+/// compare / sessions の出力フォーマット（CLI引数用）
+#[derive(Clone, Debug, clap::ValueEnum)]
+enum CompareFormatArg {
+    Table,
+    Json,
+}
+
+impl From<CompareFormatArg> for OutputFormat {
+    fn from(arg: CompareFormatArg) -> Self {
+        match arg {
+            CompareFormatArg::Table => OutputFormat::Table,
+            CompareFormatArg::Json => OutputFormat::Json,
+        }
+    }
 }
 
 /// 出力フォーマット（CLI引数用）
@@ -61,6 +461,7 @@ struct AnalyzeArgs {
 enum OutputFormatArg {
     Table,
     Json,
+    Csv,
 }
 
 impl From<OutputFormatArg> for OutputFormat {
@@ -68,20 +469,26 @@ impl From<OutputFormatArg> for OutputFormat {
         match arg {
             OutputFormatArg::Table => OutputFormat::Table,
             OutputFormatArg::Json => OutputFormat::Json,
+            OutputFormatArg::Csv => OutputFormat::Csv,
         }
     }
 }
 
 /// 監視モードの引数
 #[derive(Parser, Debug)]
+// 更新間隔は --watch または設定ファイルの interval で決まる
+#[command(group(ArgGroup::new("interval_source").args(["watch", "config"]).multiple(true)))]
 struct Args {
     /// 監視するプロセスのPID
     #[arg(short, long, conflicts_with = "name")]
     pid: Option<u32>,
 
-    /// 監視するプロセス名（部分一致）
+    /// 監視するプロセス名（--match-mode で照合、複数指定可）
     #[arg(short, long, conflicts_with = "pid")]
-    name: Option<String>,
+    name: Vec<String>,
+
+    #[command(flatten)]
+    matching: MatchArgs,
 
     /// リアルタイム監視モード（指定した間隔で更新、単位: 秒）
     #[arg(short, long)]
@@ -91,7 +498,7 @@ struct Args {
     #[arg(short, long, default_value = "memory")]
     sort: SortOrder,
 
-    /// TUIモードを使用（--watchと併用時のみ有効）
+    /// TUIモードを使用（--watch または --config と併用時のみ有効）
     #[arg(short = 't', long)]
     tui: bool,
 
@@ -103,6 +510,10 @@ struct Args {
     #[arg(short, long)]
     log: Option<String>,
 
+    /// 保持期間（例: 7d, 12h）。これより古い記録を定期的に削除（--log と併用）
+    #[arg(long, requires = "log", value_parser = analyze::parse_duration_secs)]
+    retention: Option<i64>,
+
     /// グラフ表示のデータポイント数（0で無効化）
     #[arg(long, default_value = "60")]
     graph_points: usize,
@@ -110,23 +521,219 @@ struct Args {
     /// プロセスをツリー形式で表示
     #[arg(long)]
     tree: bool,
+
+    /// ソート・最小メモリフィルタ・合計に使うメモリ指標: rss (デフォルト), pss, uss, swap
+    #[arg(long, default_value = "rss", value_enum)]
+    memory_metric: MemoryMetric,
+
+    /// PSS/USS/Shared/Swap 列を表示（Linux: /proc/<pid>/smaps_rollup）
+    #[arg(long)]
+    smaps: bool,
+
+    /// watch モードの出力形式: table (デフォルト), jsonl（画面をクリアせず JSON Lines を出力、--watch または --config と併用）
+    #[arg(long, default_value = "table", value_enum, requires = "interval_source", conflicts_with = "tui")]
+    output: WatchOutput,
+
+    /// JSON Lines の1行の単位: process (デフォルト、プロセスごと), tick（更新ごと）
+    #[arg(long, default_value = "process", value_enum)]
+    jsonl_mode: JsonlMode,
+
+    /// アラートのルール（例: "total_memory_mb > 2048", "cpu > 80 for 3"。複数指定可、--name・--watch と併用）
+    #[arg(long = "alert", value_parser = alert::parse_rule, requires_all = ["name", "watch"])]
+    alerts: Vec<alert::AlertRule>,
+
+    /// アラートの発火・解除時に実行するコマンド（sh -c、内容は PROCESS_MONITOR_ALERT_* 環境変数）
+    #[arg(long, requires = "alerts")]
+    on_alert: Option<String>,
+
+    /// 1回だけ表示するモードの出力形式: table (デフォルト), json, csv, tsv
+    #[arg(long, default_value = "table", value_enum, conflicts_with = "watch")]
+    format: SingleShotFormat,
+
+    /// 複数の監視対象を宣言した設定ファイル（TOML）。watch/TUI で全対象をまとめて監視
+    #[arg(long, conflicts_with_all = ["pid", "name", "format"])]
+    config: Option<String>,
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        // check の引数エラーは監視システムが UNKNOWN として扱えるよう終了コード 3
+        if e.use_stderr() && std::env::args().nth(1).as_deref() == Some("check") {
+            let _ = e.print();
+            std::process::exit(check::CheckStatus::Unknown.exit_code());
+        }
+        e.exit()
+    });
 
     // サブコマンドのルーティング
     match cli.command {
-        Some(Commands::Analyze(analyze_args)) => {
-            // analyze サブコマンド
-            let format: OutputFormat = analyze_args.format.into();
-            if let Err(e) = analyze::run_analyze(
-                &analyze_args.log,
-                analyze_args.name.as_deref(),
-                analyze_args.from.as_deref(),
-                analyze_args.to.as_deref(),
-                &format,
-            ) {
+        Some(Commands::Analyze(analyze_args)) => match analyze_args.command {
+            Some(AnalyzeCommands::Compare(compare_args)) => run_compare_command(compare_args),
+            None => run_analyze_command(*analyze_args),
+        },
+        Some(Commands::History(HistoryArgs {
+            command: HistoryCommands::Prune(prune_args),
+        })) => {
+            // history prune サブコマンド
+            if let Err(e) = retention::run_prune(retention::PruneOptions {
+                db_path: &prune_args.log,
+                older_than_secs: prune_args.older_than,
+                batch_size: prune_args.batch_size,
+                vacuum: prune_args.vacuum,
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::History(HistoryArgs {
+            command: HistoryCommands::Rollup(rollup_args),
+        })) => {
+            // history rollup サブコマンド
+            if let Err(e) = rollup::run_rollup(rollup::RollupOptions {
+                db_path: &rollup_args.log,
+                rebuild: rollup_args.rebuild,
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::History(HistoryArgs {
+            command: HistoryCommands::Sessions(sessions_args),
+        })) => {
+            // history sessions サブコマンド
+            let format: OutputFormat = sessions_args.format.into();
+            if let Err(e) = session::run_sessions(session::SessionsOptions {
+                db_path: &sessions_args.log,
+                format: &format,
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::History(HistoryArgs {
+            command: HistoryCommands::Export(export_args),
+        })) => {
+            // history export サブコマンド
+            if let Err(e) = export::run_export(export::ExportOptions {
+                db_path: &export_args.log,
+                format: export_args.format,
+                name: name_filter(&export_args.matching, &export_args.name).as_ref(),
+                from: export_args.from.as_deref(),
+                to: export_args.to.as_deref(),
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::History(HistoryArgs {
+            command: HistoryCommands::Import(import_args),
+        })) => {
+            // history import サブコマンド
+            if let Err(e) = export::run_import(export::ImportOptions {
+                db_path: &import_args.log,
+                files: &import_args.files,
+                format: import_args.format,
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::History(HistoryArgs {
+            command: HistoryCommands::Merge(merge_args),
+        })) => {
+            // history merge サブコマンド
+            if let Err(e) = merge::run_merge(merge::MergeOptions {
+                db_path: &merge_args.log,
+                sources: &merge_args.sources,
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Serve(serve_args)) => {
+            // serve サブコマンド（設定ファイルの記録先・アラートは使わない）
+            let (targets, interval_secs) = match serve_args.config {
+                Some(ref path) => {
+                    let config = load_config(path, &ConfigDefaults {
+                        match_mode: serve_args.matching.match_mode,
+                        ignore_case: serve_args.matching.ignore_case,
+                        exclude: &serve_args.matching.exclude,
+                        sort_order: &SortOrder::Pid,
+                        memory_metric: &serve_args.memory_metric,
+                        min_memory_mb: serve_args.min_memory_mb,
+                        log: None,
+                        retention_secs: None,
+                    });
+                    // PSS / USS / スワップのメトリクスも公開する
+                    let targets = config.targets.into_iter().map(|target| Target { smaps: true, ..target }).collect();
+                    (targets, serve_args.interval.or(Some(config.interval_secs)))
+                }
+                None => {
+                    let matcher = name_matcher(&serve_args.matching, &serve_args.name);
+                    let target = Target {
+                        label: matcher.to_string(),
+                        matcher,
+                        min_memory_mb: serve_args.min_memory_mb,
+                        memory_metric: serve_args.memory_metric.clone(),
+                        sort_order: SortOrder::Pid,
+                        alerts: vec![],
+                        log: None,
+                        on_alert: None,
+                        smaps: true,
+                    };
+                    (vec![target], serve_args.interval)
+                }
+            };
+            if let Err(e) = serve::run_serve(serve::ServeOptions {
+                listen: &serve_args.listen,
+                targets: &targets,
+                interval_secs,
+            }) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Check(check_args)) => {
+            // check サブコマンド（パターンの誤りも UNKNOWN）
+            let matcher = check_args.matching.matcher(&check_args.name).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(check::CheckStatus::Unknown.exit_code());
+            });
+            let query = ProcessQuery {
+                matcher: &matcher,
+                sort_order: &SortOrder::Pid,
+                min_memory_mb: check_args.min_memory_mb,
+                memory_metric: &check_args.memory_metric,
+                collect_smaps: check_args.memory_metric.needs_smaps(),
+            };
+            let status = check::run_check(check::CheckOptions {
+                query: &query,
+                count: check::Thresholds {
+                    warning: check_args.warning_count.as_ref(),
+                    critical: check_args.critical_count.as_ref(),
+                },
+                process_memory_mb: check::Thresholds {
+                    warning: check_args.warning_memory_mb.as_ref(),
+                    critical: check_args.critical_memory_mb.as_ref(),
+                },
+                total_memory_mb: check::Thresholds {
+                    warning: check_args.warning_total_memory_mb.as_ref(),
+                    critical: check_args.critical_total_memory_mb.as_ref(),
+                },
+                cpu: check::Thresholds {
+                    warning: check_args.warning_cpu.as_ref(),
+                    critical: check_args.critical_cpu.as_ref(),
+                },
+            });
+            std::process::exit(status.exit_code());
+        }
+        Some(Commands::Daemon(daemon_args)) => {
+            // daemon サブコマンド
+            if let Err(e) = daemon::run_daemon(daemon::DaemonOptions {
+                config_path: &daemon_args.config,
+                pidfile: daemon_args.pidfile.as_deref(),
+                error_log: daemon_args.error_log.as_deref(),
+            }) {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
@@ -135,31 +742,38 @@ fn main() {
             // サブコマンドなし: 既存の監視モード
             let args = &cli.monitor_args;
 
-            // リアルタイム監視モードの場合
-            if let Some(interval) = args.watch {
-                if args.tui {
-                    // TUIモード
-                    if let Some(name) = &args.name {
-                        if let Err(e) = tui::run_tui(name, &args.sort, interval, args.min_memory_mb, args.log.as_deref(), args.graph_points, args.tree) {
-                            eprintln!("Error running TUI: {}", e);
-                            std::process::exit(1);
-                        }
-                    } else {
-                        eprintln!("Error: TUI mode requires --name option");
-                        std::process::exit(1);
-                    }
-                } else {
-                    // 通常の監視モード
-                    let monitor_args = MonitorArgs {
-                        pid: args.pid,
-                        name: args.name.as_deref(),
-                        sort: &args.sort,
-                        min_memory_mb: args.min_memory_mb,
-                        log_path: args.log.as_deref(),
-                        tree: args.tree,
-                    };
-                    watch_mode(monitor_args, interval);
-                }
+            if let Some(ref path) = args.config {
+                // 設定ファイルの全監視対象をまとめて監視（--watch で間隔を上書き）
+                let config = load_config(path, &ConfigDefaults {
+                    match_mode: args.matching.match_mode,
+                    ignore_case: args.matching.ignore_case,
+                    exclude: &args.matching.exclude,
+                    sort_order: &args.sort,
+                    memory_metric: &args.memory_metric,
+                    min_memory_mb: args.min_memory_mb,
+                    log: args.log.as_deref(),
+                    retention_secs: args.retention,
+                });
+                let interval = args.watch.unwrap_or(config.interval_secs);
+                run_monitor(args, config.targets, config.retention_secs, interval, false);
+            } else if let Some(interval) = args.watch {
+                // リアルタイム監視モードの場合
+                let targets = name_filter(&args.matching, &args.name)
+                    .map(|matcher| {
+                        vec![Target {
+                            label: matcher.to_string(),
+                            matcher,
+                            min_memory_mb: args.min_memory_mb,
+                            memory_metric: args.memory_metric.clone(),
+                            sort_order: args.sort.clone(),
+                            alerts: args.alerts.clone(),
+                            log: args.log.clone(),
+                            on_alert: args.on_alert.clone(),
+                            smaps: false,
+                        }]
+                    })
+                    .unwrap_or_default();
+                run_monitor(args, targets, args.retention, interval, true);
             } else {
                 // 通常モード（1回だけ表示）
                 single_shot_mode(args);
@@ -168,19 +782,163 @@ fn main() {
     }
 }
 
+/// --name と照合オプションから抽出条件を作成（パターンが不正な場合は終了）
+fn name_matcher(matching: &MatchArgs, names: &[String]) -> NameMatcher {
+    matching.matcher(names).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// --name・--exclude のどちらかがあれば抽出条件を作成
+fn name_filter(matching: &MatchArgs, names: &[String]) -> Option<NameMatcher> {
+    (!names.is_empty() || !matching.exclude.is_empty()).then(|| name_matcher(matching, names))
+}
+
+/// 設定ファイルを読み込む（エラーの場合は終了）
+fn load_config(path: &str, defaults: &ConfigDefaults) -> Config {
+    config::load(path, defaults).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// watch / TUI モード（targets が空の場合は --pid のプロセス）
+fn run_monitor(args: &Args, mut targets: Vec<Target>, retention_secs: Option<i64>, interval: u64, exit_on_no_match: bool) {
+    // --smaps の列と JSON Lines の PSS などの値には smaps_rollup が必要
+    let smaps = args.smaps || args.output == WatchOutput::Jsonl;
+    for target in &mut targets {
+        target.smaps = smaps;
+    }
+
+    if args.tui {
+        // TUIモード
+        if targets.is_empty() {
+            eprintln!("Error: TUI mode requires --name or --config option");
+            std::process::exit(1);
+        }
+        let options = tui::TuiOptions {
+            interval_secs: interval,
+            retention_secs,
+            graph_points: args.graph_points,
+            tree_mode: args.tree,
+            show_smaps: args.smaps,
+        };
+        if let Err(e) = tui::run_tui(targets, &options) {
+            eprintln!("Error running TUI: {}", e);
+            std::process::exit(1);
+        }
+    } else {
+        // 通常の監視モード
+        let monitor_args = MonitorArgs {
+            pid: args.pid,
+            targets,
+            exit_on_no_match,
+            retention_secs,
+            tree: args.tree,
+            show_smaps: args.smaps,
+            output: &args.output,
+            jsonl_mode: &args.jsonl_mode,
+        };
+        watch_mode(monitor_args, interval);
+    }
+}
+
 /// 1回だけ表示するモード
 fn single_shot_mode(args: &Args) {
     let mut sys = System::new_all();
     sys.refresh_processes(ProcessesToUpdate::All, true);
 
-    if let Some(name) = &args.name {
+    // --pid 指定時は名前の条件を使わない
+    let matcher = if args.pid.is_some() { None } else { name_filter(&args.matching, &args.name) };
+    // JSON / CSV / TSV は PSS などの列も出力する
+    let collect_smaps = args.smaps || args.memory_metric.needs_smaps() || args.format != SingleShotFormat::Table;
+    let query = matcher.as_ref().map(|matcher| ProcessQuery {
+        matcher,
+        sort_order: &args.sort,
+        min_memory_mb: args.min_memory_mb,
+        memory_metric: &args.memory_metric,
+        collect_smaps,
+    });
+
+    // 機械可読な形式（JSON / CSV / TSV）
+    if args.format != SingleShotFormat::Table {
+        if let Err(e) = print_report(&sys, ReportOptions {
+            query: query.as_ref(),
+            pid: args.pid,
+            tree: args.tree,
+            format: &args.format,
+        }) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(query) = query {
         if args.tree {
-            show_processes_by_name_tree(&sys, name, &args.sort, args.min_memory_mb);
+            show_processes_by_name_tree(&sys, &query, args.smaps);
         } else {
-            show_processes_by_name(&sys, name, &args.sort, args.min_memory_mb);
+            show_processes_by_name(&sys, &query, args.smaps);
         }
     } else {
-        let target_pid = args.pid.unwrap_or_else(|| std::process::id());
-        show_process_by_pid(&sys, target_pid);
+        let target_pid = args.pid.unwrap_or_else(std::process::id);
+        show_process_by_pid(&sys, target_pid, args.smaps);
     }
-}
\ No newline at end of file
+}
+
+/// analyze サブコマンド
+fn run_analyze_command(analyze_args: AnalyzeArgs) {
+    let db_path = analyze_args.log.expect("--log is required without a subcommand");
+    let format: OutputFormat = analyze_args.format.into();
+    let leaks = analyze_args.leaks.then(|| LeakCriteria {
+        min_growth_bytes_per_hour: analyze_args.leak_min_rate_mb * 1024.0 * 1024.0,
+        min_r_squared: analyze_args.leak_min_r2,
+        min_duration_secs: analyze_args.leak_min_duration,
+        limit_bytes: analyze_args.leak_limit_mb.map(|mb| mb * 1024 * 1024),
+    });
+    let recommend = analyze_args.recommend.map(|target| RecommendOptions {
+        target,
+        reserve_percent: analyze_args.reserve_percent,
+        mpm: analyze_args.mpm,
+        threads_per_child: analyze_args.threads_per_child,
+    });
+    if let Err(e) = analyze::run_analyze(AnalyzeOptions {
+        db_path: &db_path,
+        name: name_filter(&analyze_args.matching, &analyze_args.name).as_ref(),
+        from: analyze_args.from.as_deref(),
+        to: analyze_args.to.as_deref(),
+        last_secs: analyze_args.last,
+        session: analyze_args.session,
+        host: analyze_args.host.as_deref(),
+        format: &format,
+        recommend: recommend.as_ref(),
+        group_by: analyze_args.group_by.as_ref(),
+        group_sort: &analyze_args.group_sort,
+        top: analyze_args.top,
+        bucket: analyze_args.bucket.as_deref(),
+        leaks,
+        events: analyze_args.events,
+    }) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// analyze compare サブコマンド
+fn run_compare_command(compare_args: CompareArgs) {
+    let format: OutputFormat = compare_args.format.into();
+    if let Err(e) = compare::run_compare(CompareOptions {
+        db_path: &compare_args.log,
+        baseline_db_path: compare_args.baseline_log.as_deref(),
+        name: name_filter(&compare_args.matching, &compare_args.name).as_ref(),
+        baseline_from: compare_args.baseline_from.as_deref(),
+        baseline_to: compare_args.baseline_to.as_deref(),
+        from: compare_args.from.as_deref(),
+        to: compare_args.to.as_deref(),
+        format: &format,
+    }) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,237 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// プロセス名の照合方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Substring,
+    Exact,
+    Regex,
+    Glob,
+}
+
+/// プロセス名の照合オプション（--name と組み合わせるサブコマンドで共有）
+#[derive(clap::Args, Debug, Clone)]
+pub struct MatchArgs {
+    /// How --name and --exclude are matched against process names: substring (default), exact, regex, glob
+    #[arg(long, default_value = "substring", value_enum)]
+    pub match_mode: MatchMode,
+
+    /// Match process names case-insensitively
+    #[arg(long)]
+    pub ignore_case: bool,
+
+    /// Exclude processes whose name matches this pattern (repeatable)
+    #[arg(long)]
+    pub exclude: Vec<String>,
+}
+
+impl MatchArgs {
+    /// --name の値と組み合わせて NameMatcher を作成
+    pub fn matcher(&self, names: &[String]) -> Result<NameMatcher, String> {
+        NameMatcher::new(names, &self.exclude, self.match_mode, self.ignore_case)
+    }
+}
+
+/// コンパイル済みの1つのパターン
+#[derive(Debug, Clone)]
+enum Pattern {
+    Substring(String),
+    Exact(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// ignore_case の場合、文字列のパターンは小文字で保持し、照合する名前も小文字にする
+    fn new(pattern: &str, mode: MatchMode, ignore_case: bool) -> Result<Self, String> {
+        let fold = |s: &str| if ignore_case { s.to_lowercase() } else { s.to_string() };
+        let regex = |source: &str| {
+            RegexBuilder::new(source)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|e| format!("invalid pattern '{}': {}", pattern, e))
+        };
+        Ok(match mode {
+            MatchMode::Substring => Pattern::Substring(fold(pattern)),
+            MatchMode::Exact => Pattern::Exact(fold(pattern)),
+            MatchMode::Regex => Pattern::Regex(regex(pattern)?),
+            MatchMode::Glob => Pattern::Regex(regex(&glob_to_regex(pattern))?),
+        })
+    }
+
+    fn is_match(&self, name: &str, folded: &str) -> bool {
+        match self {
+            Pattern::Substring(pattern) => folded.contains(pattern.as_str()),
+            Pattern::Exact(pattern) => folded == pattern,
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// シェルのワイルドカード（`*`, `?`, `[abc]`, `[!abc]`）を名前全体に一致する正規表現に変換
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::from("^");
+    let chars: Vec<char> = glob.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            '[' => {
+                // 閉じていない [ は文字として扱う（[] の直後の ] はクラスの文字）
+                let start = if chars.get(i + 1) == Some(&'!') { i + 2 } else { i + 1 };
+                match chars.iter().skip(start + 1).position(|&c| c == ']') {
+                    Some(offset) => {
+                        let end = start + 1 + offset;
+                        out.push('[');
+                        if start == i + 2 {
+                            out.push('^');
+                        }
+                        for &c in &chars[start..end] {
+                            if c == '-' {
+                                out.push(c);
+                            } else {
+                                out.push_str(&regex::escape(&c.to_string()));
+                            }
+                        }
+                        out.push(']');
+                        i = end;
+                    }
+                    None => out.push_str(r"\["),
+                }
+            }
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    out.push('$');
+    out
+}
+
+/// プロセス名の抽出条件（--name・--exclude・--match-mode・--ignore-case）
+///
+/// --name のいずれかに一致し（指定がなければすべて）、--exclude のどれにも一致しない名前を選ぶ
+#[derive(Debug, Clone)]
+pub struct NameMatcher {
+    names: Vec<String>,
+    excludes: Vec<String>,
+    mode: MatchMode,
+    ignore_case: bool,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl NameMatcher {
+    pub fn new(names: &[String], excludes: &[String], mode: MatchMode, ignore_case: bool) -> Result<Self, String> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p, mode, ignore_case))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            names: names.to_vec(),
+            excludes: excludes.to_vec(),
+            mode,
+            ignore_case,
+            include: compile(names)?,
+            exclude: compile(excludes)?,
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        let folded = if self.ignore_case { name.to_lowercase() } else { name.to_string() };
+        (self.include.is_empty() || self.include.iter().any(|p| p.is_match(name, &folded)))
+            && !self.exclude.iter().any(|p| p.is_match(name, &folded))
+    }
+
+    /// 条件の説明（例: "contains 'php' or 'httpd' and does not contain 'phpunit' (ignore case)"）
+    pub fn describe(&self) -> String {
+        let quoted = |patterns: &[String]| {
+            patterns.iter().map(|p| format!("'{}'", p)).collect::<Vec<_>>().join(" or ")
+        };
+        let (verb, negated) = match self.mode {
+            MatchMode::Substring => ("contains", "does not contain"),
+            MatchMode::Exact => ("is", "is not"),
+            MatchMode::Regex => ("matches regex", "does not match regex"),
+            MatchMode::Glob => ("matches glob", "does not match glob"),
+        };
+        let mut parts = vec![];
+        if !self.names.is_empty() {
+            parts.push(format!("{} {}", verb, quoted(&self.names)));
+        }
+        if !self.excludes.is_empty() {
+            parts.push(format!("{} {}", negated, quoted(&self.excludes)));
+        }
+        let mut text = parts.join(" and ");
+        if self.ignore_case {
+            text.push_str(" (ignore case)");
+        }
+        text
+    }
+}
+
+/// 表示・記録用の名前（--name の値をカンマ区切り、除外があれば "!" 付きで続ける）
+impl fmt::Display for NameMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let excludes = self.excludes.iter().map(|p| format!("!{}", p));
+        let parts: Vec<String> = self.names.iter().cloned().chain(excludes).collect();
+        f.write_str(&parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(names: &[&str], excludes: &[&str], mode: MatchMode, ignore_case: bool) -> NameMatcher {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        NameMatcher::new(&strings(names), &strings(excludes), mode, ignore_case).unwrap()
+    }
+
+    #[test]
+    fn test_match_modes() {
+        let substring = matcher(&["php"], &[], MatchMode::Substring, false);
+        assert!(substring.is_match("php-fpm") && substring.is_match("phpunit"));
+        assert!(!substring.is_match("PHP-FPM"));
+
+        let exact = matcher(&["php-fpm"], &[], MatchMode::Exact, true);
+        assert!(exact.is_match("PHP-FPM"));
+        assert!(!exact.is_match("php-fpm8.2"));
+
+        let regex = matcher(&["^php-fpm[0-9.]*$"], &[], MatchMode::Regex, false);
+        assert!(regex.is_match("php-fpm8.2"));
+        assert!(!regex.is_match("phpunit"));
+
+        let glob = matcher(&["php-fpm*", "httpd"], &[], MatchMode::Glob, false);
+        assert!(glob.is_match("php-fpm8.2") && glob.is_match("httpd"));
+        assert!(!glob.is_match("xhttpd") && !glob.is_match("php"));
+
+        assert!(NameMatcher::new(&["(".to_string()], &[], MatchMode::Regex, false).is_err());
+    }
+
+    #[test]
+    fn test_excludes_and_description() {
+        let m = matcher(&["php"], &["phpunit", "php-cgi"], MatchMode::Substring, false);
+        assert!(m.is_match("php-fpm"));
+        assert!(!m.is_match("phpunit") && !m.is_match("php-cgi"));
+        assert_eq!(m.to_string(), "php, !phpunit, !php-cgi");
+        assert_eq!(m.describe(), "contains 'php' and does not contain 'phpunit' or 'php-cgi'");
+
+        // --name なしで除外のみ
+        let m = matcher(&[], &["kworker*"], MatchMode::Glob, false);
+        assert!(m.is_match("sshd") && !m.is_match("kworker/0:1"));
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!(glob_to_regex("a*b?.c"), r"^a.*b.\.c$");
+        assert_eq!(glob_to_regex("php[!0-9]"), "^php[^0-9]$");
+        assert_eq!(glob_to_regex("[]a]"), r"^[\]a]$");
+        // 閉じていない [ は文字として扱う
+        assert_eq!(glob_to_regex("x[y"), r"^x\[y$");
+    }
+}
@@ -0,0 +1,91 @@
+use crate::history::ProcessHistory;
+use chrono::Local;
+use std::path::Path;
+
+/// history merge の引数
+pub struct MergeOptions<'a> {
+    pub db_path: &'a str,
+    pub sources: &'a [String],
+}
+
+/// `LABEL=PATH` または `PATH` を (ラベル, パス) に分解
+///
+/// ラベルを省略した場合はファイル名（拡張子を除く）をラベルにする
+fn parse_source(source: &str) -> Result<(String, &str), String> {
+    if let Some((label, path)) = source.split_once('=')
+        && !label.is_empty()
+        && !Path::new(source).exists()
+    {
+        return Ok((label.to_string(), path));
+    }
+
+    let label = Path::new(source)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| !stem.is_empty())
+        .ok_or_else(|| format!("Cannot derive a host label from {}. Use LABEL=PATH", source))?;
+    Ok((label.to_string(), source))
+}
+
+/// history merge のエントリーポイント（データベースがなければ作成）
+pub fn run_merge(options: MergeOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let sources = options
+        .sources
+        .iter()
+        .map(|source| parse_source(source))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut history = ProcessHistory::new(options.db_path)?;
+    let target = Path::new(options.db_path).canonicalize()?;
+
+    let mut range: Option<(i64, i64)> = None;
+    for (label, path) in sources {
+        if !Path::new(path).exists() {
+            return Err(format!("Database file not found: {}", path).into());
+        }
+        if Path::new(path).canonicalize()? == target {
+            return Err(format!("Cannot merge {} into itself", path).into());
+        }
+
+        let counts = history
+            .merge_from(path, &label)
+            .map_err(|e| format!("Failed to merge {}: {}", path, e))?;
+        println!(
+            "Merged {} as '{}': {} snapshot(s), {} event(s), {} system snapshot(s), {} session(s), {} alert(s)",
+            path, label, counts.snapshots, counts.events, counts.system_snapshots, counts.sessions, counts.alerts
+        );
+        if let Some((first, last)) = counts.range {
+            range = Some(range.map_or((first, last), |(f, l)| (f.min(first), l.max(last))));
+        }
+    }
+
+    // 取り込んだ範囲の集計を作り直す（範囲外の集計はそのまま残す）
+    let now = Local::now().timestamp();
+    let counts = match range {
+        Some((first, last)) => history.rebuild_rollups(first, last, now),
+        None => history.update_rollups(now),
+    }
+    .map_err(|e| format!("Failed to rebuild rollups: {}", e))?;
+    println!(
+        "Rollups rebuilt: {} per-minute row(s), {} per-hour row(s)",
+        counts.minute, counts.hour
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            parse_source("web01=/var/log/monitor.db").unwrap(),
+            ("web01".to_string(), "/var/log/monitor.db")
+        );
+        assert_eq!(
+            parse_source("/tmp/web02.db").unwrap(),
+            ("web02".to_string(), "/tmp/web02.db")
+        );
+    }
+}
@@ -1,76 +1,211 @@
 use sysinfo::{System, ProcessesToUpdate};
+use std::io::{self, BufWriter, ErrorKind, Write};
 use std::thread;
 use std::time::Duration;
-use crate::process::{show_process_by_pid, show_processes_by_name, show_processes_by_name_tree, create_snapshots, SortOrder};
-use crate::history::ProcessHistory;
+use crate::alert::{self, AlertEvaluator};
+use crate::process::{exit_no_match, print_process_list, print_process_tree, print_system_information, show_process_by_pid, snapshots_from_nodes, snapshot_by_pid, SystemMemory};
+use crate::export::SnapshotRecord;
+use crate::history::ProcessSnapshot;
+use crate::targets::{Target, TargetSet, TargetSetOptions};
+use serde::Serialize;
+
+/// watch モードの出力形式
+#[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
+pub enum WatchOutput {
+    Table, // 画面をクリアして表を表示
+    Jsonl, // 画面をクリアせず JSON Lines を標準出力へ
+}
+
+/// JSON Lines の1行の単位
+#[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
+pub enum JsonlMode {
+    Process, // プロセスごとに1行
+    Tick,    // 更新ごとに1行（プロセスの一覧とシステムメモリ）
+}
+
+/// --jsonl-mode tick の1行
+#[derive(Serialize)]
+struct TickRecord {
+    timestamp: String,
+    system_memory: SystemMemory,
+    processes: Vec<SnapshotRecord>,
+}
 
 pub struct MonitorArgs<'a> {
     pub pid: Option<u32>,
-    pub name: Option<&'a str>,
-    pub sort: &'a SortOrder,
-    pub min_memory_mb: Option<u64>,
-    pub log_path: Option<&'a str>,
+    pub targets: Vec<Target>, // pid モードでは空
+    /// 一致するプロセスがなくなったら終了する（--name で1つだけ指定した場合）
+    pub exit_on_no_match: bool,
+    pub retention_secs: Option<i64>,
     pub tree: bool,
+    pub show_smaps: bool,
+    pub output: &'a WatchOutput,
+    pub jsonl_mode: &'a JsonlMode,
 }
 
 /// リアルタイム監視モード
 pub fn watch_mode(args: MonitorArgs, interval_secs: u64) {
     let mut sys = System::new_all();
+    let jsonl = *args.output == WatchOutput::Jsonl;
 
-    // 履歴記録の初期化
-    let mut history = if let Some(log_path) = args.log_path {
-        match ProcessHistory::new(log_path) {
-            Ok(h) => {
-                println!("Logging to: {}", log_path);
-                Some(h)
-            }
-            Err(e) => {
-                eprintln!("Warning: Failed to initialize history database: {}", e);
-                eprintln!("Continuing without logging...");
-                None
-            }
+    // 記録先の初期化・起動・終了の検出・アラートの評価（name モードのみ）
+    let (mut targets, warnings) = TargetSet::new(
+        args.targets,
+        &TargetSetOptions {
+            interval_secs,
+            retention_secs: args.retention_secs,
+            hook_output: true,
+        },
+    );
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    for path in targets.log_paths() {
+        // JSON Lines 出力時は標準出力を汚さない
+        if jsonl {
+            eprintln!("Logging to: {}", path);
+        } else {
+            println!("Logging to: {}", path);
         }
-    } else {
-        None
-    };
+    }
+    let multiple = targets.len() > 1;
 
     loop {
         // 画面をクリア（ANSIエスケープシーケンス）
-        print!("\x1B[2J\x1B[1;1H");
+        if !jsonl {
+            print!("\x1B[2J\x1B[1;1H");
+        }
 
         // プロセス情報を更新
         sys.refresh_processes(ProcessesToUpdate::All, true);
+        sys.refresh_memory();
+        sys.refresh_cpu_usage();
 
-        // 履歴記録（name モードのみ）
-        if let Some(ref mut hist) = history {
-            if let Some(name) = args.name {
-                let snapshots = create_snapshots(&sys, name, args.min_memory_mb);
-                if let Err(e) = hist.insert_snapshots(&snapshots) {
-                    eprintln!("Warning: Failed to log snapshots: {}", e);
-                }
-            }
+        // すべての監視対象の抽出・記録・アラートの評価
+        let result = targets.update(&sys);
+        for event in &result.alerts {
+            // 表は毎回クリアされるため、発火・解除は標準エラー出力にも残す
+            eprintln!("{}", alert::describe(event));
+        }
+        for warning in &result.warnings {
+            eprintln!("Warning: {}", warning);
         }
 
-        // 現在時刻を表示
-        println!("Last updated: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
-        if history.is_some() {
-            println!("Logging: enabled");
+        // --name で1つだけ指定した場合は、一致するプロセスがなくなったら終了（表・JSON Lines 共通）
+        if args.exit_on_no_match
+            && let Some((target, _)) = targets.results().find(|(_, nodes)| nodes.is_empty())
+        {
+            exit_no_match(&target.query());
         }
-        println!("Press Ctrl+C to exit\n");
 
-        // プロセス情報を表示
-        if let Some(name) = args.name {
-            if args.tree {
-                show_processes_by_name_tree(&sys, name, args.sort, args.min_memory_mb);
+        if jsonl {
+            // pid モードは指定したプロセスのみ（終了したらエラーで終了する点は表示時と同じ）
+            let snapshots = if !targets.is_empty() {
+                targets.results().flat_map(|(_, nodes)| snapshots_from_nodes(nodes)).collect()
             } else {
-                show_processes_by_name(&sys, name, args.sort, args.min_memory_mb);
+                let target_pid = args.pid.unwrap_or_else(std::process::id);
+                match snapshot_by_pid(&sys, target_pid) {
+                    Some(snapshot) => vec![snapshot],
+                    None => {
+                        eprintln!("Error: Process not found (PID: {})", target_pid);
+                        std::process::exit(1);
+                    }
+                }
+            };
+            let mut out = BufWriter::new(io::stdout().lock());
+            match write_jsonl(&mut out, &sys, &snapshots, args.jsonl_mode) {
+                // 読み手（head や jq など）が終了したら、記録を閉じて正常終了
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                    for warning in targets.close() {
+                        eprintln!("Warning: {}", warning);
+                    }
+                    std::process::exit(0);
+                }
+                Err(e) => eprintln!("Warning: Failed to write JSON: {}", e),
+                Ok(()) => {}
             }
         } else {
-            let target_pid = args.pid.unwrap_or_else(|| std::process::id());
-            show_process_by_pid(&sys, target_pid);
+            // 現在時刻を表示
+            println!("Last updated: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+            if targets.is_logging() {
+                println!("Logging: enabled");
+            }
+            for (index, (target, _)) in targets.results().enumerate() {
+                let evaluator = targets.evaluator(index);
+                if !evaluator.is_empty() {
+                    print_alert_status(multiple.then_some(target.label.as_str()), evaluator);
+                }
+            }
+            println!("Press Ctrl+C to exit\n");
+
+            // プロセス情報を表示
+            if !targets.is_empty() {
+                print_system_information(&sys);
+                for (target, nodes) in targets.results() {
+                    let query = target.query();
+                    if multiple {
+                        println!("=== Target: {} ===", target.label);
+                    }
+                    if nodes.is_empty() {
+                        // 設定ファイルの監視対象は、一致するプロセスがなくても監視を続ける
+                        println!("No processes found matching '{}'\n", target.matcher);
+                    } else if args.tree {
+                        print_process_tree(&query, nodes, args.show_smaps);
+                        println!();
+                    } else {
+                        print_process_list(&query, nodes.to_vec(), args.show_smaps);
+                        println!();
+                    }
+                }
+            } else {
+                let target_pid = args.pid.unwrap_or_else(std::process::id);
+                show_process_by_pid(&sys, target_pid, args.show_smaps);
+            }
         }
 
         // 指定秒数待機
         thread::sleep(Duration::from_secs(interval_secs));
     }
-}
\ No newline at end of file
+}
+
+/// 発火中のアラートを表示（複数の監視対象がある場合は表示名付き）
+fn print_alert_status(label: Option<&str>, evaluator: &AlertEvaluator) {
+    let title = label.map_or("Alerts".to_string(), |label| format!("Alerts [{}]", label));
+    let firing: Vec<String> = evaluator
+        .firing()
+        .map(|(rule, value)| format!("{} (value: {:.2})", rule, value))
+        .collect();
+    if firing.is_empty() {
+        println!("{}: OK ({} rule(s))", title, evaluator.len());
+    } else {
+        println!("{}: FIRING {}", title, firing.join(", "));
+    }
+}
+
+/// 1回の更新分を JSON Lines で出力（更新ごとに1回 flush）
+fn write_jsonl(
+    out: &mut impl Write,
+    sys: &System,
+    snapshots: &[ProcessSnapshot],
+    mode: &JsonlMode,
+) -> io::Result<()> {
+    let records: Vec<SnapshotRecord> = snapshots.iter().map(SnapshotRecord::from).collect();
+    match mode {
+        JsonlMode::Process => {
+            for record in &records {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+            }
+        }
+        JsonlMode::Tick => {
+            let tick = TickRecord {
+                timestamp: chrono::Local::now().to_rfc3339(),
+                system_memory: SystemMemory::current(sys),
+                processes: records,
+            };
+            serde_json::to_writer(&mut *out, &tick)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
@@ -1,12 +1,14 @@
-use crate::formatter::{format_bytes, format_status, format_system_memory, format_system_swap, get_tgid, get_thread_count, truncate_string};
+use crate::formatter::{format_bytes, format_optional_bytes, format_status, format_system_memory, format_system_swap, get_smaps_memory, truncate_string, SmapsMemory};
 use crate::history::ProcessSnapshot;
-use crate::tree::{build_process_tree, create_tree_nodes, generate_tree_prefix};
+use crate::matcher::NameMatcher;
+use crate::tree::{build_process_tree, create_tree_node, create_tree_nodes, generate_tree_prefix, ProcessTreeNode};
 use chrono::Local;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
 use sysinfo::{Pid, System};
 
 /// ソート順の指定
-#[derive(Debug, Clone, clap::ValueEnum)]
+#[derive(Debug, Clone, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum SortOrder {
     Memory,  // メモリ使用量順（降順）
     Cpu,     // CPU使用率順（降順）
@@ -14,198 +16,310 @@ pub enum SortOrder {
     Name,    // プロセス名順（昇順）
 }
 
-/// PIDでプロセス情報を表示
-pub fn show_process_by_pid(sys: &System, target_pid: u32) {
-    let pid = Pid::from_u32(target_pid);
+/// メモリの指標（ソート・フィルタ・合計に使用）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryMetric {
+    Rss,   // Resident Set Size（共有ページを重複カウント）
+    Pss,   // Proportional Set Size（共有ページを按分）
+    Uss,   // Unique Set Size（プロセス固有のページのみ）
+    Swap,  // スワップアウトされた量
+}
 
-    if let Some(process) = sys.process(pid) {
-        println!("Process Information:");
-        println!("  PID:     {}", process.pid());
-        println!("  Name:    {}", process.name().to_string_lossy());
-        println!("  CPU:     {:.2}%", process.cpu_usage());
-        println!("  Memory:  {}", format_bytes(process.memory()));
-        println!("  Status:  {:?}", process.status());
-    } else {
-        eprintln!("Error: Process not found (PID: {})", target_pid);
-        std::process::exit(1);
+impl MemoryMetric {
+    /// RSS と smaps の値から指標に対応する値を選択
+    ///
+    /// smaps が取得できない場合、PSS/USS は RSS、Swap は 0 にフォールバックする
+    pub fn select(&self, rss: u64, smaps: Option<&SmapsMemory>) -> u64 {
+        match (self, smaps) {
+            (MemoryMetric::Rss, _) => rss,
+            (MemoryMetric::Pss, Some(m)) => m.pss,
+            (MemoryMetric::Uss, Some(m)) => m.uss,
+            (MemoryMetric::Swap, Some(m)) => m.swap,
+            (MemoryMetric::Pss | MemoryMetric::Uss, None) => rss,
+            (MemoryMetric::Swap, None) => 0,
+        }
+    }
+
+    /// 値の取得に smaps_rollup が必要か（RSS 以外）
+    pub fn needs_smaps(&self) -> bool {
+        !matches!(self, MemoryMetric::Rss)
+    }
+
+    /// 表示用のラベル
+    pub fn label(&self) -> &'static str {
+        match self {
+            MemoryMetric::Rss => "RSS",
+            MemoryMetric::Pss => "PSS",
+            MemoryMetric::Uss => "USS",
+            MemoryMetric::Swap => "Swap",
+        }
+    }
+
+    /// 合計行の見出し（RSS の場合は従来どおり "Memory"）
+    pub fn summary_title(&self) -> String {
+        match self {
+            MemoryMetric::Rss => "Memory".to_string(),
+            _ => format!("Memory ({})", self.label()),
+        }
     }
 }
 
-/// プロセス名でプロセス情報を表示（複数マッチする可能性あり）
-pub fn show_processes_by_name(sys: &System, name: &str, sort_order: &SortOrder, min_memory_mb: Option<u64>) {
-    let min_memory_bytes = min_memory_mb.map(|mb| mb * 1024 * 1024);
+/// プロセスの抽出条件
+pub struct ProcessQuery<'a> {
+    pub matcher: &'a NameMatcher,
+    pub sort_order: &'a SortOrder,
+    pub min_memory_mb: Option<u64>,
+    pub memory_metric: &'a MemoryMetric,
+    pub collect_smaps: bool, // smaps_rollup を読むか（PSS/USS/Swap 指標、--smaps、記録時）
+}
+
+/// システム全体のメモリ（JSON 出力用）
+#[derive(Serialize)]
+pub struct SystemMemory {
+    pub total_memory_bytes: u64,
+    pub used_memory_bytes: u64,
+    pub available_memory_bytes: u64,
+    pub total_swap_bytes: u64,
+    pub used_swap_bytes: u64,
+}
+
+impl SystemMemory {
+    /// 現在の System の値から作成（メモリは呼び出し側で更新しておく）
+    pub fn current(sys: &System) -> Self {
+        Self {
+            total_memory_bytes: sys.total_memory(),
+            used_memory_bytes: sys.used_memory(),
+            available_memory_bytes: sys.available_memory(),
+            total_swap_bytes: sys.total_swap(),
+            used_swap_bytes: sys.used_swap(),
+        }
+    }
+}
 
+/// 抽出したプロセスの集計値
+#[derive(Serialize)]
+pub struct ProcessSummary {
+    pub count: usize,
+    pub total_threads: usize,
+    pub total_cpu: f32,
+    #[serde(rename = "total_memory_bytes")]
+    pub total_memory: u64,
+    #[serde(rename = "min_memory_bytes")]
+    pub min_memory: u64,
+    #[serde(rename = "avg_memory_bytes")]
+    pub avg_memory: u64,
+    #[serde(rename = "max_memory_bytes")]
+    pub max_memory: u64,
+}
+
+impl ProcessSummary {
+    /// ノードの一覧から集計（メモリは指定した指標で計算）
+    pub fn from_nodes(nodes: &[ProcessTreeNode], memory_metric: &MemoryMetric) -> Self {
+        let count = nodes.len();
+        let memories: Vec<u64> = nodes.iter().map(|n| n.memory_by(memory_metric)).collect();
+        let total_memory: u64 = memories.iter().sum();
+
+        Self {
+            count,
+            total_threads: nodes.iter().map(|n| n.thread_count).sum(),
+            total_cpu: nodes.iter().map(|n| n.cpu_usage).sum(),
+            total_memory,
+            min_memory: *memories.iter().min().unwrap_or(&0),
+            avg_memory: if count > 0 { total_memory / count as u64 } else { 0 },
+            max_memory: *memories.iter().max().unwrap_or(&0),
+        }
+    }
+}
+
+/// 条件に一致するプロセスを TGID でグループ化したノードとして取得
+///
+/// 最小メモリフィルタは選択したメモリ指標で判定する
+pub fn find_matching_nodes(sys: &System, query: &ProcessQuery) -> Vec<ProcessTreeNode> {
     let matching_processes: Vec<_> = sys.processes()
         .iter()
-        .filter(|(_, p)| {
-            let matches_name = p.name().to_string_lossy().contains(name);
-            let meets_min_memory = if let Some(min_bytes) = min_memory_bytes {
-                p.memory() >= min_bytes
-            } else {
-                true
-            };
-            matches_name && meets_min_memory
-        })
+        .filter(|(_, p)| query.matcher.is_match(&p.name().to_string_lossy()))
         .collect();
 
-    if matching_processes.is_empty() {
-        eprintln!("Error: No processes found matching '{}'", name);
-        if let Some(min_mb) = min_memory_mb {
-            eprintln!("(with minimum memory filter: {} MB)", min_mb);
-        }
-        std::process::exit(1);
-    }
+    let min_memory_bytes = query.min_memory_mb.map(|mb| mb * 1024 * 1024);
 
-    // ツリーノードに変換（TGIDでグループ化される）
-    let tree_nodes = create_tree_nodes(&matching_processes);
+    create_tree_nodes(&matching_processes, query.collect_smaps)
+        .into_iter()
+        .filter(|node| match min_memory_bytes {
+            Some(min_bytes) => node.memory_by(query.memory_metric) >= min_bytes,
+            None => true,
+        })
+        .collect()
+}
 
-    // ソート
-    let mut sorted_nodes = tree_nodes;
+/// ノードをソート
+pub fn sort_nodes(nodes: &mut [ProcessTreeNode], sort_order: &SortOrder, memory_metric: &MemoryMetric) {
     match sort_order {
         SortOrder::Memory => {
-            sorted_nodes.sort_by(|a, b| b.memory_bytes.cmp(&a.memory_bytes));
+            nodes.sort_by_key(|n| std::cmp::Reverse(n.memory_by(memory_metric)));
         }
         SortOrder::Cpu => {
-            sorted_nodes.sort_by(|a, b| {
+            nodes.sort_by(|a, b| {
                 b.cpu_usage.partial_cmp(&a.cpu_usage).unwrap_or(std::cmp::Ordering::Equal)
             });
         }
         SortOrder::Pid => {
-            sorted_nodes.sort_by_key(|n| n.pid);
+            nodes.sort_by_key(|n| n.pid);
         }
         SortOrder::Name => {
-            sorted_nodes.sort_by(|a, b| a.process_name.cmp(&b.process_name));
+            nodes.sort_by(|a, b| a.process_name.cmp(&b.process_name));
         }
     }
+}
 
-    // 統計情報の計算（グループ化後のユニークなプロセスから）
-    let total_count = sorted_nodes.len();
-    let total_memory: u64 = sorted_nodes.iter().map(|n| n.memory_bytes).sum();
-    let total_cpu: f32 = sorted_nodes.iter().map(|n| n.cpu_usage).sum();
-    let total_threads: usize = sorted_nodes.iter().map(|n| n.thread_count).sum();
-
-    // メモリの統計値（Min/Avg/Max）
-    let (min_memory, avg_memory, max_memory) = if total_count > 0 {
-        let memories: Vec<u64> = sorted_nodes.iter().map(|n| n.memory_bytes).collect();
-        let min = *memories.iter().min().unwrap_or(&0);
-        let max = *memories.iter().max().unwrap_or(&0);
-        let avg = total_memory / total_count as u64;
-        (min, avg, max)
+/// PIDでプロセス情報を表示
+pub fn show_process_by_pid(sys: &System, target_pid: u32, show_smaps: bool) {
+    let pid = Pid::from_u32(target_pid);
+
+    if let Some(process) = sys.process(pid) {
+        println!("Process Information:");
+        println!("  PID:     {}", process.pid());
+        println!("  Name:    {}", process.name().to_string_lossy());
+        println!("  CPU:     {:.2}%", process.cpu_usage());
+        println!("  Memory:  {}", format_bytes(process.memory()));
+        if show_smaps {
+            let smaps = get_smaps_memory(target_pid);
+            println!("  PSS:     {}", format_optional_bytes(smaps.map(|m| m.pss)));
+            println!("  USS:     {}", format_optional_bytes(smaps.map(|m| m.uss)));
+            println!("  Shared:  {}", format_optional_bytes(smaps.map(|m| m.shared())));
+            println!("  Swap:    {}", format_optional_bytes(smaps.map(|m| m.swap)));
+        }
+        println!("  Status:  {:?}", process.status());
     } else {
-        (0, 0, 0)
-    };
+        eprintln!("Error: Process not found (PID: {})", target_pid);
+        std::process::exit(1);
+    }
+}
+
+/// 条件に一致するプロセスが無い場合のエラー表示と終了
+pub fn exit_no_match(query: &ProcessQuery) -> ! {
+    eprintln!("Error: No processes found matching '{}'", query.matcher);
+    if let Some(min_mb) = query.min_memory_mb {
+        eprintln!("(with minimum memory filter: {} MB)", min_mb);
+    }
+    std::process::exit(1);
+}
 
-    // ===== ヘッダー表示（システム情報追加） =====
+/// システム全体のメモリ・スワップを表示
+pub fn print_system_information(sys: &System) {
     println!("=== System Information ===");
     println!("{}", format_system_memory(sys));
     println!("{}", format_system_swap(sys));
     println!();
+}
 
-    println!("=== Process Information ===");
-    print!("Processes matching '{}'", name);
-    if let Some(min_mb) = min_memory_mb {
-        print!(" (>= {} MB)", min_mb);
+/// 集計値のヘッダーを表示
+fn print_summary_header(query: &ProcessQuery, summary: &ProcessSummary, title: &str) {
+    println!("=== {} ===", title);
+    print!("Processes matching '{}'", query.matcher);
+    if let Some(min_mb) = query.min_memory_mb {
+        print!(" (>= {} MB {})", min_mb, query.memory_metric.label());
     }
-    println!(" (sorted by {:?}):", sort_order);
+    println!(" (sorted by {:?}):", query.sort_order);
+
+    println!("Total: {} process(es) ({} threads)", summary.count, summary.total_threads);
+    println!("{}: {} (Min: {}, Avg: {}, Max: {})",
+             query.memory_metric.summary_title(),
+             format_bytes(summary.total_memory),
+             format_bytes(summary.min_memory),
+             format_bytes(summary.avg_memory),
+             format_bytes(summary.max_memory));
+    println!("CPU: {:.2}%\n", summary.total_cpu);
+}
+
+/// smaps 由来の列（PSS/USS/Shared/Swap）を整形
+fn format_smaps_columns(smaps: Option<&SmapsMemory>) -> String {
+    format!("{:<12} {:<12} {:<12} {:<12}",
+            format_optional_bytes(smaps.map(|m| m.pss)),
+            format_optional_bytes(smaps.map(|m| m.uss)),
+            format_optional_bytes(smaps.map(|m| m.shared())),
+            format_optional_bytes(smaps.map(|m| m.swap)))
+}
+
+/// プロセス名でプロセス情報を表示（複数マッチする可能性あり）
+pub fn show_processes_by_name(sys: &System, query: &ProcessQuery, show_smaps: bool) {
+    // ツリーノードに変換（TGIDでグループ化される）
+    let nodes = find_matching_nodes(sys, query);
+
+    if nodes.is_empty() {
+        exit_no_match(query);
+    }
+
+    print_system_information(sys);
+    print_process_list(query, nodes, show_smaps);
+}
+
+/// 抽出済みのノードをソートして表示
+pub fn print_process_list(query: &ProcessQuery, mut sorted_nodes: Vec<ProcessTreeNode>, show_smaps: bool) {
+    // ソート
+    sort_nodes(&mut sorted_nodes, query.sort_order, query.memory_metric);
+
+    // 統計情報の計算（グループ化後のユニークなプロセスから）
+    let summary = ProcessSummary::from_nodes(&sorted_nodes, query.memory_metric);
 
-    println!("Total: {} process(es) ({} threads)", total_count, total_threads);
-    println!("Memory: {} (Min: {}, Avg: {}, Max: {})",
-             format_bytes(total_memory),
-             format_bytes(min_memory),
-             format_bytes(avg_memory),
-             format_bytes(max_memory));
-    println!("CPU: {:.2}%\n", total_cpu);
+    print_summary_header(query, &summary, "Process Information");
 
     // 表のヘッダー
-    println!("{:<8} {:<25} {:<8} {:<8} {:<12} {:<15}",
-             "PID", "Name", "Threads", "CPU %", "Memory", "Status");
-    println!("{}", "-".repeat(82));
+    print!("{:<8} {:<25} {:<8} {:<8} {:<12} {:<15}",
+           "PID", "Name", "Threads", "CPU %", "Memory", "Status");
+    if show_smaps {
+        print!(" {:<12} {:<12} {:<12} {:<12}", "PSS", "USS", "Shared", "Swap");
+    }
+    println!();
+    println!("{}", "-".repeat(if show_smaps { 134 } else { 82 }));
 
     // ソート済みのユニークなプロセスを表示
     for node in sorted_nodes {
-        println!("{:<8} {:<25} {:<8} {:<8.2} {:<12} {:<15}",
-                 node.pid,
-                 truncate_string(&node.process_name, 25),
-                 node.thread_count,
-                 node.cpu_usage,
-                 format_bytes(node.memory_bytes),
-                 format_status(node.status));
+        print!("{:<8} {:<25} {:<8} {:<8.2} {:<12} {:<15}",
+               node.pid,
+               truncate_string(&node.process_name, 25),
+               node.thread_count,
+               node.cpu_usage,
+               format_bytes(node.memory_bytes),
+               format_status(node.status));
+        if show_smaps {
+            print!(" {}", format_smaps_columns(node.smaps.as_ref()));
+        }
+        println!();
     }
 }
 
 /// プロセス名でプロセス情報をツリー表示（複数マッチする可能性あり）
-pub fn show_processes_by_name_tree(sys: &System, name: &str, sort_order: &SortOrder, min_memory_mb: Option<u64>) {
-    let min_memory_bytes = min_memory_mb.map(|mb| mb * 1024 * 1024);
-
-    let matching_processes: Vec<_> = sys.processes()
-        .iter()
-        .filter(|(_, p)| {
-            let matches_name = p.name().to_string_lossy().contains(name);
-            let meets_min_memory = if let Some(min_bytes) = min_memory_bytes {
-                p.memory() >= min_bytes
-            } else {
-                true
-            };
-            matches_name && meets_min_memory
-        })
-        .collect();
+pub fn show_processes_by_name_tree(sys: &System, query: &ProcessQuery, show_smaps: bool) {
+    // ツリーノードに変換
+    let tree_nodes = find_matching_nodes(sys, query);
 
-    if matching_processes.is_empty() {
-        eprintln!("Error: No processes found matching '{}'", name);
-        if let Some(min_mb) = min_memory_mb {
-            eprintln!("(with minimum memory filter: {} MB)", min_mb);
-        }
-        std::process::exit(1);
+    if tree_nodes.is_empty() {
+        exit_no_match(query);
     }
 
-    // ツリーノードに変換
-    let tree_nodes = create_tree_nodes(&matching_processes);
+    print_system_information(sys);
+    print_process_tree(query, &tree_nodes, show_smaps);
+}
 
+/// 抽出済みのノードをツリー表示
+pub fn print_process_tree(query: &ProcessQuery, tree_nodes: &[ProcessTreeNode], show_smaps: bool) {
     // ツリー構築
-    let flattened_tree = build_process_tree(&tree_nodes, sort_order);
+    let flattened_tree = build_process_tree(tree_nodes, query.sort_order, query.memory_metric);
 
     // 統計情報の計算
-    let total_count = tree_nodes.len();
-    let total_memory: u64 = tree_nodes.iter().map(|n| n.memory_bytes).sum();
-    let total_cpu: f32 = tree_nodes.iter().map(|n| n.cpu_usage).sum();
-    let total_threads: usize = tree_nodes.iter().map(|n| n.thread_count).sum();
-
-    // メモリの統計値（Min/Avg/Max）
-    let (min_memory, avg_memory, max_memory) = if total_count > 0 {
-        let memories: Vec<u64> = tree_nodes.iter().map(|n| n.memory_bytes).collect();
-        let min = *memories.iter().min().unwrap_or(&0);
-        let max = *memories.iter().max().unwrap_or(&0);
-        let avg = total_memory / total_count as u64;
-        (min, avg, max)
-    } else {
-        (0, 0, 0)
-    };
+    let summary = ProcessSummary::from_nodes(tree_nodes, query.memory_metric);
 
-    // ===== ヘッダー表示（システム情報追加） =====
-    println!("=== System Information ===");
-    println!("{}", format_system_memory(sys));
-    println!("{}", format_system_swap(sys));
-    println!();
-
-    println!("=== Process Information (Tree View) ===");
-    print!("Processes matching '{}'", name);
-    if let Some(min_mb) = min_memory_mb {
-        print!(" (>= {} MB)", min_mb);
-    }
-    println!(" (sorted by {:?}):", sort_order);
-
-    println!("Total: {} process(es) ({} threads)", total_count, total_threads);
-    println!("Memory: {} (Min: {}, Avg: {}, Max: {})",
-             format_bytes(total_memory),
-             format_bytes(min_memory),
-             format_bytes(avg_memory),
-             format_bytes(max_memory));
-    println!("CPU: {:.2}%\n", total_cpu);
+    print_summary_header(query, &summary, "Process Information (Tree View)");
 
     // 表のヘッダー
-    println!("{:<8} {:<35} {:<8} {:<8} {:<12} {:<15}",
-             "PID", "Name", "Threads", "CPU %", "Memory", "Status");
-    println!("{}", "-".repeat(92));
+    print!("{:<8} {:<35} {:<8} {:<8} {:<12} {:<15}",
+           "PID", "Name", "Threads", "CPU %", "Memory", "Status");
+    if show_smaps {
+        print!(" {:<12} {:<12} {:<12} {:<12}", "PSS", "USS", "Shared", "Swap");
+    }
+    println!();
+    println!("{}", "-".repeat(if show_smaps { 144 } else { 92 }));
 
     // ツリー表示
     let mut prefix_stack: Vec<bool> = Vec::new();
@@ -222,64 +336,43 @@ pub fn show_processes_by_name_tree(sys: &System, name: &str, sort_order: &SortOr
         let max_name_len = 30usize.saturating_sub(node.depth * 3);
         let name_with_prefix = format!("{}{}", prefix, truncate_string(&node.process_name, max_name_len));
 
-        println!("{:<8} {:<35} {:<8} {:<8.2} {:<12} {:<15}",
-                 node.pid,
-                 name_with_prefix,
-                 node.thread_count,
-                 node.cpu_usage,
-                 format_bytes(node.memory_bytes),
-                 format_status(node.status));
+        print!("{:<8} {:<35} {:<8} {:<8.2} {:<12} {:<15}",
+               node.pid,
+               name_with_prefix,
+               node.thread_count,
+               node.cpu_usage,
+               format_bytes(node.memory_bytes),
+               format_status(node.status));
+        if show_smaps {
+            print!(" {}", format_smaps_columns(node.smaps.as_ref()));
+        }
+        println!();
     }
 }
 
-/// プロセス情報のリストからスナップショットを生成
-///
-/// TGID でグループ化された後のユニークなプロセスのみを記録する
-pub fn create_snapshots(
-    sys: &System,
-    name: &str,
-    min_memory_mb: Option<u64>
-) -> Vec<ProcessSnapshot> {
-    let min_memory_bytes = min_memory_mb.map(|mb| mb * 1024 * 1024);
+/// PID を指定してスナップショットを生成
+pub fn snapshot_by_pid(sys: &System, target_pid: u32) -> Option<ProcessSnapshot> {
+    let process = sys.process(Pid::from_u32(target_pid))?;
+    snapshots_from_nodes(&[create_tree_node(process, true)]).pop()
+}
+
+/// 抽出済みのノードから現在時刻のスナップショットを生成
+pub fn snapshots_from_nodes(nodes: &[ProcessTreeNode]) -> Vec<ProcessSnapshot> {
     let timestamp = Local::now();
 
-    // 既存のフィルタリング処理を再利用
-    let matching_processes: Vec<_> = sys.processes()
+    nodes
         .iter()
-        .filter(|(_, p)| {
-            let matches_name = p.name().to_string_lossy().contains(name);
-            let meets_min_memory = if let Some(min_bytes) = min_memory_bytes {
-                p.memory() >= min_bytes
-            } else {
-                true
-            };
-            matches_name && meets_min_memory
-        })
-        .collect();
-
-    // TGID でグループ化（既存の処理と同じ）
-    let mut seen_pids = HashSet::new();
-    let mut snapshots = Vec::new();
-
-    for (_, process) in matching_processes {
-        let lwp = process.pid().as_u32();
-        let tgid = get_tgid(lwp);
-
-        if seen_pids.contains(&tgid) {
-            continue;
-        }
-        seen_pids.insert(tgid);
-
-        snapshots.push(ProcessSnapshot {
+        .map(|node| ProcessSnapshot {
             timestamp,
-            process_name: process.name().to_string_lossy().to_string(),
-            pid: tgid,
-            cpu_usage: process.cpu_usage(),
-            memory_bytes: process.memory(),
-            thread_count: get_thread_count(tgid),
-            status: process.status(),
-        });
-    }
-
-    snapshots
-}
\ No newline at end of file
+            process_name: node.process_name.clone(),
+            pid: node.pid,
+            parent_pid: node.parent_pid,
+            cpu_usage: node.cpu_usage,
+            memory_bytes: node.memory_bytes,
+            smaps: node.smaps,
+            thread_count: node.thread_count,
+            status: node.status,
+            host: None,
+        })
+        .collect()
+}
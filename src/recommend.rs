@@ -0,0 +1,574 @@
+use crate::analyze::AnalysisResult;
+use crate::formatter;
+use crate::history::ProcessSnapshot;
+use serde::Serialize;
+use std::collections::HashSet;
+use sysinfo::System;
+
+/// 推奨値を算出する対象のサーバー
+#[derive(Clone, Debug, clap::ValueEnum)]
+pub enum RecommendTarget {
+    /// PHP-FPM (pm = dynamic)
+    PhpFpm,
+    /// Apache httpd (prefork / event)
+    Apache,
+}
+
+/// Apache の MPM
+#[derive(Clone, Copy, Debug, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ApacheMpm {
+    Prefork,
+    Event,
+}
+
+/// 推奨値算出のオプション
+#[derive(Clone, Debug)]
+pub struct RecommendOptions {
+    pub target: RecommendTarget,
+    pub reserve_percent: f64,
+    pub mpm: ApacheMpm,
+    pub threads_per_child: u64,
+}
+
+/// ホストのメモリ情報
+#[derive(Serialize, Clone, Copy)]
+pub struct HostMemory {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+impl HostMemory {
+    /// 現在のホストからメモリ情報を取得
+    pub fn current() -> Self {
+        let mut sys = System::new();
+        sys.refresh_memory();
+        Self {
+            total_bytes: sys.total_memory(),
+            available_bytes: sys.available_memory(),
+        }
+    }
+}
+
+/// 推奨値の算出根拠
+#[derive(Serialize)]
+pub struct RecommendationBasis {
+    pub total_memory_bytes: u64,
+    pub available_memory_bytes: u64,
+    pub reserve_percent: f64,
+    pub reserve_bytes: u64,
+    pub budget_bytes: u64,
+    pub worker_memory_bytes: u64,
+    pub worker_avg_bytes: f64,
+}
+
+/// PHP-FPM の推奨設定値
+#[derive(Serialize)]
+pub struct PhpFpmRecommendation {
+    pub pm_max_children: u64,
+    pub pm_start_servers: u64,
+    pub pm_min_spare_servers: u64,
+    pub pm_max_spare_servers: u64,
+}
+
+/// Apache の推奨設定値
+#[derive(Serialize)]
+pub struct ApacheRecommendation {
+    pub mpm: ApacheMpm,
+    pub max_request_workers: u64,
+    pub server_limit: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads_per_child: Option<u64>,
+    pub max_connections_per_child: u64,
+}
+
+/// 推奨結果（対象ごと）
+#[derive(Serialize)]
+#[serde(tag = "target", rename_all = "kebab-case")]
+pub enum Recommendation {
+    PhpFpm {
+        basis: RecommendationBasis,
+        settings: PhpFpmRecommendation,
+        excluded_parent_pids: Vec<u32>,
+        reasoning: Vec<String>,
+    },
+    Apache {
+        basis: RecommendationBasis,
+        settings: ApacheRecommendation,
+        excluded_parent_pids: Vec<u32>,
+        reasoning: Vec<String>,
+    },
+}
+
+/// 記録されたスナップショットとホストメモリから推奨値を算出
+pub fn recommend(
+    snapshots: &[ProcessSnapshot],
+    host: HostMemory,
+    options: &RecommendOptions,
+) -> Result<Recommendation, Box<dyn std::error::Error>> {
+    if !(0.0..100.0).contains(&options.reserve_percent) {
+        return Err(format!(
+            "Invalid reserve percent: {}. Expected 0 <= value < 100",
+            options.reserve_percent
+        )
+        .into());
+    }
+
+    match options.target {
+        RecommendTarget::PhpFpm => recommend_php_fpm(snapshots, host, options.reserve_percent),
+        RecommendTarget::Apache => recommend_apache(snapshots, host, options),
+    }
+}
+
+/// ワーカー用に使えるメモリ量を計算
+fn build_basis(
+    host: HostMemory,
+    reserve_percent: f64,
+    worker_memory_bytes: u64,
+    worker_avg_bytes: f64,
+) -> RecommendationBasis {
+    let reserve_bytes = (host.total_bytes as f64 * reserve_percent / 100.0) as u64;
+    RecommendationBasis {
+        total_memory_bytes: host.total_bytes,
+        available_memory_bytes: host.available_bytes,
+        reserve_percent,
+        reserve_bytes,
+        budget_bytes: host.total_bytes.saturating_sub(reserve_bytes),
+        worker_memory_bytes,
+        worker_avg_bytes,
+    }
+}
+
+/// PHP-FPM (pm = dynamic) の推奨値を算出
+///
+/// マスタープロセスを除いたワーカーの p95 メモリを1ワーカーあたりのサイズとして使用する
+fn recommend_php_fpm(
+    snapshots: &[ProcessSnapshot],
+    host: HostMemory,
+    reserve_percent: f64,
+) -> Result<Recommendation, Box<dyn std::error::Error>> {
+    let (workers, excluded_parent_pids) = split_workers(snapshots);
+    if workers.is_empty() {
+        return Err("No worker processes found after excluding parent processes".into());
+    }
+
+    let worker_analysis = AnalysisResult::from_snapshots(&workers)?;
+    let worker_memory = worker_analysis.memory_stats.p95_bytes;
+    if worker_memory == 0 {
+        return Err("Recorded worker memory is zero; cannot derive a recommendation".into());
+    }
+
+    let basis = build_basis(
+        host,
+        reserve_percent,
+        worker_memory,
+        worker_analysis.memory_stats.avg_bytes,
+    );
+    let settings = php_fpm_settings(basis.budget_bytes, worker_memory);
+
+    let mut reasoning = vec![
+        excluded_parents_reason(&excluded_parent_pids),
+        format!(
+            "Reserve {:.0}% of {} for OS and other services: {}",
+            reserve_percent,
+            formatter::format_bytes(basis.total_memory_bytes),
+            formatter::format_bytes(basis.reserve_bytes)
+        ),
+        format!(
+            "Memory budget for PHP-FPM workers: {} - {} = {}",
+            formatter::format_bytes(basis.total_memory_bytes),
+            formatter::format_bytes(basis.reserve_bytes),
+            formatter::format_bytes(basis.budget_bytes)
+        ),
+        format!(
+            "Per-worker memory (p95 of {} worker records): {} (avg: {}, max: {})",
+            workers.len(),
+            formatter::format_bytes(worker_memory),
+            formatter::format_bytes(basis.worker_avg_bytes as u64),
+            formatter::format_bytes(worker_analysis.memory_stats.max_bytes)
+        ),
+        format!(
+            "pm.max_children = {} / {} = {}",
+            formatter::format_bytes(basis.budget_bytes),
+            formatter::format_bytes(worker_memory),
+            settings.pm_max_children
+        ),
+        format!(
+            "pm.min_spare_servers = 10% of max_children = {}, pm.max_spare_servers = 30% of max_children = {}",
+            settings.pm_min_spare_servers, settings.pm_max_spare_servers
+        ),
+        format!(
+            "pm.start_servers = min_spare + (max_spare - min_spare) / 2 = {}",
+            settings.pm_start_servers
+        ),
+    ];
+
+    let pool_peak = worker_memory * settings.pm_max_children;
+    if pool_peak > basis.available_memory_bytes {
+        reasoning.push(format!(
+            "Warning: a full pool ({}) exceeds currently available memory ({}); other processes may need to shrink first",
+            formatter::format_bytes(pool_peak),
+            formatter::format_bytes(basis.available_memory_bytes)
+        ));
+    }
+
+    Ok(Recommendation::PhpFpm {
+        basis,
+        settings,
+        excluded_parent_pids,
+        reasoning,
+    })
+}
+
+/// メモリ予算とワーカーサイズから pm.* の値を計算
+fn php_fpm_settings(budget_bytes: u64, worker_memory_bytes: u64) -> PhpFpmRecommendation {
+    let max_children = (budget_bytes / worker_memory_bytes).max(1);
+    let min_spare = (max_children / 10).max(1);
+    let max_spare = (max_children * 3 / 10).max(min_spare);
+    let start_servers = min_spare + (max_spare - min_spare) / 2;
+
+    PhpFpmRecommendation {
+        pm_max_children: max_children,
+        pm_start_servers: start_servers,
+        pm_min_spare_servers: min_spare,
+        pm_max_spare_servers: max_spare,
+    }
+}
+
+/// 親プロセス（他のスナップショットの親PIDになっているプロセス）を除外
+///
+/// 戻り値: (ワーカーのスナップショット, 除外した親PID)
+fn split_workers(snapshots: &[ProcessSnapshot]) -> (Vec<ProcessSnapshot>, Vec<u32>) {
+    let pids: HashSet<u32> = snapshots.iter().map(|s| s.pid).collect();
+    let parent_pids: HashSet<u32> = snapshots
+        .iter()
+        .filter_map(|s| s.parent_pid)
+        .filter(|ppid| pids.contains(ppid))
+        .collect();
+
+    let workers = snapshots
+        .iter()
+        .filter(|s| !parent_pids.contains(&s.pid))
+        .cloned()
+        .collect();
+
+    let mut excluded: Vec<u32> = parent_pids.into_iter().collect();
+    excluded.sort();
+    (workers, excluded)
+}
+
+/// 除外した親プロセスの説明
+fn excluded_parents_reason(excluded_parent_pids: &[u32]) -> String {
+    if excluded_parent_pids.is_empty() {
+        "No parent process identified (records without parent PID); all processes treated as workers".to_string()
+    } else {
+        format!(
+            "Excluded parent process(es) from worker stats: PID {:?}",
+            excluded_parent_pids
+        )
+    }
+}
+
+/// Apache httpd の推奨値を算出
+///
+/// 親プロセスを除いたワーカーの p95 メモリを1プロセスあたりのサイズとして使用する
+fn recommend_apache(
+    snapshots: &[ProcessSnapshot],
+    host: HostMemory,
+    options: &RecommendOptions,
+) -> Result<Recommendation, Box<dyn std::error::Error>> {
+    let (workers, excluded_parent_pids) = split_workers(snapshots);
+    if workers.is_empty() {
+        return Err("No worker processes found after excluding parent processes".into());
+    }
+
+    let worker_analysis = AnalysisResult::from_snapshots(&workers)?;
+    let p50 = worker_analysis.memory_stats.p50_bytes;
+    let p95 = worker_analysis.memory_stats.p95_bytes;
+    if p95 == 0 {
+        return Err("Recorded worker memory is zero; cannot derive a recommendation".into());
+    }
+
+    let basis = build_basis(
+        host,
+        options.reserve_percent,
+        p95,
+        worker_analysis.memory_stats.avg_bytes,
+    );
+    let settings = apache_settings(
+        basis.budget_bytes,
+        p95,
+        worker_analysis.memory_stats.max_bytes,
+        p50,
+        options.mpm,
+        options.threads_per_child,
+    );
+
+    let mut reasoning = vec![excluded_parents_reason(&excluded_parent_pids)];
+    reasoning.push(format!(
+        "Reserve {:.0}% of {} for OS and other services: {}",
+        options.reserve_percent,
+        formatter::format_bytes(basis.total_memory_bytes),
+        formatter::format_bytes(basis.reserve_bytes)
+    ));
+    reasoning.push(format!(
+        "Memory budget for httpd workers: {} - {} = {}",
+        formatter::format_bytes(basis.total_memory_bytes),
+        formatter::format_bytes(basis.reserve_bytes),
+        formatter::format_bytes(basis.budget_bytes)
+    ));
+    reasoning.push(format!(
+        "Per-worker memory (p95 of {} worker records): {} (p50: {}, max: {})",
+        workers.len(),
+        formatter::format_bytes(p95),
+        formatter::format_bytes(p50),
+        formatter::format_bytes(worker_analysis.memory_stats.max_bytes)
+    ));
+    match options.mpm {
+        ApacheMpm::Prefork => {
+            reasoning.push(format!(
+                "MaxRequestWorkers = {} / {} = {} (prefork: one request per process)",
+                formatter::format_bytes(basis.budget_bytes),
+                formatter::format_bytes(p95),
+                settings.max_request_workers
+            ));
+            reasoning.push("ServerLimit = MaxRequestWorkers (prefork)".to_string());
+        }
+        ApacheMpm::Event => {
+            reasoning.push(format!(
+                "ServerLimit = {} / {} = {} (event: memory is per child process)",
+                formatter::format_bytes(basis.budget_bytes),
+                formatter::format_bytes(p95),
+                settings.server_limit
+            ));
+            reasoning.push(format!(
+                "MaxRequestWorkers = ServerLimit x ThreadsPerChild = {} x {} = {}",
+                settings.server_limit, options.threads_per_child, settings.max_request_workers
+            ));
+        }
+    }
+    reasoning.push(format!(
+        "MaxConnectionsPerChild = {} (max/p50 worker memory ratio: {:.2}; 0 = never recycle)",
+        settings.max_connections_per_child,
+        worker_analysis.memory_stats.max_bytes as f64 / p50.max(1) as f64
+    ));
+
+    Ok(Recommendation::Apache {
+        basis,
+        settings,
+        excluded_parent_pids,
+        reasoning,
+    })
+}
+
+/// メモリ予算とワーカーサイズから Apache の設定値を計算
+///
+/// MaxConnectionsPerChild はワーカーの肥大化（max / p50）が大きいほど小さくして再生成を促す
+fn apache_settings(
+    budget_bytes: u64,
+    worker_p95_bytes: u64,
+    worker_max_bytes: u64,
+    worker_p50_bytes: u64,
+    mpm: ApacheMpm,
+    threads_per_child: u64,
+) -> ApacheRecommendation {
+    let processes = (budget_bytes / worker_p95_bytes).max(1);
+
+    let growth = worker_max_bytes as f64 / worker_p50_bytes.max(1) as f64;
+    let max_connections_per_child = if growth >= 1.5 {
+        1000
+    } else if growth >= 1.2 {
+        5000
+    } else {
+        0
+    };
+
+    match mpm {
+        ApacheMpm::Prefork => ApacheRecommendation {
+            mpm,
+            max_request_workers: processes,
+            server_limit: processes,
+            threads_per_child: None,
+            max_connections_per_child,
+        },
+        ApacheMpm::Event => ApacheRecommendation {
+            mpm,
+            max_request_workers: processes * threads_per_child,
+            server_limit: processes,
+            threads_per_child: Some(threads_per_child),
+            max_connections_per_child,
+        },
+    }
+}
+
+/// テーブル形式で推奨値を出力
+pub fn print_table(recommendation: &Recommendation) {
+    match recommendation {
+        Recommendation::PhpFpm {
+            basis,
+            settings,
+            reasoning,
+            ..
+        } => {
+            println!("\nPHP-FPM Recommendation (pm = dynamic):");
+            print_basis(basis);
+            println!("\n  Suggested settings:");
+            println!("    pm.max_children      = {}", settings.pm_max_children);
+            println!("    pm.start_servers     = {}", settings.pm_start_servers);
+            println!("    pm.min_spare_servers = {}", settings.pm_min_spare_servers);
+            println!("    pm.max_spare_servers = {}", settings.pm_max_spare_servers);
+            print_reasoning(reasoning);
+        }
+        Recommendation::Apache {
+            basis,
+            settings,
+            reasoning,
+            ..
+        } => {
+            println!("\nApache Recommendation (MPM: {:?}):", settings.mpm);
+            print_basis(basis);
+            println!("\n  Suggested settings:");
+            println!("    ServerLimit            {}", settings.server_limit);
+            if let Some(threads) = settings.threads_per_child {
+                println!("    ThreadsPerChild        {}", threads);
+            }
+            println!("    MaxRequestWorkers      {}", settings.max_request_workers);
+            println!("    MaxConnectionsPerChild {}", settings.max_connections_per_child);
+            print_reasoning(reasoning);
+        }
+    }
+    println!("{}", "=".repeat(70));
+}
+
+/// 算出根拠を出力
+fn print_basis(basis: &RecommendationBasis) {
+    println!(
+        "  Host Memory:   {} total, {} available",
+        formatter::format_bytes(basis.total_memory_bytes),
+        formatter::format_bytes(basis.available_memory_bytes)
+    );
+    println!(
+        "  Reserve:       {} ({:.0}%)",
+        formatter::format_bytes(basis.reserve_bytes),
+        basis.reserve_percent
+    );
+    println!(
+        "  Budget:        {}",
+        formatter::format_bytes(basis.budget_bytes)
+    );
+    println!(
+        "  Worker Memory: {}",
+        formatter::format_bytes(basis.worker_memory_bytes)
+    );
+}
+
+/// 算出過程を出力
+fn print_reasoning(reasoning: &[String]) {
+    println!("\n  Reasoning:");
+    for line in reasoning {
+        println!("    - {}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_php_fpm_settings() {
+        // 800MB / 40MB = 20 children
+        let settings = php_fpm_settings(800 * MB, 40 * MB);
+        assert_eq!(settings.pm_max_children, 20);
+        assert_eq!(settings.pm_min_spare_servers, 2);
+        assert_eq!(settings.pm_max_spare_servers, 6);
+        assert_eq!(settings.pm_start_servers, 4);
+    }
+
+    #[test]
+    fn test_php_fpm_settings_small_budget() {
+        // 予算がワーカー1つ分未満でも最低1を返す
+        let settings = php_fpm_settings(10 * MB, 40 * MB);
+        assert_eq!(settings.pm_max_children, 1);
+        assert_eq!(settings.pm_min_spare_servers, 1);
+        assert_eq!(settings.pm_max_spare_servers, 1);
+        assert_eq!(settings.pm_start_servers, 1);
+    }
+
+    #[test]
+    fn test_build_basis_reserve() {
+        let host = HostMemory {
+            total_bytes: 1000 * MB,
+            available_bytes: 500 * MB,
+        };
+        let basis = build_basis(host, 20.0, 10 * MB, 8.0 * MB as f64);
+        assert_eq!(basis.reserve_bytes, 200 * MB);
+        assert_eq!(basis.budget_bytes, 800 * MB);
+    }
+
+    fn snapshot(pid: u32, parent_pid: Option<u32>, memory_bytes: u64) -> ProcessSnapshot {
+        ProcessSnapshot {
+            timestamp: chrono::Local::now(),
+            process_name: "httpd".to_string(),
+            pid,
+            parent_pid,
+            cpu_usage: 0.0,
+            memory_bytes,
+            smaps: None,
+            thread_count: 1,
+            status: sysinfo::ProcessStatus::Sleep,
+            host: None,
+        }
+    }
+
+    #[test]
+    fn test_split_workers_excludes_parent() {
+        let snapshots = vec![
+            snapshot(100, Some(1), 4 * MB),
+            snapshot(101, Some(100), 12 * MB),
+            snapshot(102, Some(100), 13 * MB),
+        ];
+        let (workers, excluded) = split_workers(&snapshots);
+        assert_eq!(excluded, vec![100]);
+        assert_eq!(workers.len(), 2);
+        assert!(workers.iter().all(|s| s.pid != 100));
+    }
+
+    #[test]
+    fn test_php_fpm_excludes_master_and_uses_p95() {
+        // マスター（200MB）と、20件中1件だけ 100MB に膨らんだワーカー
+        let mut snapshots = vec![snapshot(100, Some(1), 200 * MB)];
+        snapshots.extend((0..20).map(|i| snapshot(101 + i % 4, Some(100), if i == 0 { 100 } else { 40 } * MB)));
+        let host = HostMemory {
+            total_bytes: 1000 * MB,
+            available_bytes: 1000 * MB,
+        };
+
+        let Recommendation::PhpFpm {
+            basis,
+            settings,
+            excluded_parent_pids,
+            ..
+        } = recommend_php_fpm(&snapshots, host, 20.0).unwrap()
+        else {
+            panic!("expected a PHP-FPM recommendation");
+        };
+        assert_eq!(excluded_parent_pids, vec![100]);
+        assert_eq!(basis.worker_memory_bytes, 40 * MB);
+        assert_eq!(settings.pm_max_children, 20);
+    }
+
+    #[test]
+    fn test_apache_settings_prefork_and_event() {
+        let prefork = apache_settings(600 * MB, 12 * MB, 12 * MB, 11 * MB, ApacheMpm::Prefork, 25);
+        assert_eq!(prefork.max_request_workers, 50);
+        assert_eq!(prefork.server_limit, 50);
+        assert_eq!(prefork.max_connections_per_child, 0);
+
+        let event = apache_settings(600 * MB, 60 * MB, 100 * MB, 50 * MB, ApacheMpm::Event, 25);
+        assert_eq!(event.server_limit, 10);
+        assert_eq!(event.max_request_workers, 250);
+        assert_eq!(event.max_connections_per_child, 1000);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::export::csv_field;
+use crate::process::{exit_no_match, find_matching_nodes, sort_nodes, MemoryMetric, ProcessQuery, ProcessSummary, SortOrder, SystemMemory};
+use crate::tree::{build_process_tree, create_tree_node, ProcessTreeNode};
+use chrono::Local;
+use serde::Serialize;
+use sysinfo::{Pid, System};
+
+/// 1回だけ表示するモードの出力形式
+#[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
+pub enum SingleShotFormat {
+    Table, // 固定幅の表（従来どおり）
+    Json,  // システムメモリ・集計値・プロセス一覧を1つのオブジェクトで
+    Csv,   // プロセスごとに1行
+    Tsv,   // プロセスごとに1行（タブ区切り）
+}
+
+/// 抽出条件（JSON 出力用）
+#[derive(Serialize)]
+struct ReportQuery<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    min_memory_mb: Option<u64>,
+    memory_metric: &'a MemoryMetric,
+    sort: &'a SortOrder,
+    tree: bool,
+}
+
+/// 1プロセス分の行
+///
+/// depth はツリー表示時のみ（ルートが 0）
+#[derive(Debug, PartialEq, Serialize)]
+struct ProcessRow {
+    pid: u32,
+    parent_pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<usize>,
+    process_name: String,
+    cpu_usage: f32,
+    memory_bytes: u64,
+    thread_count: usize,
+    status: String,
+    pss_bytes: Option<u64>,
+    uss_bytes: Option<u64>,
+    shared_clean_bytes: Option<u64>,
+    shared_dirty_bytes: Option<u64>,
+    swap_bytes: Option<u64>,
+}
+
+/// CSV / TSV のヘッダ（ProcessRow のフィールド順）
+const COLUMNS: [&str; 13] = [
+    "pid",
+    "parent_pid",
+    "depth",
+    "process_name",
+    "cpu_usage",
+    "memory_bytes",
+    "thread_count",
+    "status",
+    "pss_bytes",
+    "uss_bytes",
+    "shared_clean_bytes",
+    "shared_dirty_bytes",
+    "swap_bytes",
+];
+
+impl ProcessRow {
+    fn new(node: &ProcessTreeNode, tree: bool) -> Self {
+        Self {
+            pid: node.pid,
+            parent_pid: node.parent_pid,
+            depth: tree.then_some(node.depth),
+            process_name: node.process_name.clone(),
+            cpu_usage: node.cpu_usage,
+            memory_bytes: node.memory_bytes,
+            thread_count: node.thread_count,
+            status: format!("{:?}", node.status),
+            pss_bytes: node.smaps.map(|m| m.pss),
+            uss_bytes: node.smaps.map(|m| m.uss),
+            shared_clean_bytes: node.smaps.map(|m| m.shared_clean),
+            shared_dirty_bytes: node.smaps.map(|m| m.shared_dirty),
+            swap_bytes: node.smaps.map(|m| m.swap),
+        }
+    }
+
+    /// COLUMNS の順の値（空欄は None）。文字列の値は escape で整形する
+    fn fields(&self, escape: fn(&str) -> String) -> Vec<String> {
+        let optional = |v: Option<u64>| v.map_or(String::new(), |v| v.to_string());
+        vec![
+            self.pid.to_string(),
+            self.parent_pid.map_or(String::new(), |p| p.to_string()),
+            self.depth.map_or(String::new(), |d| d.to_string()),
+            escape(&self.process_name),
+            self.cpu_usage.to_string(),
+            self.memory_bytes.to_string(),
+            self.thread_count.to_string(),
+            escape(&self.status),
+            optional(self.pss_bytes),
+            optional(self.uss_bytes),
+            optional(self.shared_clean_bytes),
+            optional(self.shared_dirty_bytes),
+            optional(self.swap_bytes),
+        ]
+    }
+}
+
+/// --format json の出力全体
+#[derive(Serialize)]
+struct SingleShotReport<'a> {
+    timestamp: String,
+    system_memory: SystemMemory,
+    query: ReportQuery<'a>,
+    summary: ProcessSummary,
+    processes: Vec<ProcessRow>,
+}
+
+/// TSV の値（タブ・改行は空白に置き換える）
+fn tsv_field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+/// 1回だけ表示するモードの機械可読な出力の引数
+pub struct ReportOptions<'a> {
+    pub query: Option<&'a ProcessQuery<'a>>,
+    pub pid: Option<u32>,
+    pub tree: bool,
+    pub format: &'a SingleShotFormat,
+}
+
+/// プロセス名または PID で抽出した結果を JSON / CSV / TSV で出力
+///
+/// 一致するプロセスがない場合は表示時と同じくエラーで終了する
+pub fn print_report(sys: &System, options: ReportOptions) -> serde_json::Result<()> {
+    let (nodes, memory_metric, sort_order) = match options.query {
+        Some(query) => {
+            let mut nodes = find_matching_nodes(sys, query);
+            if nodes.is_empty() {
+                exit_no_match(query);
+            }
+            if options.tree {
+                nodes = build_process_tree(&nodes, query.sort_order, query.memory_metric);
+            } else {
+                sort_nodes(&mut nodes, query.sort_order, query.memory_metric);
+            }
+            (nodes, query.memory_metric, query.sort_order)
+        }
+        None => {
+            let target_pid = options.pid.unwrap_or_else(std::process::id);
+            match sys.process(Pid::from_u32(target_pid)) {
+                Some(process) => (vec![create_tree_node(process, true)], &MemoryMetric::Rss, &SortOrder::Memory),
+                None => {
+                    eprintln!("Error: Process not found (PID: {})", target_pid);
+                    std::process::exit(1);
+                }
+            }
+        }
+    };
+
+    // PID 指定時は1プロセスなのでツリーの深さは付けない
+    let tree = options.tree && options.query.is_some();
+    let rows: Vec<ProcessRow> = nodes.iter().map(|node| ProcessRow::new(node, tree)).collect();
+
+    match options.format {
+        SingleShotFormat::Json => {
+            let report = SingleShotReport {
+                timestamp: Local::now().to_rfc3339(),
+                system_memory: SystemMemory::current(sys),
+                query: ReportQuery {
+                    name: options.query.map(|q| q.matcher.to_string()),
+                    pid: options.query.is_none().then(|| options.pid.unwrap_or_else(std::process::id)),
+                    min_memory_mb: options.query.and_then(|q| q.min_memory_mb),
+                    memory_metric,
+                    sort: sort_order,
+                    tree,
+                },
+                summary: ProcessSummary::from_nodes(&nodes, memory_metric),
+                processes: rows,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        SingleShotFormat::Csv => print_delimited(&rows, ",", csv_field),
+        SingleShotFormat::Tsv => print_delimited(&rows, "\t", tsv_field),
+        SingleShotFormat::Table => unreachable!("table is printed by show_processes_by_*"),
+    }
+    Ok(())
+}
+
+/// ヘッダ付きで区切り文字の表を出力
+fn print_delimited(rows: &[ProcessRow], separator: &str, escape: fn(&str) -> String) {
+    println!("{}", COLUMNS.join(separator));
+    for row in rows {
+        println!("{}", row.fields(escape).join(separator));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::SmapsMemory;
+    use sysinfo::ProcessStatus;
+
+    fn node(depth: usize) -> ProcessTreeNode {
+        ProcessTreeNode {
+            pid: 1235,
+            parent_pid: Some(1234),
+            process_name: "php-fpm, pool\twww".to_string(),
+            cpu_usage: 0.5,
+            memory_bytes: 52428800,
+            smaps: Some(SmapsMemory {
+                pss: 31457280,
+                uss: 26214400,
+                shared_clean: 20971520,
+                shared_dirty: 0,
+                swap: 0,
+            }),
+            thread_count: 1,
+            status: ProcessStatus::Sleep,
+            depth,
+            is_last_child: true,
+        }
+    }
+
+    #[test]
+    fn test_process_row_fields() {
+        let row = ProcessRow::new(&node(1), true);
+        assert_eq!(row.fields(csv_field).len(), COLUMNS.len());
+        assert_eq!(
+            row.fields(csv_field).join(","),
+            "1235,1234,1,\"php-fpm, pool\twww\",0.5,52428800,1,Sleep,31457280,26214400,20971520,0,0"
+        );
+        assert_eq!(row.fields(tsv_field)[3], "php-fpm, pool www");
+
+        // ツリー表示でなければ depth は空欄・JSON では省略
+        let flat = ProcessRow::new(&node(1), false);
+        assert_eq!(flat.fields(csv_field)[2], "");
+        let json = serde_json::to_value(&flat).unwrap();
+        assert!(json.get("depth").is_none());
+        assert_eq!(json["parent_pid"], 1234);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::analyze::{format_timestamp, open_history};
+use crate::formatter;
+use crate::history::{ProcessHistory, PruneCounts};
+use chrono::{Duration, Local};
+use std::time::Instant;
+
+/// 1回の DELETE で削除する最大行数
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// watch/TUI で古い記録を削除する間隔
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// 保持期間を過ぎた記録の cutoff（記録と同じ形式の文字列）
+fn cutoff(retention_secs: i64) -> String {
+    format_timestamp(&(Local::now() - Duration::seconds(retention_secs)))
+}
+
+/// watch/TUI 用: 保持期間を過ぎた記録を定期的に削除する
+pub struct RetentionPruner {
+    retention_secs: i64,
+    last_run: Option<Instant>,
+}
+
+impl RetentionPruner {
+    pub fn new(retention_secs: i64) -> Self {
+        Self {
+            retention_secs,
+            last_run: None,
+        }
+    }
+
+    /// 起動直後と、その後は PRUNE_INTERVAL ごとに削除を実行
+    pub fn run_if_due(&mut self, history: &ProcessHistory) -> rusqlite::Result<Option<PruneCounts>> {
+        if self.last_run.is_some_and(|t| t.elapsed() < PRUNE_INTERVAL) {
+            return Ok(None);
+        }
+        self.last_run = Some(Instant::now());
+
+        history
+            .prune_before(&cutoff(self.retention_secs), DEFAULT_BATCH_SIZE)
+            .map(Some)
+    }
+}
+
+/// history prune の引数
+pub struct PruneOptions<'a> {
+    pub db_path: &'a str,
+    pub older_than_secs: i64,
+    pub batch_size: usize,
+    pub vacuum: bool,
+}
+
+/// history prune のエントリーポイント
+pub fn run_prune(options: PruneOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let history = open_history(options.db_path)?;
+    let cutoff = cutoff(options.older_than_secs);
+
+    let (size_before, _) = history.storage_bytes()?;
+    let counts = history
+        .prune_before(&cutoff, options.batch_size)
+        .map_err(|e| format!("Failed to delete records: {}", e))?;
+
+    println!("Cutoff: {}", cutoff);
+    println!(
+        "Removed: {} snapshot(s), {} event(s), {} system snapshot(s), {} session(s), {} alert(s)",
+        counts.snapshots, counts.events, counts.system_snapshots, counts.sessions, counts.alerts
+    );
+
+    if options.vacuum {
+        history
+            .vacuum()
+            .map_err(|e| format!("VACUUM failed: {}", e))?;
+        let (size_after, _) = history.storage_bytes()?;
+        println!(
+            "Database size: {} -> {} (reclaimed {})",
+            formatter::format_bytes(size_before),
+            formatter::format_bytes(size_after),
+            formatter::format_bytes(size_before.saturating_sub(size_after))
+        );
+    } else {
+        let (size_after, free) = history.storage_bytes()?;
+        println!(
+            "Database size: {} ({} free for reuse; run with --vacuum to shrink the file)",
+            formatter::format_bytes(size_after),
+            formatter::format_bytes(free)
+        );
+    }
+
+    Ok(())
+}
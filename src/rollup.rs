@@ -0,0 +1,31 @@
+use crate::analyze::open_history;
+use chrono::Local;
+
+/// history rollup の引数
+pub struct RollupOptions<'a> {
+    pub db_path: &'a str,
+    pub rebuild: bool,
+}
+
+/// history rollup のエントリーポイント
+///
+/// --rebuild でも生データが削除済みの期間の集計は残る
+pub fn run_rollup(options: RollupOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let history = open_history(options.db_path)?;
+    let now = Local::now().timestamp();
+
+    let counts = if options.rebuild {
+        history
+            .first_snapshot_epoch()
+            .and_then(|first| history.rebuild_rollups(first.unwrap_or(now), now, now))
+    } else {
+        history.update_rollups(now)
+    }
+    .map_err(|e| format!("Failed to update rollups: {}", e))?;
+
+    println!(
+        "Rollups updated: {} per-minute row(s), {} per-hour row(s)",
+        counts.minute, counts.hour
+    );
+    Ok(())
+}
@@ -0,0 +1,548 @@
+use crate::process::{MemoryMetric, SystemMemory, find_matching_nodes};
+use crate::targets::Target;
+use crate::tree::ProcessTreeNode;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{ProcessesToUpdate, System};
+
+/// メトリクス名の接頭辞
+const PREFIX: &str = "process_monitor";
+
+/// Prometheus テキスト形式の Content-Type
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// リクエスト（リクエスト行とヘッダ全体）の読み込みを待つ最大時間
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// レスポンスの書き込みを待つ最大時間
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// リクエスト行とヘッダの最大サイズ
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+/// 同時に処理する接続の上限（超えた接続は閉じる）
+const MAX_CONNECTIONS: usize = 16;
+
+/// serve サブコマンドの引数
+pub struct ServeOptions<'a> {
+    pub listen: &'a str,
+    pub targets: &'a [Target],
+    pub interval_secs: Option<u64>,
+}
+
+/// serve のエントリーポイント（Ctrl+C まで待ち受ける）
+///
+/// interval_secs を指定した場合は一定間隔で更新した結果を返し、
+/// 指定しない場合はスクレイプのたびに System を更新する
+pub fn run_serve(options: ServeOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(options.listen)
+        .map_err(|e| format!("Failed to listen on {}: {}", options.listen, e))?;
+    println!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+
+    let targets = options.targets;
+    let sys = Mutex::new(System::new_all());
+    // 一定間隔で更新する場合の最新の出力
+    let cached = Mutex::new(None::<String>);
+    // 処理中の接続数
+    let active = AtomicUsize::new(0);
+    let (sys, cached, active) = (&sys, &cached, &active);
+
+    thread::scope(|scope| {
+        if let Some(interval_secs) = options.interval_secs {
+            scope.spawn(move || {
+                loop {
+                    let body = {
+                        let mut sys = sys.lock().unwrap();
+                        refresh(&mut sys);
+                        collect_metrics(&sys, targets)
+                    };
+                    *cached.lock().unwrap() = Some(body);
+                    thread::sleep(Duration::from_secs(interval_secs));
+                }
+            });
+        }
+
+        // 接続ごとにスレッドで処理する（遅いクライアントが他のスクレイプを待たせない）
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Warning: Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                eprintln!(
+                    "Warning: Too many connections, closing {}",
+                    describe_peer(&stream)
+                );
+                continue;
+            }
+
+            scope.spawn(move || {
+                let metrics = || match cached.lock().unwrap().clone() {
+                    Some(body) => body,
+                    None => {
+                        let mut sys = sys.lock().unwrap();
+                        refresh(&mut sys);
+                        collect_metrics(&sys, targets)
+                    }
+                };
+                if let Err(e) = handle_connection(stream, metrics) {
+                    eprintln!("Warning: Failed to handle request: {}", e);
+                }
+                active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+    Ok(())
+}
+
+/// プロセス・メモリ・CPU 使用率を更新
+fn refresh(sys: &mut System) {
+    sys.refresh_processes(ProcessesToUpdate::All, true);
+    sys.refresh_memory();
+    sys.refresh_cpu_usage();
+}
+
+/// すべての監視対象を抽出してメトリクスを出力
+fn collect_metrics(sys: &System, targets: &[Target]) -> String {
+    let results: Vec<(&Target, Vec<ProcessTreeNode>)> = targets
+        .iter()
+        .map(|target| (target, find_matching_nodes(sys, &target.query())))
+        .collect();
+    render_metrics(&results, &SystemMemory::current(sys))
+}
+
+/// 接続元のアドレス（警告の表示用）
+fn describe_peer(stream: &TcpStream) -> String {
+    stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|_| "unknown peer".to_string())
+}
+
+/// 期限までに1行を読み込む（期限を過ぎた場合は TimedOut）
+fn read_line_until(
+    reader: &mut BufReader<std::io::Take<&TcpStream>>,
+    line: &mut String,
+    deadline: Instant,
+) -> std::io::Result<usize> {
+    let remaining = deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(|| std::io::Error::new(ErrorKind::TimedOut, "request header timed out"))?;
+    reader
+        .get_ref()
+        .get_ref()
+        .set_read_timeout(Some(remaining))?;
+    reader.read_line(line)
+}
+
+/// 1つの接続を処理（GET /metrics のみ対応）
+///
+/// リクエスト行とヘッダは READ_TIMEOUT 以内・MAX_REQUEST_BYTES 以下まで読み込む
+fn handle_connection(
+    mut stream: TcpStream,
+    metrics: impl FnOnce() -> String,
+) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let deadline = Instant::now() + READ_TIMEOUT;
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_BYTES));
+
+    let mut request_line = String::new();
+    read_line_until(&mut reader, &mut request_line, deadline)?;
+    // ヘッダは読み捨てる
+    let mut header = String::new();
+    while read_line_until(&mut reader, &mut header, deadline)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", CONTENT_TYPE, metrics()),
+        (Some("GET"), Some("/")) => (
+            "200 OK",
+            "text/plain; charset=utf-8",
+            "rs-process-monitor exporter. Metrics are at /metrics\n".to_string(),
+        ),
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "Not Found\n".to_string(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            "Method Not Allowed\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// ラベル値のエスケープ（\ " 改行）
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// HELP / TYPE 行と値の行を追加
+fn write_metric(out: &mut String, name: &str, help: &str, samples: &[(String, f64)]) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} gauge", PREFIX, name);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}_{}{{{}}} {}", PREFIX, name, labels, value);
+    }
+}
+
+/// プロセス名ごとの集計値
+#[derive(Default)]
+struct NameTotals {
+    processes: usize,
+    memory_bytes: u64,
+    cpu_usage: f32,
+    threads: usize,
+}
+
+/// 監視対象ごとに抽出したプロセスとシステムメモリを Prometheus テキスト形式に変換
+///
+/// 同じプロセスが複数の監視対象に一致しても系列が重ならないよう、target ラベルを付ける
+fn render_metrics(results: &[(&Target, Vec<ProcessTreeNode>)], memory: &SystemMemory) -> String {
+    let mut out = String::new();
+    let results: Vec<(&Target, Vec<&ProcessTreeNode>)> = results
+        .iter()
+        .map(|(target, nodes)| {
+            let mut nodes: Vec<&ProcessTreeNode> = nodes.iter().collect();
+            nodes.sort_by_key(|node| node.pid);
+            (*target, nodes)
+        })
+        .collect();
+
+    let target_label = |target: &Target| format!("target=\"{}\"", escape_label(&target.label));
+    let process_labels = |target: &Target, node: &ProcessTreeNode| {
+        format!(
+            "{},pid=\"{}\",name=\"{}\"",
+            target_label(target),
+            node.pid,
+            escape_label(&node.process_name)
+        )
+    };
+    let per_process = |value: fn(&ProcessTreeNode) -> Option<f64>| -> Vec<(String, f64)> {
+        results
+            .iter()
+            .flat_map(|(target, nodes)| {
+                nodes
+                    .iter()
+                    .filter_map(move |node| value(node).map(|v| (process_labels(target, node), v)))
+            })
+            .collect()
+    };
+
+    // プロセスごと
+    write_metric(
+        &mut out,
+        "process_resident_memory_bytes",
+        "Resident set size of the process.",
+        &per_process(|n| Some(n.memory_bytes as f64)),
+    );
+    write_metric(
+        &mut out,
+        "process_pss_bytes",
+        "Proportional set size of the process (Linux smaps_rollup).",
+        &per_process(|n| n.smaps.map(|m| m.pss as f64)),
+    );
+    write_metric(
+        &mut out,
+        "process_uss_bytes",
+        "Unique set size of the process (Linux smaps_rollup).",
+        &per_process(|n| n.smaps.map(|m| m.uss as f64)),
+    );
+    write_metric(
+        &mut out,
+        "process_swap_bytes",
+        "Swapped-out memory of the process (Linux smaps_rollup).",
+        &per_process(|n| n.smaps.map(|m| m.swap as f64)),
+    );
+    write_metric(
+        &mut out,
+        "process_cpu_usage_percent",
+        "CPU usage of the process since the previous refresh.",
+        &per_process(|n| Some(n.cpu_usage as f64)),
+    );
+    write_metric(
+        &mut out,
+        "process_threads",
+        "Number of threads of the process.",
+        &per_process(|n| Some(n.thread_count as f64)),
+    );
+    let statuses: Vec<(String, f64)> = results
+        .iter()
+        .flat_map(|(target, nodes)| {
+            nodes.iter().map(move |node| {
+                (
+                    format!(
+                        "{},status=\"{:?}\"",
+                        process_labels(target, node),
+                        node.status
+                    ),
+                    1.0,
+                )
+            })
+        })
+        .collect();
+    write_metric(
+        &mut out,
+        "process_status",
+        "Status of the process (always 1, see the status label).",
+        &statuses,
+    );
+
+    // 監視対象・プロセス名ごと（メモリ指標は監視対象ごとの設定）
+    let mut totals: BTreeMap<(&str, &str), (NameTotals, &MemoryMetric)> = BTreeMap::new();
+    for (target, nodes) in &results {
+        for node in nodes {
+            let (total, _) = totals
+                .entry((target.label.as_str(), node.process_name.as_str()))
+                .or_insert_with(|| (NameTotals::default(), &target.memory_metric));
+            total.processes += 1;
+            total.memory_bytes += node.memory_by(&target.memory_metric);
+            total.cpu_usage += node.cpu_usage;
+            total.threads += node.thread_count;
+        }
+    }
+    let name_labels = |(target, name): &(&str, &str)| {
+        format!(
+            "target=\"{}\",name=\"{}\"",
+            escape_label(target),
+            escape_label(name)
+        )
+    };
+    let per_name = |value: fn(&NameTotals) -> f64| -> Vec<(String, f64)> {
+        totals
+            .iter()
+            .map(|(key, (total, _))| (name_labels(key), value(total)))
+            .collect()
+    };
+    write_metric(
+        &mut out,
+        "name_processes",
+        "Number of matching processes per process name.",
+        &per_name(|t| t.processes as f64),
+    );
+    let memory_samples: Vec<(String, f64)> = totals
+        .iter()
+        .map(|(key, (total, metric))| {
+            (
+                format!("{},metric=\"{}\"", name_labels(key), metric_label(metric)),
+                total.memory_bytes as f64,
+            )
+        })
+        .collect();
+    write_metric(
+        &mut out,
+        "name_memory_bytes",
+        "Total memory per process name (see the metric label).",
+        &memory_samples,
+    );
+    write_metric(
+        &mut out,
+        "name_cpu_usage_percent",
+        "Total CPU usage per process name.",
+        &per_name(|t| t.cpu_usage as f64),
+    );
+    write_metric(
+        &mut out,
+        "name_threads",
+        "Total number of threads per process name.",
+        &per_name(|t| t.threads as f64),
+    );
+
+    // 監視対象全体（一致するプロセスがない場合も 0 を出力）
+    let matched: Vec<(String, f64)> = results
+        .iter()
+        .map(|(target, nodes)| {
+            (
+                format!(
+                    "{},query=\"{}\"",
+                    target_label(target),
+                    escape_label(&target.matcher.to_string())
+                ),
+                nodes.len() as f64,
+            )
+        })
+        .collect();
+    write_metric(
+        &mut out,
+        "matched_processes",
+        "Number of processes matching each target.",
+        &matched,
+    );
+
+    // システム全体
+    let system = [
+        (
+            "system_memory_total_bytes",
+            "Total memory of the host.",
+            memory.total_memory_bytes,
+        ),
+        (
+            "system_memory_used_bytes",
+            "Used memory of the host.",
+            memory.used_memory_bytes,
+        ),
+        (
+            "system_memory_available_bytes",
+            "Available memory of the host.",
+            memory.available_memory_bytes,
+        ),
+        (
+            "system_swap_total_bytes",
+            "Total swap of the host.",
+            memory.total_swap_bytes,
+        ),
+        (
+            "system_swap_used_bytes",
+            "Used swap of the host.",
+            memory.used_swap_bytes,
+        ),
+    ];
+    for (name, help, value) in system {
+        let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+        let _ = writeln!(out, "# TYPE {}_{} gauge", PREFIX, name);
+        let _ = writeln!(out, "{}_{} {}", PREFIX, name, value);
+    }
+
+    out
+}
+
+/// metric ラベルの値（--memory-metric の指定値）
+fn metric_label(metric: &MemoryMetric) -> &'static str {
+    match metric {
+        MemoryMetric::Rss => "rss",
+        MemoryMetric::Pss => "pss",
+        MemoryMetric::Uss => "uss",
+        MemoryMetric::Swap => "swap",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{MatchMode, NameMatcher};
+    use crate::process::SortOrder;
+    use crate::targets::Target;
+    use sysinfo::ProcessStatus;
+
+    fn node(pid: u32, name: &str, memory_bytes: u64) -> ProcessTreeNode {
+        ProcessTreeNode {
+            pid,
+            parent_pid: Some(1),
+            process_name: name.to_string(),
+            cpu_usage: 1.5,
+            memory_bytes,
+            smaps: None,
+            thread_count: 2,
+            status: ProcessStatus::Sleep,
+            depth: 0,
+            is_last_child: false,
+        }
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let target = |label: &str, memory_metric| Target {
+            label: label.to_string(),
+            matcher: NameMatcher::new(&["php".to_string()], &[], MatchMode::Substring, false)
+                .unwrap(),
+            min_memory_mb: None,
+            memory_metric,
+            sort_order: SortOrder::Memory,
+            alerts: vec![],
+            log: None,
+            on_alert: None,
+            smaps: true,
+        };
+        let (php, php_uss) = (
+            target("php", MemoryMetric::Rss),
+            target("php-uss", MemoryMetric::Uss),
+        );
+        let memory = SystemMemory {
+            total_memory_bytes: 8000,
+            used_memory_bytes: 6000,
+            available_memory_bytes: 2000,
+            total_swap_bytes: 0,
+            used_swap_bytes: 0,
+        };
+        let nodes = [
+            node(20, "php-fpm", 300),
+            node(10, "php-fpm", 100),
+            node(30, "php\"cli", 50),
+        ];
+        let text = render_metrics(&[(&php, nodes.to_vec()), (&php_uss, vec![])], &memory);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"process_monitor_process_resident_memory_bytes{target=\"php\",pid=\"10\",name=\"php-fpm\"} 100"));
+        assert!(lines.contains(&"process_monitor_process_status{target=\"php\",pid=\"30\",name=\"php\\\"cli\",status=\"Sleep\"} 1"));
+        assert!(
+            lines.contains(&"process_monitor_name_processes{target=\"php\",name=\"php-fpm\"} 2")
+        );
+        assert!(lines.contains(
+            &"process_monitor_name_memory_bytes{target=\"php\",name=\"php-fpm\",metric=\"rss\"} 400"
+        ));
+        assert!(lines.contains(
+            &"process_monitor_name_cpu_usage_percent{target=\"php\",name=\"php-fpm\"} 3"
+        ));
+        assert!(
+            lines.contains(&"process_monitor_matched_processes{target=\"php\",query=\"php\"} 3")
+        );
+        // 一致するプロセスがない監視対象も 0 を出力
+        assert!(
+            lines
+                .contains(&"process_monitor_matched_processes{target=\"php-uss\",query=\"php\"} 0")
+        );
+        assert!(lines.contains(&"process_monitor_system_memory_available_bytes 2000"));
+        // smaps が取得できないプロセスは PSS の行を出力しない
+        assert!(!text.contains("process_monitor_process_pss_bytes{"));
+        assert!(lines.contains(&"# TYPE process_monitor_process_pss_bytes gauge"));
+    }
+
+    #[test]
+    fn test_read_line_until_stops_at_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new((&server).take(MAX_REQUEST_BYTES));
+        let mut line = String::new();
+
+        client.write_all(b"GET /metrics HTTP/1.1\r\n").unwrap();
+        let deadline = Instant::now() + READ_TIMEOUT;
+        read_line_until(&mut reader, &mut line, deadline).unwrap();
+        assert_eq!(line, "GET /metrics HTTP/1.1\r\n");
+
+        // ヘッダを少しずつ送り続けるクライアントも、期限を過ぎたら打ち切る
+        client.write_all(b"Host: a\r\n").unwrap();
+        let error = read_line_until(&mut reader, &mut line, Instant::now()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+    }
+}
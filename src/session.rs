@@ -0,0 +1,116 @@
+use crate::analyze::{OutputFormat, open_history};
+use crate::formatter;
+use crate::history::Session;
+use chrono::DateTime;
+
+/// history sessions の引数
+pub struct SessionsOptions<'a> {
+    pub db_path: &'a str,
+    pub format: &'a OutputFormat,
+}
+
+/// history sessions のエントリーポイント
+pub fn run_sessions(options: SessionsOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let history = open_history(options.db_path)?;
+    let sessions = history
+        .query_sessions()
+        .map_err(|e| format!("Database query failed: {}", e))?;
+
+    match options.format {
+        OutputFormat::Table => print_table(&sessions),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&sessions)?),
+        OutputFormat::Csv => unreachable!("sessions accepts only table or json"),
+    }
+    Ok(())
+}
+
+/// セッションの記録期間（秒）。記録がなければ None
+fn duration_secs(session: &Session) -> Option<i64> {
+    let started = DateTime::parse_from_rfc3339(&session.started_at).ok()?;
+    let ended = DateTime::parse_from_rfc3339(session.ended_at.as_deref()?).ok()?;
+    Some((ended - started).num_seconds())
+}
+
+/// 1行の説明（analyze --session の見出し用）
+pub fn describe(session: &Session) -> String {
+    let mut parts = vec![format!("#{}", session.id)];
+    if let Some(ref hostname) = session.hostname {
+        parts.push(format!("host {}", hostname));
+    }
+    if let Some(ref name) = session.name_filter {
+        parts.push(format!("--name {}", name));
+    }
+    if let Some(mb) = session.min_memory_mb {
+        parts.push(format!("--min-memory-mb {}", mb));
+    }
+    parts.push(format!("every {}s", session.interval_secs));
+    parts.push(format!("v{}", session.tool_version));
+    format!(
+        "{} ({} - {})",
+        parts.join(", "),
+        session.started_at,
+        session.ended_at.as_deref().unwrap_or("no records")
+    )
+}
+
+/// テーブル形式でセッション一覧を出力
+fn print_table(sessions: &[Session]) {
+    if sessions.is_empty() {
+        println!("No sessions recorded. Sessions are recorded by --watch/--tui with --log");
+        return;
+    }
+
+    println!(
+        "{:>5} {:<19} {:>12} {:<16} {:<16} {:>8} {:>8} {:>9} {:<8}",
+        "ID", "Started", "Duration", "Host", "Name Filter", "Min MB", "Interval", "Snapshots", "Version"
+    );
+    println!("{}", "-".repeat(111));
+
+    for session in sessions {
+        println!(
+            "{:>5} {:<19} {:>12} {:<16} {:<16} {:>8} {:>7}s {:>9} {:<8}",
+            session.id,
+            DateTime::parse_from_rfc3339(&session.started_at)
+                .map_or(session.started_at.clone(), |t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+            duration_secs(session).map_or("-".to_string(), formatter::format_duration),
+            formatter::truncate_string(session.hostname.as_deref().unwrap_or("-"), 16),
+            formatter::truncate_string(session.name_filter.as_deref().unwrap_or("-"), 16),
+            session.min_memory_mb.map_or("-".to_string(), |mb| mb.to_string()),
+            session.interval_secs,
+            session.snapshots,
+            session.tool_version
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(ended_at: Option<&str>) -> Session {
+        Session {
+            id: 3,
+            started_at: "2026-01-05T14:00:00+09:00".to_string(),
+            ended_at: ended_at.map(str::to_string),
+            hostname: Some("web01".to_string()),
+            name_filter: Some("php-fpm".to_string()),
+            min_memory_mb: None,
+            interval_secs: 5,
+            tool_version: "0.5.1".to_string(),
+            snapshots: 10,
+        }
+    }
+
+    #[test]
+    fn test_describe_and_duration() {
+        let finished = session(Some("2026-01-05T15:30:00+09:00"));
+        assert_eq!(duration_secs(&finished), Some(5400));
+        assert_eq!(
+            describe(&finished),
+            "#3, host web01, --name php-fpm, every 5s, v0.5.1 \
+             (2026-01-05T14:00:00+09:00 - 2026-01-05T15:30:00+09:00)"
+        );
+
+        assert_eq!(duration_secs(&session(None)), None);
+    }
+}
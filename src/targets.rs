@@ -0,0 +1,251 @@
+use crate::alert::{self, AlertEvaluator, AlertRule};
+use crate::history::{AlertEvent, NewSession, ProcessHistory, SystemSnapshot};
+use crate::lifecycle::LifecycleTracker;
+use crate::matcher::NameMatcher;
+use crate::process::{find_matching_nodes, snapshots_from_nodes, sort_nodes, MemoryMetric, ProcessQuery, SortOrder};
+use crate::retention::RetentionPruner;
+use crate::tree::ProcessTreeNode;
+use sysinfo::System;
+
+/// 監視対象（--name で指定した1つ、または設定ファイルの [[target]]）
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub label: String, // 表示名（設定ファイルで省略した場合は name）
+    pub matcher: NameMatcher,
+    pub min_memory_mb: Option<u64>,
+    pub memory_metric: MemoryMetric,
+    pub sort_order: SortOrder,
+    pub alerts: Vec<AlertRule>,
+    pub log: Option<String>,
+    pub on_alert: Option<String>,
+    pub smaps: bool, // PSS などを表示・出力するか（--smaps、--output jsonl、serve）
+}
+
+impl Target {
+    /// 抽出条件
+    pub fn query(&self) -> ProcessQuery<'_> {
+        ProcessQuery {
+            matcher: &self.matcher,
+            sort_order: &self.sort_order,
+            min_memory_mb: self.min_memory_mb,
+            memory_metric: &self.memory_metric,
+            // 記録する場合は smaps 由来の列も保存する
+            collect_smaps: self.smaps || self.log.is_some() || self.memory_metric.needs_smaps(),
+        }
+    }
+}
+
+/// 監視対象ごとの実行時の状態
+struct TargetState {
+    target: Target,
+    nodes: Vec<ProcessTreeNode>, // 直近の抽出結果（ソート済み）
+    tracker: LifecycleTracker,
+    evaluator: AlertEvaluator,
+    recorder: Option<usize>, // recorders の添字
+}
+
+/// 記録先のデータベース（同じファイルに記録する監視対象で共有）
+struct Recorder {
+    path: String,
+    history: ProcessHistory,
+    pruner: Option<RetentionPruner>,
+}
+
+/// TargetSet の設定
+pub struct TargetSetOptions {
+    pub interval_secs: u64,
+    pub retention_secs: Option<i64>,
+    /// --on-alert のコマンドの出力を端末に出すか（TUI では false）
+    pub hook_output: bool,
+}
+
+/// 1回の更新の結果
+#[derive(Default)]
+pub struct UpdateResult {
+    pub alerts: Vec<AlertEvent>,
+    /// 記録・コマンド実行の失敗（表示方法は呼び出し側で決める）
+    pub warnings: Vec<String>,
+}
+
+/// 複数の監視対象を1回の System の更新でまとめて抽出・記録する
+pub struct TargetSet {
+    states: Vec<TargetState>,
+    recorders: Vec<Recorder>,
+    hook_output: bool,
+}
+
+impl TargetSet {
+    /// 記録先のデータベースを開き、セッションを開始する
+    ///
+    /// 開けなかったデータベースには記録せず、警告として返す
+    pub fn new(targets: Vec<Target>, options: &TargetSetOptions) -> (Self, Vec<String>) {
+        let mut recorders: Vec<Recorder> = vec![];
+        let mut warnings = vec![];
+        let mut failed: Vec<String> = vec![];
+
+        let states = targets
+            .into_iter()
+            .map(|target| {
+                let recorder = match target.log {
+                    Some(ref path) if !failed.contains(path) => {
+                        match recorders.iter().position(|r| &r.path == path) {
+                            Some(index) => Some(index),
+                            None => match ProcessHistory::new(path) {
+                                Ok(history) => {
+                                    recorders.push(Recorder {
+                                        path: path.clone(),
+                                        history,
+                                        pruner: options.retention_secs.map(RetentionPruner::new),
+                                    });
+                                    Some(recorders.len() - 1)
+                                }
+                                Err(e) => {
+                                    warnings.push(format!("Failed to initialize history database {}: {}", path, e));
+                                    failed.push(path.clone());
+                                    None
+                                }
+                            },
+                        }
+                    }
+                    _ => None,
+                };
+                TargetState {
+                    evaluator: AlertEvaluator::new(&target.alerts, &target.matcher.to_string()),
+                    nodes: vec![],
+                    tracker: LifecycleTracker::new(),
+                    recorder,
+                    target,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // セッションはデータベースごとに1つ（フィルタは記録する監視対象の名前）
+        for (index, recorder) in recorders.iter_mut().enumerate() {
+            let targets: Vec<&Target> = states
+                .iter()
+                .filter(|s| s.recorder == Some(index))
+                .map(|s| &s.target)
+                .collect();
+            let names = targets.iter().map(|t| t.matcher.to_string()).collect::<Vec<_>>().join(", ");
+            let min_memory_mb = match targets.as_slice() {
+                [target] => target.min_memory_mb,
+                _ => None,
+            };
+            if let Err(e) = recorder.history.start_session(&NewSession {
+                name_filter: Some(&names),
+                min_memory_mb,
+                interval_secs: options.interval_secs,
+            }) {
+                warnings.push(format!("Failed to record session in {}: {}", recorder.path, e));
+            }
+        }
+
+        (
+            Self {
+                states,
+                recorders,
+                hook_output: options.hook_output,
+            },
+            warnings,
+        )
+    }
+
+    /// 記録先のデータベースのパス
+    pub fn log_paths(&self) -> impl Iterator<Item = &str> {
+        self.recorders.iter().map(|r| r.path.as_str())
+    }
+
+    pub fn is_logging(&self) -> bool {
+        !self.recorders.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// 監視対象と直近の抽出結果
+    pub fn results(&self) -> impl Iterator<Item = (&Target, &[ProcessTreeNode])> {
+        self.states.iter().map(|s| (&s.target, s.nodes.as_slice()))
+    }
+
+    /// 監視対象と直近の抽出結果（添字で指定）
+    pub fn result(&self, index: usize) -> (&Target, &[ProcessTreeNode]) {
+        let state = &self.states[index];
+        (&state.target, &state.nodes)
+    }
+
+    /// 監視対象のアラートの状態
+    pub fn evaluator(&self, index: usize) -> &AlertEvaluator {
+        &self.states[index].evaluator
+    }
+
+    /// すべての記録先のデータベースを閉じる（失敗は警告として返す）
+    pub fn close(self) -> Vec<String> {
+        self.recorders
+            .into_iter()
+            .filter_map(|recorder| {
+                let path = recorder.path;
+                recorder
+                    .history
+                    .close()
+                    .err()
+                    .map(|e| format!("Failed to close history database {}: {}", path, e))
+            })
+            .collect()
+    }
+
+    /// 更新済みの System からすべての監視対象を抽出し、アラートの評価と記録を行う
+    pub fn update(&mut self, sys: &System) -> UpdateResult {
+        let mut result = UpdateResult::default();
+
+        for state in &mut self.states {
+            let query = state.target.query();
+            let mut nodes = find_matching_nodes(sys, &query);
+            sort_nodes(&mut nodes, query.sort_order, query.memory_metric);
+            state.nodes = nodes;
+
+            let alerts = state.evaluator.evaluate(&state.nodes, &state.target.memory_metric);
+            if let Some(ref command) = state.target.on_alert {
+                for event in &alerts {
+                    if let Err(e) = alert::run_hook(command, event, self.hook_output) {
+                        result.warnings.push(format!("Failed to run on_alert command: {}", e));
+                    }
+                }
+            }
+
+            if let Some(index) = state.recorder {
+                let hist = &mut self.recorders[index].history;
+                if let Err(e) = hist.insert_snapshots(&snapshots_from_nodes(&state.nodes)) {
+                    result.warnings.push(format!("Failed to log snapshots: {}", e));
+                }
+                let events = state.tracker.update(sys, &state.target.matcher);
+                if let Err(e) = hist.insert_events(&events) {
+                    result.warnings.push(format!("Failed to log process events: {}", e));
+                }
+                if let Err(e) = hist.insert_alerts(&alerts) {
+                    result.warnings.push(format!("Failed to log alerts: {}", e));
+                }
+            }
+
+            result.alerts.extend(alerts);
+        }
+
+        // ホスト全体の記録と古い記録の削除はデータベースごとに1回
+        for recorder in &mut self.recorders {
+            if let Err(e) = recorder.history.insert_system_snapshot(&SystemSnapshot::capture(sys)) {
+                result.warnings.push(format!("Failed to log system snapshot: {}", e));
+            }
+            if let Some(ref mut pruner) = recorder.pruner
+                && let Err(e) = pruner.run_if_due(&recorder.history)
+            {
+                result.warnings.push(format!("Failed to prune old records: {}", e));
+            }
+        }
+
+        result
+    }
+}
@@ -1,5 +1,5 @@
-use crate::formatter::{get_tgid, get_thread_count};
-use crate::process::SortOrder;
+use crate::formatter::{SmapsMemory, get_smaps_memory, get_tgid, get_thread_count};
+use crate::process::{MemoryMetric, SortOrder};
 use std::collections::HashMap;
 use sysinfo::{Process, ProcessStatus};
 
@@ -11,12 +11,20 @@ pub struct ProcessTreeNode {
     pub process_name: String,
     pub cpu_usage: f32,
     pub memory_bytes: u64,
+    pub smaps: Option<SmapsMemory>,
     pub thread_count: usize,
     pub status: ProcessStatus,
     pub depth: usize,
     pub is_last_child: bool,
 }
 
+impl ProcessTreeNode {
+    /// 指定したメモリ指標の値を取得
+    pub fn memory_by(&self, metric: &MemoryMetric) -> u64 {
+        metric.select(self.memory_bytes, self.smaps.as_ref())
+    }
+}
+
 /// ツリー描画用の文字定数
 pub const TREE_BRANCH: &str = "├─ ";
 pub const TREE_LAST: &str = "└─ ";
@@ -24,7 +32,9 @@ pub const TREE_VERTICAL: &str = "│  ";
 pub const TREE_SPACE: &str = "   ";
 
 /// sysinfo::Process から ProcessTreeNode を作成
-pub fn create_tree_node(process: &Process) -> ProcessTreeNode {
+///
+/// `collect_smaps` が false の場合は smaps_rollup を読まない（`smaps` は None）
+pub fn create_tree_node(process: &Process, collect_smaps: bool) -> ProcessTreeNode {
     let lwp = process.pid().as_u32();
     let tgid = get_tgid(lwp);
 
@@ -40,6 +50,7 @@ pub fn create_tree_node(process: &Process) -> ProcessTreeNode {
         process_name: process.name().to_string_lossy().to_string(),
         cpu_usage: process.cpu_usage(),
         memory_bytes: process.memory(),
+        smaps: if collect_smaps { get_smaps_memory(tgid) } else { None },
         thread_count: get_thread_count(tgid),
         status: process.status(),
         depth: 0,
@@ -48,7 +59,7 @@ pub fn create_tree_node(process: &Process) -> ProcessTreeNode {
 }
 
 /// プロセスのリストから ProcessTreeNode のリストを作成（TGID でグループ化）
-pub fn create_tree_nodes(processes: &[(&sysinfo::Pid, &Process)]) -> Vec<ProcessTreeNode> {
+pub fn create_tree_nodes(processes: &[(&sysinfo::Pid, &Process)], collect_smaps: bool) -> Vec<ProcessTreeNode> {
     let mut tgid_to_process: HashMap<u32, &Process> = HashMap::new();
 
     for (_, process) in processes {
@@ -59,15 +70,15 @@ pub fn create_tree_nodes(processes: &[(&sysinfo::Pid, &Process)]) -> Vec<Process
         // これにより、正しい親PID情報を取得できる
         if lwp == tgid {
             tgid_to_process.insert(tgid, process);
-        } else if !tgid_to_process.contains_key(&tgid) {
+        } else {
             // まだエントリがなければ追加（メインスレッドが見つからない場合のフォールバック）
-            tgid_to_process.insert(tgid, process);
+            tgid_to_process.entry(tgid).or_insert(process);
         }
     }
 
     tgid_to_process
         .values()
-        .map(|process| create_tree_node(process))
+        .map(|process| create_tree_node(process, collect_smaps))
         .collect()
 }
 
@@ -75,6 +86,7 @@ pub fn create_tree_nodes(processes: &[(&sysinfo::Pid, &Process)]) -> Vec<Process
 pub fn build_process_tree(
     nodes: &[ProcessTreeNode],
     sort_order: &SortOrder,
+    memory_metric: &MemoryMetric,
 ) -> Vec<ProcessTreeNode> {
     if nodes.is_empty() {
         return Vec::new();
@@ -116,9 +128,9 @@ pub fn build_process_tree(
     }
 
     // Step 3: 兄弟間でソート
-    sort_siblings(&mut root_pids, &nodes_map, sort_order);
+    sort_siblings(&mut root_pids, &nodes_map, sort_order, memory_metric);
     for children in children_map.values_mut() {
-        sort_siblings(children, &nodes_map, sort_order);
+        sort_siblings(children, &nodes_map, sort_order, memory_metric);
     }
 
     // Step 4: 深さ優先探索でフラット化
@@ -143,15 +155,16 @@ pub fn build_process_tree(
 
 /// 兄弟プロセス間でソート
 fn sort_siblings(
-    pids: &mut Vec<u32>,
+    pids: &mut [u32],
     nodes_map: &HashMap<u32, ProcessTreeNode>,
     sort_order: &SortOrder,
+    memory_metric: &MemoryMetric,
 ) {
     match sort_order {
         SortOrder::Memory => {
             pids.sort_by(|a, b| {
-                let mem_a = nodes_map.get(a).map(|n| n.memory_bytes).unwrap_or(0);
-                let mem_b = nodes_map.get(b).map(|n| n.memory_bytes).unwrap_or(0);
+                let mem_a = nodes_map.get(a).map(|n| n.memory_by(memory_metric)).unwrap_or(0);
+                let mem_b = nodes_map.get(b).map(|n| n.memory_by(memory_metric)).unwrap_or(0);
                 mem_b.cmp(&mem_a) // 降順
             });
         }
@@ -268,4 +281,14 @@ mod tests {
         assert_eq!(generate_tree_prefix(2, true, &[true, false]), "│  └─ ");
         assert_eq!(generate_tree_prefix(2, false, &[false, true]), "   ├─ ");
     }
+
+    #[test]
+    fn test_create_tree_node_skips_smaps_unless_requested() {
+        let mut sys = sysinfo::System::new();
+        let pid = sysinfo::Pid::from_u32(std::process::id());
+        sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+        let process = sys.process(pid).expect("current process");
+
+        assert!(create_tree_node(process, false).smaps.is_none());
+    }
 }
@@ -1,11 +1,11 @@
 use crate::formatter::{
-    format_bytes, format_status, format_system_memory, format_system_swap, get_tgid,
-    get_thread_count, truncate_string,
+    format_bytes, format_optional_bytes, format_status, format_system_memory, format_system_swap,
+    truncate_string,
 };
 use crate::graph::GraphData;
-use crate::history::ProcessHistory;
-use crate::process::{SortOrder, create_snapshots};
-use crate::tree::{build_process_tree, create_tree_nodes, generate_tree_prefix};
+use crate::process::{ProcessQuery, ProcessSummary, snapshots_from_nodes};
+use crate::targets::{Target, TargetSet, TargetSetOptions};
+use crate::tree::{ProcessTreeNode, build_process_tree, generate_tree_prefix};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
@@ -24,31 +24,30 @@ use std::io;
 use std::time::{Duration, Instant};
 use sysinfo::{ProcessesToUpdate, System};
 
+/// TUIモードのオプション
+pub struct TuiOptions {
+    pub interval_secs: u64,
+    pub retention_secs: Option<i64>,
+    pub graph_points: usize,
+    pub tree_mode: bool,
+    pub show_smaps: bool,
+}
+
 pub struct TuiApp {
     should_quit: bool,
     last_update: Instant,
     update_interval: Duration,
-    history: Option<ProcessHistory>,
-    graph_data: Option<GraphData>,
+    targets: TargetSet,
+    selected: usize,                    // 表示中の監視対象（Tab で切り替え）
+    graph_data: Option<Vec<GraphData>>, // 監視対象ごと
     tree_mode: bool,
+    show_smaps: bool,
 }
 
 impl TuiApp {
-    pub fn new(interval_secs: u64, log_path: Option<&str>, graph_points: usize, tree_mode: bool) -> Self {
-        let history = if let Some(path) = log_path {
-            match ProcessHistory::new(path) {
-                Ok(h) => Some(h),
-                Err(e) => {
-                    eprintln!("Warning: Failed to initialize history database: {}", e);
-                    None
-                }
-            }
-        } else {
-            None
-        };
-
-        let graph_data = if graph_points > 0 {
-            Some(GraphData::new(graph_points))
+    pub fn new(targets: TargetSet, options: &TuiOptions) -> Self {
+        let graph_data = if options.graph_points > 0 {
+            Some((0..targets.len()).map(|_| GraphData::new(options.graph_points)).collect())
         } else {
             None
         };
@@ -56,11 +55,13 @@ impl TuiApp {
         Self {
             should_quit: false,
             // 起動直後に即座に更新されるように、過去の時刻で初期化
-            last_update: Instant::now() - Duration::from_secs(interval_secs),
-            update_interval: Duration::from_secs(interval_secs),
-            history,
+            last_update: Instant::now() - Duration::from_secs(options.interval_secs),
+            update_interval: Duration::from_secs(options.interval_secs),
+            targets,
+            selected: 0,
             graph_data,
-            tree_mode,
+            tree_mode: options.tree_mode,
+            show_smaps: options.show_smaps,
         }
     }
 
@@ -71,18 +72,34 @@ impl TuiApp {
     pub fn mark_updated(&mut self) {
         self.last_update = Instant::now();
     }
+
+    /// 表示する監視対象を切り替え（末尾の次は先頭）
+    fn select_next(&mut self, forward: bool) {
+        let len = self.targets.len();
+        self.selected = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+    }
 }
 
 /// TUIモードでプロセス監視を実行
-pub fn run_tui(
-    name: &str,
-    sort_order: &SortOrder,
-    interval_secs: u64,
-    min_memory_mb: Option<u64>,
-    log_path: Option<&str>,
-    graph_points: usize,
-    tree_mode: bool,
-) -> Result<(), io::Error> {
+pub fn run_tui(targets: Vec<Target>, options: &TuiOptions) -> Result<(), io::Error> {
+    // 記録先の初期化（警告は画面を切り替える前に表示）
+    let (targets, warnings) = TargetSet::new(
+        targets,
+        &TargetSetOptions {
+            interval_secs: options.interval_secs,
+            retention_secs: options.retention_secs,
+            // コマンドの出力は画面を壊すので捨てる
+            hook_output: false,
+        },
+    );
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
     // ターミナルの初期化
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -91,17 +108,10 @@ pub fn run_tui(
     let mut terminal = Terminal::new(backend)?;
 
     // アプリの実行
-    let mut app = TuiApp::new(interval_secs, log_path, graph_points, tree_mode);
+    let mut app = TuiApp::new(targets, options);
     let mut sys = System::new_all();
 
-    let res = run_app(
-        &mut terminal,
-        &mut app,
-        &mut sys,
-        name,
-        sort_order,
-        min_memory_mb,
-    );
+    let res = run_app(&mut terminal, &mut app, &mut sys);
 
     // ターミナルの復元
     disable_raw_mode()?;
@@ -123,47 +133,44 @@ fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut TuiApp,
     sys: &mut System,
-    name: &str,
-    sort_order: &SortOrder,
-    min_memory_mb: Option<u64>,
 ) -> Result<(), io::Error> {
     loop {
         // プロセス情報の更新
         if app.should_update() {
             sys.refresh_processes(ProcessesToUpdate::All, true);
+            sys.refresh_memory();
+            sys.refresh_cpu_usage();
 
-            // スナップショットを作成（履歴とグラフで共有）
-            let snapshots = create_snapshots(sys, name, min_memory_mb);
+            // 抽出・アラートの評価・履歴記録（描画は次の更新まで抽出結果を使う）
+            // TUI では eprintln! が画面を壊すので記録の失敗は無視
+            let _ = app.targets.update(sys);
 
             // グラフデータの更新
-            if let Some(ref mut graph) = app.graph_data {
-                graph.push_snapshot(&snapshots);
+            if let Some(ref mut graphs) = app.graph_data {
+                for (graph, (_, nodes)) in graphs.iter_mut().zip(app.targets.results()) {
+                    graph.push_snapshot(&snapshots_from_nodes(nodes));
+                }
             }
 
             app.mark_updated();
-
-            // 履歴記録
-            if let Some(ref mut hist) = app.history {
-                if let Err(_e) = hist.insert_snapshots(&snapshots) {
-                    // TUI では eprintln! が画面を壊すので無視
-                }
-            }
         }
 
         // 画面描画
         terminal.draw(|f| {
-            ui(f, app, sys, name, sort_order, min_memory_mb);
+            ui(f, app, sys);
         })?;
 
         // イベント処理（100msタイムアウト）
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
-                        app.should_quit = true;
-                    }
-                    _ => {}
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+        {
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    app.should_quit = true;
                 }
+                KeyCode::Tab => app.select_next(true),
+                KeyCode::BackTab => app.select_next(false),
+                _ => {}
             }
         }
 
@@ -175,27 +182,28 @@ fn run_app(
     Ok(())
 }
 
-fn ui(
-    f: &mut Frame,
-    app: &TuiApp,
-    sys: &System,
-    name: &str,
-    sort_order: &SortOrder,
-    min_memory_mb: Option<u64>,
-) {
+fn ui(f: &mut Frame, app: &TuiApp, sys: &System) {
+    let (target, nodes) = app.targets.result(app.selected);
+    let query = target.query();
+    let evaluator = app.targets.evaluator(app.selected);
+    let graph = app.graph_data.as_ref().map(|graphs| &graphs[app.selected]);
+
+    // ヘッダーの高さ（アラートのルールがあれば1行追加）
+    let header_height = if evaluator.is_empty() { 7 } else { 8 };
+
     // レイアウトの作成（グラフの有無で動的に変更）
-    let constraints = if app.graph_data.is_some() {
+    let constraints = if graph.is_some() {
         vec![
-            Constraint::Length(7), // ヘッダー
-            Constraint::Length(6), // グラフ（NEW）
-            Constraint::Min(10),   // プロセステーブル
-            Constraint::Length(3), // フッター
+            Constraint::Length(header_height), // ヘッダー
+            Constraint::Length(6),             // グラフ（NEW）
+            Constraint::Min(10),               // プロセステーブル
+            Constraint::Length(3),             // フッター
         ]
     } else {
         vec![
-            Constraint::Length(7), // ヘッダー
-            Constraint::Min(10),   // プロセステーブル
-            Constraint::Length(3), // フッター
+            Constraint::Length(header_height), // ヘッダー
+            Constraint::Min(10),               // プロセステーブル
+            Constraint::Length(3),             // フッター
         ]
     };
 
@@ -204,78 +212,25 @@ fn ui(
         .constraints(constraints)
         .split(f.area());
 
-    // プロセスの抽出とソート
-    let min_memory_bytes = min_memory_mb.map(|mb| mb * 1024 * 1024);
-
-    let mut matching_processes: Vec<_> = sys
-        .processes()
-        .iter()
-        .filter(|(_, p)| {
-            let matches_name = p.name().to_string_lossy().contains(name);
-            let meets_min_memory = if let Some(min_bytes) = min_memory_bytes {
-                p.memory() >= min_bytes
-            } else {
-                true
-            };
-            matches_name && meets_min_memory
-        })
-        .collect();
-
-    // ソート
-    match sort_order {
-        SortOrder::Memory => {
-            matching_processes.sort_by(|a, b| b.1.memory().cmp(&a.1.memory()));
-        }
-        SortOrder::Cpu => {
-            matching_processes.sort_by(|a, b| {
-                b.1.cpu_usage()
-                    .partial_cmp(&a.1.cpu_usage())
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
-        }
-        SortOrder::Pid => {
-            matching_processes.sort_by_key(|(_, p)| p.pid());
-        }
-        SortOrder::Name => {
-            matching_processes.sort_by(|a, b| {
-                a.1.name()
-                    .to_string_lossy()
-                    .cmp(&b.1.name().to_string_lossy())
-            });
-        }
-    }
-
-    // TGIDでグループ化されたノードを作成（統計計算に使用）
-    let tree_nodes = create_tree_nodes(&matching_processes);
-
-    // 統計情報（グループ化後のノードから計算）
-    let actual_process_count = tree_nodes.len();
-    let total_memory: u64 = tree_nodes.iter().map(|n| n.memory_bytes).sum();
-    let total_cpu: f32 = tree_nodes.iter().map(|n| n.cpu_usage).sum();
-    let total_threads: usize = tree_nodes.iter().map(|n| n.thread_count).sum();
-
-    // メモリの統計値（Min/Avg/Max）
-    let (min_memory, avg_memory, max_memory) = if actual_process_count > 0 {
-        let memories: Vec<u64> = tree_nodes.iter().map(|n| n.memory_bytes).collect();
-        let min = *memories.iter().min().unwrap_or(&0);
-        let max = *memories.iter().max().unwrap_or(&0);
-        let avg = total_memory / actual_process_count as u64;
-        (min, avg, max)
-    } else {
-        (0, 0, 0)
-    };
+    // 統計情報（更新時に抽出・ソート済みのノードから計算）
+    let summary = ProcessSummary::from_nodes(nodes, query.memory_metric);
 
     // ===== ヘッダー（システム情報追加） =====
-    let title = if let Some(min_mb) = min_memory_mb {
+    let title = header_title(&query);
+    // 複数の監視対象がある場合は表示中の対象を示す
+    let title = if app.targets.len() > 1 {
         format!(
-            "Process Monitor: '{}' (>= {} MB) | Sort: {:?}",
-            name, min_mb, sort_order
+            "[{}/{}] {} | {}",
+            app.selected + 1,
+            app.targets.len(),
+            target.label,
+            title
         )
     } else {
-        format!("Process Monitor: '{}' | Sort: {:?}", name, sort_order)
+        title
     };
 
-    let header_lines = vec![
+    let mut header_lines = vec![
         Line::from(vec![Span::styled(
             title,
             Style::default()
@@ -293,22 +248,42 @@ fn ui(
         Line::from(vec![Span::styled(
             format!(
                 "Processes: {} ({} threads) | CPU: {:.2}%",
-                actual_process_count, total_threads, total_cpu
+                summary.count, summary.total_threads, summary.total_cpu
             ),
             Style::default().fg(Color::White),
         )]),
         Line::from(vec![Span::styled(
             format!(
-                "Memory: {} (Min: {}, Avg: {}, Max: {})",
-                format_bytes(total_memory),
-                format_bytes(min_memory),
-                format_bytes(avg_memory),
-                format_bytes(max_memory)
+                "{}: {} (Min: {}, Avg: {}, Max: {})",
+                query.memory_metric.summary_title(),
+                format_bytes(summary.total_memory),
+                format_bytes(summary.min_memory),
+                format_bytes(summary.avg_memory),
+                format_bytes(summary.max_memory)
             ),
             Style::default().fg(Color::Green),
         )]),
     ];
 
+    // アラートの状態
+    if !evaluator.is_empty() {
+        let firing: Vec<String> = evaluator
+            .firing()
+            .map(|(rule, value)| format!("{} (value: {:.2})", rule, value))
+            .collect();
+        header_lines.push(if firing.is_empty() {
+            Line::from(vec![Span::styled(
+                format!("Alerts: OK ({} rule(s))", evaluator.len()),
+                Style::default().fg(Color::Green),
+            )])
+        } else {
+            Line::from(vec![Span::styled(
+                format!("Alerts: FIRING {}", firing.join(", ")),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )])
+        });
+    }
+
     let header = Paragraph::new(header_lines).block(
         Block::default()
             .borders(Borders::ALL)
@@ -317,7 +292,7 @@ fn ui(
     f.render_widget(header, chunks[0]);
 
     // グラフセクション（有効な場合）
-    let table_chunk_index = if let Some(ref graph) = app.graph_data {
+    let table_chunk_index = if let Some(graph) = graph {
         render_graphs(f, graph, chunks[1]);
         2 // テーブルは chunks[2] に移動
     } else {
@@ -325,96 +300,123 @@ fn ui(
     };
 
     // プロセステーブル
-    let header_cells = ["PID", "Name", "Threads", "CPU %", "Memory", "Status"]
-        .iter()
-        .map(|h| {
-            Cell::from(*h).style(
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )
-        });
+    let mut header_titles = vec!["PID", "Name", "Threads", "CPU %", "Memory", "Status"];
+    let mut widths = vec![
+        Constraint::Length(8),  // PID
+        Constraint::Length(20), // Name
+        Constraint::Length(8),  // Threads
+        Constraint::Length(10), // CPU %
+        Constraint::Length(12), // Memory
+        Constraint::Length(15), // Status
+    ];
+    if app.show_smaps {
+        header_titles.extend(["PSS", "USS", "Shared", "Swap"]);
+        widths.extend([Constraint::Length(12); 4]);
+    }
+
+    let header_cells = header_titles.iter().map(|h| {
+        Cell::from(*h).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    });
     let header_row = Row::new(header_cells).height(1).bottom_margin(1);
 
     // ツリーモードの場合
     let rows: Vec<Row> = if app.tree_mode {
-        // 統計計算で既に作成した tree_nodes を再利用
-        let flattened_tree = build_process_tree(&tree_nodes, sort_order);
+        let flattened_tree = build_process_tree(nodes, query.sort_order, query.memory_metric);
 
         let mut prefix_stack: Vec<bool> = Vec::new();
-        flattened_tree.iter().map(|node| {
-            // プレフィックス更新
-            while prefix_stack.len() > node.depth {
-                prefix_stack.pop();
-            }
-            if node.depth > 0 && prefix_stack.len() < node.depth {
-                prefix_stack.push(!node.is_last_child);
-            }
+        flattened_tree
+            .iter()
+            .map(|node| {
+                // プレフィックス更新
+                while prefix_stack.len() > node.depth {
+                    prefix_stack.pop();
+                }
+                if node.depth > 0 && prefix_stack.len() < node.depth {
+                    prefix_stack.push(!node.is_last_child);
+                }
 
-            let prefix = generate_tree_prefix(node.depth, node.is_last_child, &prefix_stack);
-            let max_name_len = 17usize.saturating_sub(node.depth * 3);
-            let name_display = format!("{}{}", prefix, truncate_string(&node.process_name, max_name_len));
-
-            let cells = vec![
-                Cell::from(format!("{}", node.pid)),
-                Cell::from(name_display),
-                Cell::from(format!("{}", node.thread_count)),
-                Cell::from(format!("{:.2}", node.cpu_usage)),
-                Cell::from(format_bytes(node.memory_bytes)),
-                Cell::from(format_status(node.status)),
-            ];
-            Row::new(cells).height(1)
-        }).collect()
+                let prefix = generate_tree_prefix(node.depth, node.is_last_child, &prefix_stack);
+                let max_name_len = 17usize.saturating_sub(node.depth * 3);
+                let name_display = format!(
+                    "{}{}",
+                    prefix,
+                    truncate_string(&node.process_name, max_name_len)
+                );
+
+                node_row(node, name_display, app.show_smaps)
+            })
+            .collect()
     } else {
-        // 通常モード: ユニークなPIDだけを表示
-        let mut seen_pids = std::collections::HashSet::new();
-        matching_processes.iter().filter_map(|(_, process)| {
-            let lwp = process.pid().as_u32();
-            let tgid = get_tgid(lwp);
-
-            // 既に表示したPIDはスキップ
-            if seen_pids.contains(&tgid) {
-                return None;
-            }
-            seen_pids.insert(tgid);
-
-            let thread_count = get_thread_count(tgid);
-            let cells = vec![
-                Cell::from(format!("{}", tgid)), // LWPではなくTGIDを表示
-                Cell::from(truncate_string(&process.name().to_string_lossy(), 20)),
-                Cell::from(format!("{}", thread_count)),
-                Cell::from(format!("{:.2}", process.cpu_usage())),
-                Cell::from(format_bytes(process.memory())),
-                Cell::from(format_status(process.status())),
-            ];
-            Some(Row::new(cells).height(1))
-        }).collect()
+        // 通常モード: ソート済みのユニークなPID（TGID）を表示
+        nodes
+            .iter()
+            .map(|node| node_row(node, truncate_string(&node.process_name, 20), app.show_smaps))
+            .collect()
     };
 
-    let table = Table::new(
-        rows,
-        [
-            Constraint::Length(8),  // PID
-            Constraint::Length(20), // Name
-            Constraint::Length(8),  // Threads
-            Constraint::Length(10), // CPU %
-            Constraint::Length(12), // Memory
-            Constraint::Length(15), // Status
-        ],
-    )
-    .header(header_row)
-    .block(Block::default().borders(Borders::ALL).title("Processes"))
-    .style(Style::default().fg(Color::White));
+    let table = Table::new(rows, widths)
+        .header(header_row)
+        .block(Block::default().borders(Borders::ALL).title("Processes"))
+        .style(Style::default().fg(Color::White));
 
     f.render_widget(table, chunks[table_chunk_index]);
 
     // フッター
-    let footer = Paragraph::new("Press 'q' or 'Esc' to quit")
+    let help = if app.targets.len() > 1 {
+        "Press 'q' or 'Esc' to quit, 'Tab' / 'Shift+Tab' to switch targets"
+    } else {
+        "Press 'q' or 'Esc' to quit"
+    };
+    let footer = Paragraph::new(help)
         .style(Style::default().fg(Color::Gray))
         .block(Block::default().borders(Borders::ALL).title("Help"));
     f.render_widget(footer, chunks[table_chunk_index + 1]);
 }
 
+/// ヘッダーのタイトル（抽出条件とソート順）
+fn header_title(query: &ProcessQuery) -> String {
+    if let Some(min_mb) = query.min_memory_mb {
+        format!(
+            "Process Monitor: '{}' (>= {} MB {}) | Sort: {:?}",
+            query.matcher,
+            min_mb,
+            query.memory_metric.label(),
+            query.sort_order
+        )
+    } else {
+        format!(
+            "Process Monitor: '{}' | Sort: {:?}",
+            query.matcher, query.sort_order
+        )
+    }
+}
+
+/// プロセスノードをテーブルの行に変換
+fn node_row(node: &ProcessTreeNode, name_display: String, show_smaps: bool) -> Row<'static> {
+    let mut cells = vec![
+        Cell::from(format!("{}", node.pid)),
+        Cell::from(name_display),
+        Cell::from(format!("{}", node.thread_count)),
+        Cell::from(format!("{:.2}", node.cpu_usage)),
+        Cell::from(format_bytes(node.memory_bytes)),
+        Cell::from(format_status(node.status)),
+    ];
+    if show_smaps {
+        let smaps = node.smaps.as_ref();
+        cells.extend([
+            Cell::from(format_optional_bytes(smaps.map(|m| m.pss))),
+            Cell::from(format_optional_bytes(smaps.map(|m| m.uss))),
+            Cell::from(format_optional_bytes(smaps.map(|m| m.shared()))),
+            Cell::from(format_optional_bytes(smaps.map(|m| m.swap))),
+        ]);
+    }
+    Row::new(cells).height(1)
+}
+
 /// グラフセクションをレンダリング
 fn render_graphs(f: &mut Frame, graph: &GraphData, area: Rect) {
     // データポイントが不足している場合